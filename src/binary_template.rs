@@ -0,0 +1,1017 @@
+use std::{collections::HashMap, fmt::Write, iter::Peekable, str::Chars};
+
+use crate::{
+    expr,
+    structure::{Definition, Expr, Field, Struct},
+    value::Endianness,
+};
+
+/// Operators, longest first so that `==` isn't read as two `=`.
+const OPERATORS: [&str; 41] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<<", ">>", "++", "--", "+=", "-=", "*=", "/=", "->", "::",
+    "{", "}", "[", "]", "(", ")", ";", ",", "<", ">", "=", "+", "-", "*", "/", "%", "!", "&", "|",
+    "^", "~", "?", ":", ".", "@",
+];
+
+/// Functions that only show or check things and don't change what is read, which are skipped.
+const IGNORED_FUNCTIONS: [&str; 12] = [
+    "Assert",
+    "DisplayFormatBinary",
+    "DisplayFormatDecimal",
+    "DisplayFormatHex",
+    "DisplayFormatOctal",
+    "Printf",
+    "RequiresFile",
+    "RequiresVersion",
+    "SetBackColor",
+    "SetColor",
+    "SetForeColor",
+    "Warning",
+];
+
+/// The structure types of the basic types of 010 Editor.
+const TYPES: [(&str, &str); 49] = [
+    ("char", "char"),
+    ("CHAR", "char"),
+    ("byte", "i8"),
+    ("BYTE", "i8"),
+    ("int8", "i8"),
+    ("INT8", "i8"),
+    ("uchar", "u8"),
+    ("UCHAR", "u8"),
+    ("ubyte", "u8"),
+    ("UBYTE", "u8"),
+    ("uint8", "u8"),
+    ("UINT8", "u8"),
+    ("short", "i16"),
+    ("SHORT", "i16"),
+    ("int16", "i16"),
+    ("INT16", "i16"),
+    ("ushort", "u16"),
+    ("USHORT", "u16"),
+    ("uint16", "u16"),
+    ("UINT16", "u16"),
+    ("WORD", "u16"),
+    ("int", "i32"),
+    ("INT", "i32"),
+    ("int32", "i32"),
+    ("INT32", "i32"),
+    ("long", "i32"),
+    ("LONG", "i32"),
+    ("uint", "u32"),
+    ("UINT", "u32"),
+    ("uint32", "u32"),
+    ("UINT32", "u32"),
+    ("ulong", "u32"),
+    ("ULONG", "u32"),
+    ("DWORD", "u32"),
+    ("int64", "i64"),
+    ("INT64", "i64"),
+    ("quad", "i64"),
+    ("QUAD", "i64"),
+    ("__int64", "i64"),
+    ("uint64", "u64"),
+    ("UINT64", "u64"),
+    ("uquad", "u64"),
+    ("UQUAD", "u64"),
+    ("QWORD", "u64"),
+    ("__uint64", "u64"),
+    ("float", "f32"),
+    ("FLOAT", "f32"),
+    ("double", "f64"),
+    ("DOUBLE", "f64"),
+];
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(u64),
+    Str,
+    Operator(&'static str),
+}
+
+/// Tokens along with their line numbers.
+type Tokens = Vec<(Token, usize)>;
+
+/// A type fields can be declared with.
+#[derive(Clone)]
+struct Type {
+    /// The structure type.
+    ty: String,
+    /// The length of an array type declared with `typedef`.
+    count: Option<Expr>,
+    /// The names of the values of an enum.
+    values: HashMap<String, String>,
+}
+
+/// Reads an 010 Editor binary template (`.bt`) into a structure definition. Structs, enums and `typedef`s become
+/// structures and types, variables of the basic types and of those become fields, and arrays, `local` and `const`
+/// variables and `#define`s used as constants, `if` and `else`, `LittleEndian()`, `BigEndian()` and `FSeek()` are
+/// supported. Anything else that changes what is read, like loops or assignments, is an error.
+pub fn parse(source: &str) -> Result<Definition, String> {
+    let (tokens, defines) = tokenize(source)?;
+    let mut parser = Parser {
+        tokens,
+        ..Parser::default()
+    };
+
+    for (name, tokens, line) in defines {
+        let mut define = Parser {
+            tokens,
+            constants: parser.constants.clone(),
+            ..Parser::default()
+        };
+        let value = define
+            .expr(&[])
+            .map_err(|e| format!("line {}: {}", line, e))?;
+        parser.constants.insert(name, value.into_text());
+    }
+
+    let fields = parser.statements(false)?;
+
+    Ok(Definition {
+        endian: None,
+        fields,
+        structs: parser.structs,
+    })
+}
+
+#[derive(Default)]
+struct Parser {
+    tokens: Tokens,
+    i: usize,
+    /// The expressions of constants and the values of enum names by name.
+    constants: HashMap<String, String>,
+    /// The types declared with `typedef`, and the structs and enums, by name.
+    types: HashMap<String, Type>,
+    structs: HashMap<String, Struct>,
+    /// The byte order set last with `LittleEndian()` or `BigEndian()`.
+    endian: Option<Endianness>,
+    /// Where the next field starts, set by `FSeek()`.
+    offset: Option<Expr>,
+    /// The conditions of the `if`s the statements are inside.
+    conditions: Vec<String>,
+    /// How many anonymous structs have been named.
+    anonymous: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.i).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.i += 1;
+        token
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.i)
+            .or(self.tokens.last())
+            .map_or(0, |&(_, line)| line)
+    }
+
+    fn error(&self, message: impl AsRef<str>) -> String {
+        format!("line {}: {}", self.line(), message.as_ref())
+    }
+
+    fn is(&self, operator: &str) -> bool {
+        matches!(self.peek(), Some(Token::Operator(o)) if *o == operator)
+    }
+
+    fn is_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w == word)
+    }
+
+    fn expect(&mut self, operator: &str) -> Result<(), String> {
+        match self.is(operator) {
+            true => {
+                self.i += 1;
+                Ok(())
+            }
+            false => Err(self.error(format!("expected '{}'", operator))),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Word(word)) => {
+                let word = word.clone();
+                self.i += 1;
+                Ok(word)
+            }
+            _ => Err(self.error("expected a name")),
+        }
+    }
+
+    /// Reads statements up to the end of the template, or up to a `}` if `block` is set, returning their fields.
+    fn statements(&mut self, block: bool) -> Result<Vec<Field>, String> {
+        let mut fields = Vec::new();
+
+        loop {
+            match self.peek() {
+                None if block => return Err(self.error("expected '}'")),
+                None => return Ok(fields),
+                Some(Token::Operator("}")) if block => return Ok(fields),
+                Some(_) => fields.extend(self.statement()?),
+            }
+        }
+    }
+
+    fn statement(&mut self) -> Result<Vec<Field>, String> {
+        let word = match self.peek() {
+            Some(Token::Operator(";")) => {
+                self.i += 1;
+                return Ok(Vec::new());
+            }
+            Some(Token::Operator("{")) => {
+                self.i += 1;
+                let fields = self.statements(true)?;
+                self.expect("}")?;
+                return Ok(fields);
+            }
+            Some(Token::Word(word)) => word.clone(),
+            _ => return Err(self.error("expected a statement")),
+        };
+
+        match word.as_str() {
+            "typedef" => {
+                self.i += 1;
+                self.typedef()?;
+                Ok(Vec::new())
+            }
+            "local" | "const" => {
+                self.i += 1;
+                self.constants()?;
+                Ok(Vec::new())
+            }
+            "if" => self.if_statement(),
+            "while" | "for" | "do" | "switch" | "return" | "break" | "continue" | "union" => {
+                Err(self.error(format!("{} isn't supported", word)))
+            }
+            "struct" | "enum" if self.defines_type() => {
+                let ty = self.type_definition(None)?;
+                self.declarations(&ty)
+            }
+            _ if self.tokens.get(self.i + 1).map(|(token, _)| token)
+                == Some(&Token::Operator("(")) =>
+            {
+                self.call(&word)?;
+                Ok(Vec::new())
+            }
+            _ if self.tokens.get(self.i + 1).map(|(token, _)| token)
+                == Some(&Token::Operator("=")) =>
+            {
+                Err(self.error("assignments aren't supported"))
+            }
+            _ => {
+                let ty = self.ty()?;
+                self.declarations(&ty)
+            }
+        }
+    }
+
+    /// Returns whether the `struct` or `enum` at the current token is followed by a definition rather than the name of
+    /// one defined before.
+    fn defines_type(&self) -> bool {
+        self.tokens[self.i + 1..]
+            .iter()
+            .take(6)
+            .map(|(token, _)| token)
+            .take_while(|&token| *token != Token::Operator(";"))
+            .any(|token| *token == Token::Operator("{"))
+    }
+
+    /// Reads a `typedef` of a struct, an enum or another type, possibly as an array.
+    fn typedef(&mut self) -> Result<(), String> {
+        if (self.is_word("struct") || self.is_word("enum")) && self.defines_type() {
+            let start = self.i;
+            // the name comes after the definition, so the definition is read again once it is known
+            self.skip_definition()?;
+            let name = self.name()?;
+            self.i = start;
+            self.type_definition(Some(&name))?;
+            self.name()?;
+        } else {
+            let ty = self.ty()?;
+            let name = self.name()?;
+            let count = match self.is("[") {
+                true => Some(self.array()?),
+                false => None,
+            };
+
+            if ty.count.is_some() && count.is_some() {
+                return Err(self.error("arrays of arrays aren't supported"));
+            }
+
+            self.types.insert(
+                name,
+                Type {
+                    count: count.or(ty.count.clone()),
+                    ..ty
+                },
+            );
+        }
+
+        self.expect(";")
+    }
+
+    /// Skips a struct or enum definition up to its closing brace.
+    fn skip_definition(&mut self) -> Result<(), String> {
+        while !self.is("{") {
+            self.next().ok_or_else(|| self.error("expected '{'"))?;
+        }
+
+        let mut depth = 0;
+
+        loop {
+            match self.next() {
+                Some(Token::Operator("{")) => depth += 1,
+                Some(Token::Operator("}")) if depth == 1 => return Ok(()),
+                Some(Token::Operator("}")) => depth -= 1,
+                Some(_) => (),
+                None => return Err(self.error("expected '}'")),
+            }
+        }
+    }
+
+    /// Reads a struct or enum definition and returns its type, which is known by `name` if given or else by the name
+    /// after `struct` or `enum`.
+    fn type_definition(&mut self, name: Option<&str>) -> Result<Type, String> {
+        let kind = self.name()?;
+
+        let base = match (kind.as_str(), self.is("<")) {
+            ("enum", true) => {
+                self.i += 1;
+                let base = self.ty()?;
+                self.expect(">")?;
+                base
+            }
+            ("enum", false) => basic("int"),
+            _ => basic(""),
+        };
+
+        let tag = match self.peek() {
+            Some(Token::Word(_)) => Some(self.name()?),
+            _ => None,
+        };
+        let name = match (name, &tag) {
+            (Some(name), _) => name.to_owned(),
+            (None, Some(tag)) => tag.clone(),
+            (None, None) => {
+                self.anonymous += 1;
+                format!("anonymous{}", self.anonymous)
+            }
+        };
+
+        if self.is("(") {
+            return Err(self.error("parameters aren't supported"));
+        }
+
+        self.expect("{")?;
+
+        let ty = if kind == "enum" {
+            Type {
+                values: self.enumerators()?,
+                ..base
+            }
+        } else {
+            // a struct reads its own fields, whatever the statements around its definition are
+            let conditions = std::mem::take(&mut self.conditions);
+            let offset = self.offset.take();
+            let fields = self.statements(true)?;
+            self.conditions = conditions;
+            self.offset = offset;
+
+            self.structs.insert(
+                name.clone(),
+                Struct {
+                    endian: None,
+                    fields,
+                },
+            );
+            basic(&name)
+        };
+
+        self.expect("}")?;
+
+        for name in [Some(name), tag].into_iter().flatten() {
+            self.types.insert(name, ty.clone());
+        }
+
+        Ok(ty)
+    }
+
+    /// Reads the names of an enum up to its closing brace, each one more than the one before unless given a value.
+    /// The names can be used in expressions.
+    fn enumerators(&mut self) -> Result<HashMap<String, String>, String> {
+        let mut values = HashMap::new();
+        let mut next = 0;
+
+        while !self.is("}") {
+            let name = self.name()?;
+
+            if self.is("=") {
+                self.i += 1;
+                let value = self.expr(&[",", "}"])?.into_text();
+                next = expr::evaluate(&value, &|_| None).map_err(|e| self.error(e))?;
+            }
+
+            let value = format!("{:#x}", next);
+            self.constants.insert(name.clone(), value.clone());
+            values.insert(value, name);
+            next += 1;
+
+            if !self.is("}") {
+                self.expect(",")?;
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Reads a type, which is a basic type, possibly written the C way like `unsigned short`, or a type declared
+    /// before, possibly after `struct` or `enum`.
+    fn ty(&mut self) -> Result<Type, String> {
+        const C_WORDS: [&str; 6] = ["unsigned", "signed", "long", "short", "int", "char"];
+
+        if self.is_word("struct") || self.is_word("enum") {
+            self.i += 1;
+        }
+
+        let mut words = Vec::new();
+        while let Some(Token::Word(word)) = self.peek() {
+            if !C_WORDS.contains(&word.as_str()) {
+                break;
+            }
+
+            words.push(word.clone());
+            self.i += 1;
+        }
+
+        let ty = match words.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] => {
+                let name = self.name()?;
+                return match TYPES.iter().find(|(basic, _)| *basic == name) {
+                    Some((_, ty)) => Ok(basic(ty)),
+                    None => self
+                        .types
+                        .get(&name)
+                        .cloned()
+                        .ok_or_else(|| self.error(format!("unknown type {}", name))),
+                };
+            }
+            ["char"] => "char",
+            ["signed", "char"] => "i8",
+            ["unsigned", "char"] => "u8",
+            ["short"] | ["short", "int"] | ["signed", "short"] => "i16",
+            ["unsigned", "short"] | ["unsigned", "short", "int"] => "u16",
+            ["int"] | ["signed"] | ["signed", "int"] | ["long"] | ["long", "int"] => "i32",
+            ["unsigned"] | ["unsigned", "int"] | ["unsigned", "long"] => "u32",
+            ["long", "long"] | ["signed", "long", "long"] => "i64",
+            ["unsigned", "long", "long"] => "u64",
+            _ => return Err(self.error(format!("unknown type {}", words.join(" ")))),
+        };
+
+        Ok(basic(ty))
+    }
+
+    /// Reads the variables declared with `ty` up to the `;`, returning their fields.
+    fn declarations(&mut self, ty: &Type) -> Result<Vec<Field>, String> {
+        let mut fields = Vec::new();
+
+        // a struct or enum can be defined without declaring any variables
+        while !self.is(";") {
+            let name = self.name()?;
+            let count = match self.is("[") {
+                true => Some(self.array()?),
+                false => None,
+            };
+
+            if self.is(":") {
+                return Err(self.error("bitfields aren't supported"));
+            }
+
+            if self.is("=") {
+                return Err(self.error("only local and const variables can have a value"));
+            }
+
+            if self.is("<") {
+                self.skip_attributes()?;
+            }
+
+            if ty.count.is_some() && count.is_some() {
+                return Err(self.error("arrays of arrays aren't supported"));
+            }
+
+            let condition = match self.conditions.is_empty() {
+                true => None,
+                false => Some(self.conditions.join(" * ")),
+            };
+
+            fields.push(Field {
+                name,
+                ty: ty.ty.clone(),
+                count: count.or(ty.count.clone()),
+                offset: self.offset.take(),
+                size: None,
+                condition,
+                endian: self.endian,
+                values: ty.values.clone(),
+            });
+
+            if !self.is(";") {
+                self.expect(",")?;
+            }
+        }
+
+        self.expect(";")?;
+        Ok(fields)
+    }
+
+    /// Reads the length of an array in brackets.
+    fn array(&mut self) -> Result<Expr, String> {
+        self.expect("[")?;
+
+        if self.is("]") {
+            return Err(self.error("arrays without a length aren't supported"));
+        }
+
+        let count = self.expr(&["]"])?;
+        self.expect("]")?;
+        Ok(count)
+    }
+
+    /// Skips the attributes of a variable in angle brackets, like `<format=hex>`.
+    fn skip_attributes(&mut self) -> Result<(), String> {
+        self.expect("<")?;
+
+        loop {
+            match self.next() {
+                Some(Token::Operator(">")) => return Ok(()),
+                Some(_) => (),
+                None => return Err(self.error("expected '>'")),
+            }
+        }
+    }
+
+    /// Reads `local` or `const` variables, whose values are used in place of their names in later expressions. Their
+    /// values can't change.
+    fn constants(&mut self) -> Result<(), String> {
+        self.ty()?;
+
+        loop {
+            let name = self.name()?;
+
+            if self.is("[") {
+                return Err(self.error("local arrays aren't supported"));
+            }
+
+            let value = match self.is("=") {
+                true => {
+                    self.i += 1;
+                    self.expr(&[",", ";"])?.into_text()
+                }
+                false => String::from("0"),
+            };
+            self.constants.insert(name, value);
+
+            if self.is(";") {
+                self.i += 1;
+                return Ok(());
+            }
+
+            self.expect(",")?;
+        }
+    }
+
+    /// Reads an `if` and its `else` if it has one, which become conditions of the fields inside them.
+    fn if_statement(&mut self) -> Result<Vec<Field>, String> {
+        self.i += 1;
+        self.expect("(")?;
+        let condition = self.expr(&[")"])?.into_text();
+        self.expect(")")?;
+
+        // comparisons bind less tightly than arithmetic, so each is in parentheses to be multiplied
+        self.conditions.push(format!("(({}) != 0)", condition));
+        let mut fields = self.statement();
+        self.conditions.pop();
+
+        if self.is_word("else") {
+            self.i += 1;
+            self.conditions.push(format!("(({}) == 0)", condition));
+            let else_fields = self.statement();
+            self.conditions.pop();
+
+            fields = fields.and_then(|mut fields| {
+                fields.extend(else_fields?);
+                Ok(fields)
+            });
+        }
+
+        fields
+    }
+
+    /// Reads a call of the function `name` as a statement.
+    fn call(&mut self, name: &str) -> Result<(), String> {
+        self.i += 2;
+
+        match name {
+            "LittleEndian" => self.endian = Some(Endianness::Little),
+            "BigEndian" => self.endian = Some(Endianness::Big),
+            "FSeek" => self.offset = Some(self.expr(&[")"])?),
+            _ if IGNORED_FUNCTIONS.contains(&name) => {
+                let mut depth = 1;
+
+                while depth > 0 {
+                    match self.next() {
+                        Some(Token::Operator("(")) => depth += 1,
+                        Some(Token::Operator(")")) => depth -= 1,
+                        Some(_) => (),
+                        None => return Err(self.error("expected ')'")),
+                    }
+                }
+
+                return self.expect(";");
+            }
+            _ => return Err(self.error(format!("{} isn't supported", name))),
+        }
+
+        self.expect(")")?;
+        self.expect(";")
+    }
+
+    /// Reads an expression up to one of `ends` outside parentheses and brackets, or up to the end of the tokens, and
+    /// translates it into a goto expression. Numbers are written in hex, constants are replaced by their values, and
+    /// paths to fields like `header.length` by the name of the field, since expressions know the fields read last by
+    /// their names.
+    fn expr(&mut self, ends: &[&str]) -> Result<Expr, String> {
+        let start = self.i;
+        let mut text = String::new();
+        let mut depth = 0;
+
+        while let Some(token) = self.peek().cloned() {
+            match token {
+                Token::Operator(operator) if depth == 0 && ends.contains(&operator) => break,
+                Token::Operator(operator @ ("(" | "[")) => {
+                    depth += 1;
+                    text.push_str(operator);
+                }
+                Token::Operator(operator @ (")" | "]")) => {
+                    if depth == 0 {
+                        return Err(self.error(format!("unexpected '{}'", operator)));
+                    }
+
+                    depth -= 1;
+                    text.push_str(operator);
+                }
+                Token::Operator(
+                    operator
+                    @ ("+" | "-" | "*" | "/" | "%" | "==" | "!=" | "<" | "<=" | ">" | ">="),
+                ) => text.push_str(operator),
+                Token::Operator(operator) => {
+                    return Err(self.error(format!("{} isn't supported in expressions", operator)))
+                }
+                Token::Number(number) => write!(text, "{:#x}", number).unwrap(),
+                Token::Str => return Err(self.error("strings aren't supported in expressions")),
+                Token::Word(word) => {
+                    self.i += 1;
+
+                    if self.is("(") {
+                        return Err(self.error(format!("{} isn't supported in expressions", word)));
+                    }
+
+                    if self.is(".") {
+                        let mut name = word;
+                        while self.is(".") {
+                            self.i += 1;
+                            name = self.name()?;
+                        }
+                        text.push_str(&name);
+                    } else {
+                        match (word.as_str(), self.constants.get(&word)) {
+                            ("true", _) => text.push('1'),
+                            ("false", _) => text.push('0'),
+                            (_, Some(value)) => write!(text, "({})", value).unwrap(),
+                            (_, None) => text.push_str(&word),
+                        }
+                    }
+
+                    text.push(' ');
+                    continue;
+                }
+            }
+
+            text.push(' ');
+            self.i += 1;
+        }
+
+        match self.tokens[start..self.i] {
+            [(Token::Number(number), _)] => usize::try_from(number)
+                .map(Expr::Number)
+                .map_err(|_| self.error("number out of range")),
+            [] => Err(self.error("expected an expression")),
+            _ => Ok(Expr::Text(text.trim_end().to_owned())),
+        }
+    }
+}
+
+fn basic(ty: &str) -> Type {
+    Type {
+        ty: ty.to_owned(),
+        count: None,
+        values: HashMap::new(),
+    }
+}
+
+/// A `#define` of a name, with the tokens of its value and its line number.
+type Define = (String, Tokens, usize);
+
+/// Splits `source` into tokens along with their line numbers, leaving out comments, and returns the `#define`s apart.
+fn tokenize(source: &str) -> Result<(Tokens, Vec<Define>), String> {
+    let mut tokens = Vec::new();
+    let mut defines = Vec::new();
+
+    for (number, line) in logical_lines(source)? {
+        let trimmed = line.trim_start();
+
+        if let Some(directive) = trimmed.strip_prefix('#') {
+            let mut words = directive.trim_start().splitn(3, char::is_whitespace);
+
+            match words.next() {
+                Some("define") => {
+                    let name = words
+                        .next()
+                        .filter(|name| !name.contains('('))
+                        .ok_or_else(|| format!("line {}: only constants can be defined", number))?;
+                    let value = tokenize_line(words.next().unwrap_or("0"), number)?;
+                    defines.push((name.to_owned(), value, number));
+                }
+                Some("pragma" | "ifndef" | "endif") => (),
+                Some(directive) => {
+                    return Err(format!("line {}: #{} isn't supported", number, directive))
+                }
+                None => (),
+            }
+
+            continue;
+        }
+
+        tokens.extend(tokenize_line(&line, number)?);
+    }
+
+    Ok((tokens, defines))
+}
+
+/// Splits `source` into lines without comments, each with its line number. A block comment spanning lines is left out
+/// of both of the lines it starts and ends in.
+fn logical_lines(source: &str) -> Result<Vec<(usize, String)>, String> {
+    let mut lines = Vec::new();
+    let mut in_comment = None;
+
+    for (i, line) in source.lines().enumerate() {
+        let mut text = String::new();
+        let mut chars = line.chars().peekable();
+        let mut quote = None;
+
+        while let Some(c) = chars.next() {
+            if in_comment.is_some() {
+                if c == '*' && chars.next_if_eq(&'/').is_some() {
+                    in_comment = None;
+                    text.push(' ');
+                }
+                continue;
+            }
+
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), '\\') => {
+                    text.push(c);
+                    if let Some(c) = chars.next() {
+                        text.push(c);
+                    }
+                    continue;
+                }
+                (Some(_), _) => (),
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '/') if chars.next_if_eq(&'/').is_some() => break,
+                (None, '/') if chars.next_if_eq(&'*').is_some() => {
+                    in_comment = Some(i + 1);
+                    continue;
+                }
+                (None, _) => (),
+            }
+
+            text.push(c);
+        }
+
+        lines.push((i + 1, text));
+    }
+
+    match in_comment {
+        Some(line) => Err(format!("line {}: unterminated comment", line)),
+        None => Ok(lines),
+    }
+}
+
+fn tokenize_line(line: &str, number: usize) -> Result<Tokens, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    let error = |message: String| format!("line {}: {}", number, message);
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let token = if c.is_alphanumeric() || c == '_' {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|&c| c.is_alphanumeric() || c == '_') {
+                word.push(c);
+            }
+
+            match c.is_ascii_digit() {
+                true => Token::Number(
+                    c_number(&word).ok_or_else(|| error(format!("invalid number {}", word)))?,
+                ),
+                false => Token::Word(word),
+            }
+        } else if c == '"' {
+            chars.next();
+            quoted(&mut chars, '"').ok_or_else(|| error(String::from("unterminated string")))?;
+            Token::Str
+        } else if c == '\'' {
+            chars.next();
+            let text = quoted(&mut chars, '\'')
+                .ok_or_else(|| error(String::from("unterminated character")))?;
+
+            // a character constant is the value of its bytes
+            let bytes = text.as_bytes();
+            if bytes.is_empty() || bytes.len() > 8 {
+                return Err(error(format!("invalid character constant '{}'", text)));
+            }
+            Token::Number(
+                bytes
+                    .iter()
+                    .fold(0, |value, &byte| value << 8 | u64::from(byte)),
+            )
+        } else {
+            let rest: String = chars.clone().take(2).collect();
+            let operator = OPERATORS
+                .iter()
+                .find(|operator| rest.starts_with(**operator))
+                .ok_or_else(|| error(format!("unexpected '{}'", c)))?;
+
+            for _ in 0..operator.len() {
+                chars.next();
+            }
+            Token::Operator(operator)
+        };
+
+        tokens.push((token, number));
+    }
+
+    Ok(tokens)
+}
+
+/// Reads a string or character constant up to `quote`, replacing simple escapes.
+fn quoted(chars: &mut Peekable<Chars>, quote: char) -> Option<String> {
+    let mut text = String::new();
+
+    loop {
+        match chars.next()? {
+            c if c == quote => return Some(text),
+            '\\' => text.push(match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                c => c,
+            }),
+            c => text.push(c),
+        }
+    }
+}
+
+/// Parses a C integer constant: decimal, hex with `0x` or octal with a leading 0, with an optional `u` or `l` suffix.
+fn c_number(word: &str) -> Option<u64> {
+    let word = word.trim_end_matches(['u', 'U', 'l', 'L']);
+
+    if let Some(hex) = word.strip_prefix("0x").or(word.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else if word.len() > 1 && word.starts_with('0') {
+        u64::from_str_radix(&word[1..], 8).ok()
+    } else {
+        word.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `source` and applies it to `buffer`, returning the path of each field read followed by its value if it
+    /// has one.
+    fn fields(source: &str, buffer: &[u8]) -> Vec<String> {
+        let structure = parse(source)
+            .unwrap()
+            .apply("test", buffer, 0, Endianness::Little);
+        assert_eq!(structure.error, None);
+
+        structure
+            .nodes
+            .into_iter()
+            .map(|node| match node.value {
+                Some(value) => format!("{} = {}", node.path, value),
+                None => node.path,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_variables_in_their_byte_order() {
+        let source = "
+            // a comment
+            char magic[2];
+            BigEndian();
+            ushort length;
+            LittleEndian();
+            /* another */
+            DWORD flags;
+        ";
+        let buffer = [b'B', b'M', 0x01, 0x02, 0x03, 0x00, 0x00, 0x00];
+
+        assert_eq!(
+            fields(source, &buffer),
+            ["magic = \"BM\"", "length = 258 (0x102)", "flags = 3 (0x3)"]
+        );
+    }
+
+    #[test]
+    fn reads_structs_enums_and_conditions() {
+        let source = "
+            #define COUNT 2
+            typedef enum <uchar> { ONE = 1, TWO } KIND;
+            typedef struct {
+                byte x;
+                byte y;
+            } POINT;
+
+            KIND kind;
+            POINT points[COUNT];
+            if (kind == TWO) {
+                uchar extra;
+            } else {
+                ushort other;
+            }
+            FSeek(0);
+            uchar first;
+        ";
+        let buffer = [2, 1, 0xff, 3, 4, 5];
+
+        assert_eq!(
+            fields(source, &buffer),
+            [
+                "kind = 2 (0x2) TWO",
+                "points",
+                "points[0]",
+                "points[0].x = 1",
+                "points[0].y = -1",
+                "points[1]",
+                "points[1].x = 3",
+                "points[1].y = 4",
+                "extra = 5 (0x5)",
+                "first = 2 (0x2)",
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_templates_are_errors() {
+        let error = |source: &str| parse(source).err();
+
+        assert_eq!(
+            error("uchar a;\nwhile (a) {}\n"),
+            Some(String::from("line 2: while isn't supported"))
+        );
+        assert_eq!(
+            error("uchar a;\na = 1;\n"),
+            Some(String::from("line 2: assignments aren't supported"))
+        );
+        assert_eq!(
+            error("#include \"other.bt\"\n"),
+            Some(String::from("line 1: #include isn't supported"))
+        );
+        assert_eq!(
+            error("uchar a\n"),
+            Some(String::from("line 1: expected ','"))
+        );
+        assert_eq!(
+            error("/* uchar a;\n"),
+            Some(String::from("line 1: unterminated comment"))
+        );
+    }
+}
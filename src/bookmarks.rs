@@ -0,0 +1,408 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    ops::Range,
+};
+
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::highlight::parse_color;
+
+/// The color of annotations that aren't given one.
+pub const DEFAULT_ANNOTATION_COLOR: &str = "dark-yellow";
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bookmark {
+    pub name: String,
+    pub offset: usize,
+}
+
+/// A comment on a range of bytes, which is highlighted in its color.
+pub struct Annotation {
+    pub range: Range<usize>,
+    pub color: Color,
+    /// The color as written by the user, to save it the same way.
+    pub color_name: String,
+    pub comment: String,
+}
+
+/// An annotation as written in exported JSON, covering the bytes from `start` up to `end`.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnnotationRecord {
+    start: usize,
+    end: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    #[serde(default)]
+    comment: String,
+}
+
+/// The bookmarks and annotations of a file as exported to JSON.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Export {
+    #[serde(default)]
+    bookmarks: Vec<Bookmark>,
+    #[serde(default)]
+    annotations: Vec<AnnotationRecord>,
+}
+
+/// Named offsets and annotated ranges in a file, persisted in a sidecar file next to it.
+pub struct Bookmarks {
+    bookmarks: Vec<Bookmark>,
+    annotations: Vec<Annotation>,
+    path: String,
+}
+
+impl Bookmarks {
+    /// No bookmarks or annotations, saved next to `filename`.
+    pub fn new(filename: &str) -> Bookmarks {
+        Bookmarks {
+            bookmarks: Vec::new(),
+            annotations: Vec::new(),
+            path: sidecar_path(filename),
+        }
+    }
+
+    pub fn load(filename: &str) -> Result<Bookmarks, io::Error> {
+        let mut bookmarks = Bookmarks::new(filename);
+        let contents = match fs::read_to_string(&bookmarks.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        for line in contents.lines() {
+            let mut parts = line.splitn(3, ' ');
+
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("bookmark"), Some(offset), Some(name)) => {
+                    if let Some(offset) = parse_offset(offset) {
+                        bookmarks.set(name, offset);
+                    }
+                }
+                // annotation <start> <end> <color> <comment>
+                (Some("annotation"), Some(start), Some(rest)) => {
+                    let mut parts = rest.splitn(3, ' ');
+
+                    if let (Some(start), Some(end), Some(color), Some(comment)) = (
+                        parse_offset(start),
+                        parts.next().and_then(parse_offset),
+                        parts.next(),
+                        parts.next(),
+                    ) {
+                        let _ = bookmarks.annotate(start..end, color, comment);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(bookmarks)
+    }
+
+    /// Writes the bookmarks and annotations to the sidecar file, or removes it if there are none left.
+    pub fn save(&self) -> Result<(), io::Error> {
+        if self.bookmarks.is_empty() && self.annotations.is_empty() {
+            return match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+
+        let contents = self
+            .bookmarks
+            .iter()
+            .map(|bookmark| format!("bookmark {:#x} {}\n", bookmark.offset, bookmark.name))
+            .chain(self.annotations.iter().map(|annotation| {
+                format!(
+                    "annotation {:#x} {:#x} {} {}\n",
+                    annotation.range.start,
+                    annotation.range.end,
+                    annotation.color_name,
+                    annotation.comment
+                )
+            }))
+            .collect::<String>();
+
+        fs::write(&self.path, contents)
+    }
+
+    /// Sets the bookmark `name` to `offset`, replacing any existing bookmark with the same name.
+    pub fn set(&mut self, name: &str, offset: usize) {
+        self.bookmarks.retain(|bookmark| bookmark.name != name);
+
+        let i = self
+            .bookmarks
+            .partition_point(|bookmark| bookmark.offset <= offset);
+        self.bookmarks.insert(
+            i,
+            Bookmark {
+                name: name.to_owned(),
+                offset,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, i: usize) {
+        self.bookmarks.remove(i);
+    }
+
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.bookmarks
+            .iter()
+            .find(|bookmark| bookmark.name == name)
+            .map(|bookmark| bookmark.offset)
+    }
+
+    /// The bookmarks, sorted by offset.
+    pub fn list(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    /// Adds an annotation of `range` in the color named `color`, like `dark-blue` or `#ff8000`. Comments are kept on a
+    /// single line.
+    pub fn annotate(
+        &mut self,
+        range: Range<usize>,
+        color: &str,
+        comment: &str,
+    ) -> Result<(), String> {
+        if range.is_empty() {
+            return Err(format!(
+                "empty range: {:#x} to {:#x}",
+                range.start, range.end
+            ));
+        }
+
+        let annotation = Annotation {
+            range,
+            color: parse_color(color).ok_or_else(|| format!("invalid color: {}", color))?,
+            color_name: color.to_owned(),
+            comment: comment.replace(['\n', '\r'], " "),
+        };
+
+        let i = self
+            .annotations
+            .partition_point(|other| other.range.start <= annotation.range.start);
+        self.annotations.insert(i, annotation);
+
+        Ok(())
+    }
+
+    pub fn remove_annotation(&mut self, i: usize) {
+        self.annotations.remove(i);
+    }
+
+    /// The annotations, sorted by the start of their range.
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    /// Returns the index of the innermost annotation of `offset`, the shortest one if several overlap there.
+    pub fn annotation_at(&self, offset: usize) -> Option<usize> {
+        self.annotations
+            .iter()
+            .enumerate()
+            .filter(|(_, annotation)| annotation.range.contains(&offset))
+            .min_by_key(|(_, annotation)| annotation.range.len())
+            .map(|(i, _)| i)
+    }
+
+    /// Returns the first offset after `offset` where an annotation starts.
+    pub fn next_annotation_start(&self, offset: usize) -> Option<usize> {
+        let i = self
+            .annotations
+            .partition_point(|annotation| annotation.range.start <= offset);
+
+        self.annotations
+            .get(i)
+            .map(|annotation| annotation.range.start)
+    }
+
+    /// Returns the bookmarks and annotations as JSON, to be shared or read by other tools.
+    pub fn to_json(&self) -> String {
+        let export = Export {
+            bookmarks: self.bookmarks.clone(),
+            annotations: self
+                .annotations
+                .iter()
+                .map(|annotation| AnnotationRecord {
+                    start: annotation.range.start,
+                    end: annotation.range.end,
+                    color: Some(annotation.color_name.clone()),
+                    comment: annotation.comment.clone(),
+                })
+                .collect(),
+        };
+
+        serde_json::to_string_pretty(&export).unwrap() + "\n"
+    }
+
+    /// Adds the bookmarks and annotations in `json`, written like `to_json` writes them, and returns how many of each
+    /// there were. Bookmarks replace those with the same name, and annotations already there aren't added again.
+    /// Nothing is added if any of them is invalid.
+    pub fn import_json(&mut self, json: &str) -> Result<(usize, usize), String> {
+        let export: Export = serde_json::from_str(json).map_err(|e| e.to_string())?;
+
+        for record in &export.annotations {
+            if record.start >= record.end {
+                return Err(format!(
+                    "empty range: {:#x} to {:#x}",
+                    record.start, record.end
+                ));
+            }
+
+            if let Some(color) = record
+                .color
+                .as_deref()
+                .filter(|&color| parse_color(color).is_none())
+            {
+                return Err(format!("invalid color: {}", color));
+            }
+        }
+
+        for bookmark in &export.bookmarks {
+            self.set(&bookmark.name, bookmark.offset);
+        }
+
+        for record in &export.annotations {
+            let duplicate = self.annotations.iter().any(|annotation| {
+                annotation.range == (record.start..record.end)
+                    && annotation.comment == record.comment
+            });
+
+            if !duplicate {
+                let color = record.color.as_deref().unwrap_or(DEFAULT_ANNOTATION_COLOR);
+                self.annotate(record.start..record.end, color, &record.comment)?;
+            }
+        }
+
+        Ok((export.bookmarks.len(), export.annotations.len()))
+    }
+}
+
+fn parse_offset(offset: &str) -> Option<usize> {
+    usize::from_str_radix(offset.strip_prefix("0x").unwrap_or(offset), 16).ok()
+}
+
+fn sidecar_path(filename: &str) -> String {
+    format!("{}.hexzen", filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::Path, process};
+
+    use super::*;
+
+    fn names(bookmarks: &Bookmarks) -> Vec<(&str, usize)> {
+        bookmarks
+            .list()
+            .iter()
+            .map(|bookmark| (bookmark.name.as_str(), bookmark.offset))
+            .collect()
+    }
+
+    fn ranges(bookmarks: &Bookmarks) -> Vec<(Range<usize>, &str, &str)> {
+        bookmarks
+            .annotations()
+            .iter()
+            .map(|annotation| {
+                (
+                    annotation.range.clone(),
+                    annotation.color_name.as_str(),
+                    annotation.comment.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bookmarks_are_sorted_and_replaced_by_name() {
+        let mut bookmarks = Bookmarks::new("file");
+        bookmarks.set("checksum", 0x40);
+        bookmarks.set("header", 0);
+        bookmarks.set("strings", 0x20);
+        bookmarks.set("checksum", 0x10);
+
+        assert_eq!(
+            names(&bookmarks),
+            [("header", 0), ("checksum", 0x10), ("strings", 0x20)]
+        );
+        assert_eq!(bookmarks.get("strings"), Some(0x20));
+        assert_eq!(bookmarks.get("nope"), None);
+    }
+
+    #[test]
+    fn saved_bookmarks_are_loaded() {
+        let filename = env::temp_dir()
+            .join(format!("hexzen-test-bookmarks-{}", process::id()))
+            .to_string_lossy()
+            .into_owned();
+
+        let mut bookmarks = Bookmarks::new(&filename);
+        bookmarks.set("string table", 0x1a0);
+        bookmarks
+            .annotate(4..8, "#ff8000", "length\nof the table")
+            .unwrap();
+        bookmarks.save().unwrap();
+
+        let loaded = Bookmarks::load(&filename).unwrap();
+        assert_eq!(names(&loaded), [("string table", 0x1a0)]);
+        assert_eq!(ranges(&loaded), [(4..8, "#ff8000", "length of the table")]);
+
+        // saving nothing removes the sidecar file
+        Bookmarks::new(&filename).save().unwrap();
+        assert!(!Path::new(&sidecar_path(&filename)).exists());
+    }
+
+    #[test]
+    fn exported_json_is_imported() {
+        let mut bookmarks = Bookmarks::new("file");
+        bookmarks.set("header", 0);
+        bookmarks.annotate(0..4, "dark-blue", "magic").unwrap();
+
+        let mut imported = Bookmarks::new("other");
+        assert_eq!(imported.import_json(&bookmarks.to_json()), Ok((1, 1)));
+        assert_eq!(names(&imported), [("header", 0)]);
+        assert_eq!(ranges(&imported), [(0..4, "dark-blue", "magic")]);
+
+        // annotations already there aren't added again
+        assert_eq!(imported.import_json(&bookmarks.to_json()), Ok((1, 1)));
+        assert_eq!(imported.annotations().len(), 1);
+
+        // colors default when they aren't given
+        let json = r#"{"annotations": [{"start": 8, "end": 9}]}"#;
+        assert_eq!(imported.import_json(json), Ok((0, 1)));
+        assert_eq!(
+            imported.annotations()[1].color_name,
+            DEFAULT_ANNOTATION_COLOR
+        );
+    }
+
+    #[test]
+    fn invalid_json_imports_nothing() {
+        let mut bookmarks = Bookmarks::new("file");
+        let json = r#"{
+            "bookmarks": [{"name": "header", "offset": 0}],
+            "annotations": [{"start": 4, "end": 4}]
+        }"#;
+
+        assert_eq!(
+            bookmarks.import_json(json),
+            Err(String::from("empty range: 0x4 to 0x4"))
+        );
+        assert!(bookmarks.list().is_empty());
+
+        let json = r#"{"annotations": [{"start": 0, "end": 4, "color": "nope"}]}"#;
+        assert_eq!(
+            bookmarks.import_json(json),
+            Err(String::from("invalid color: nope"))
+        );
+        assert!(bookmarks.import_json("{\"marks\": []}").is_err());
+    }
+}
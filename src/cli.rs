@@ -0,0 +1,982 @@
+use std::{
+    char::REPLACEMENT_CHARACTER,
+    env,
+    fs::{self, File},
+    io::{self, BufWriter, IsTerminal, Write},
+    process,
+};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use clap::{
+    builder::{PossibleValuesParser, TypedValueParser},
+    error::ErrorKind,
+    CommandFactory, Parser, Subcommand, ValueEnum, ValueHint,
+};
+use clap_complete::Shell;
+use crossterm::style::{self, Color, Colors};
+
+use crate::{
+    config_file, diff,
+    dump::{
+        base64, hexdump, include, literal, numbers, open_window, plain, read_input, records,
+        DumpOptions, Language, NumberType, OffsetRadix, RecordFormat,
+    },
+    editor::FileEditor,
+    filter,
+    highlight::Rule,
+    keymap::Keymap,
+    parse_number,
+    plugin::Plugins,
+    screen::Screen,
+    script,
+    search::{self, Pattern},
+    template::{self, Template},
+    value::Endianness,
+    BYTES_PER_ROW,
+};
+
+/// Bytes in each row of the hex dump with `-b`, the same as in `xxd -b`.
+const BITS_BYTES_PER_ROW: usize = 6;
+/// Bytes in each row printed by `-p`, the same as in `xxd -p`.
+const PLAIN_BYTES_PER_ROW: usize = 30;
+
+/// Writes each of `files` in the dump format given by `args` to the output file, or to stdout if there is none. The
+/// hex dumps of several files are preceded by their names unless `--no-header` is given. Like `head`, a file that
+/// can't be read is reported and skipped, and the result tells whether every file was written.
+fn dump(files: &[String], args: &Args, config: Config) -> Result<bool, io::Error> {
+    if let Some(ty) = args
+        .types
+        .iter()
+        .find(|ty| !config.bytes_per_row.is_multiple_of(ty.size))
+    {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "--width must be a multiple of the {}-byte units of -t",
+                    ty.size
+                ),
+            )
+            .exit();
+    }
+
+    // the bytes written by -r aren't for reading on a terminal, paged or not
+    let mut pager = None;
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path).map_err(|e| path_error(path, e))?),
+        None if !args.no_pager && !args.reverse && io::stdout().is_terminal() => {
+            match filter::pager() {
+                Some(mut child) => {
+                    let stdin = child.stdin.take().unwrap();
+                    pager = Some(child);
+                    Box::new(stdin)
+                }
+                None => Box::new(io::stdout().lock()),
+            }
+        }
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut out = BufWriter::new(output);
+    let result = write_dump(&mut out, files, args, config);
+    drop(out);
+
+    if let Some(mut pager) = pager {
+        pager.wait()?;
+
+        // quitting the pager before the end of the dump closes the pipe
+        if result
+            .as_ref()
+            .is_err_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+        {
+            return Ok(true);
+        }
+    }
+
+    result
+}
+
+fn write_dump(
+    out: &mut impl Write,
+    files: &[String],
+    args: &Args,
+    config: Config,
+) -> Result<bool, io::Error> {
+    let mut options = config.dump_options();
+    if args.bits && args.width.is_none() {
+        options.bytes_per_row = BITS_BYTES_PER_ROW;
+    }
+
+    let highlight = args.highlight.as_ref().map(|pattern| {
+        Pattern::parse(pattern, config.endianness)
+            .filter(|p| !p.is_empty())
+            .unwrap_or_else(|| {
+                Args::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!("invalid search pattern: {}", pattern),
+                    )
+                    .exit()
+            })
+    });
+
+    if let Some(format) = args.format {
+        records(out, files, args.skip, args.length, format, &options)?;
+        out.flush()?;
+        return Ok(true);
+    }
+
+    let mut written = true;
+
+    for (i, file) in files.iter().enumerate() {
+        match write_file(out, files, i, args, &config, &options, highlight.as_ref()) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                eprintln!("{}: {}", file, e);
+                written = false;
+            }
+            result => result?,
+        }
+    }
+
+    out.flush()?;
+    Ok(written)
+}
+
+/// Writes the `i`th of `files` in the dump format given by `args`.
+fn write_file(
+    out: &mut impl Write,
+    files: &[String],
+    i: usize,
+    args: &Args,
+    config: &Config,
+    options: &DumpOptions,
+    highlight: Option<&Pattern>,
+) -> Result<(), io::Error> {
+    let file = &files[i];
+
+    if args.dump || args.bits || !args.types.is_empty() || !args.templates.is_empty() {
+        if files.len() > 1 && !args.no_header {
+            if i > 0 {
+                writeln!(out)?;
+            }
+
+            writeln!(out, "==> {} <==", file)?;
+        }
+
+        if !args.templates.is_empty() {
+            let reader = open_window(file, args.skip, args.length)?;
+            template::write(
+                &args.templates,
+                out,
+                reader,
+                args.skip,
+                config.endianness,
+                config.replacement_char,
+            )?;
+        } else if args.types.is_empty() {
+            hexdump(
+                out,
+                file,
+                args.skip,
+                args.length,
+                args.bits,
+                args.words,
+                !args.no_squeeze,
+                highlight,
+                options,
+            )?;
+        } else {
+            numbers(
+                out,
+                file,
+                args.skip,
+                args.length,
+                &args.types,
+                !args.no_squeeze,
+                options,
+            )?;
+        }
+    } else if args.include {
+        if i > 0 {
+            writeln!(out)?;
+        }
+
+        include(out, file, args.skip, args.length, args.uppercase)?;
+    } else if let Some(language) = args.literal {
+        if i > 0 {
+            writeln!(out)?;
+        }
+
+        literal(out, file, args.skip, args.length, language, args.uppercase)?;
+    } else if args.reverse {
+        reverse(out, file, args.base64)?;
+    } else if args.base64 {
+        base64(out, file, args.skip, args.length)?;
+    } else if args.plain {
+        let width = args.width.map_or(PLAIN_BYTES_PER_ROW, usize::from);
+        plain(out, file, args.skip, args.length, width, args.uppercase)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the bytes of `file` given as base64 if `base64` is set, or as hex digits like the output of `-p`.
+/// Whitespace is ignored.
+fn reverse(out: &mut impl Write, file: &str, base64: bool) -> Result<(), io::Error> {
+    let input = read_input(file)?;
+    let text: String = String::from_utf8_lossy(&input).split_whitespace().collect();
+
+    let bytes = match base64 {
+        true => BASE64_STANDARD
+            .decode(&text)
+            .map_err(|e| format!("invalid base64: {}", e)),
+        false => parse_hex(&text).ok_or_else(|| String::from("invalid hex")),
+    };
+    let bytes = bytes.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    out.write_all(&bytes)
+}
+
+/// Parses pairs of hex digits.
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Prints the offsets of all matches of `pattern` in `file`.
+fn find(file: &str, pattern: &str, config: Config) -> Result<(), io::Error> {
+    let Some(pattern) = Pattern::parse(pattern, config.endianness).filter(|p| !p.is_empty()) else {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                format!("invalid search pattern: {}", pattern),
+            )
+            .exit();
+    };
+
+    let buffer = read_input(file).map_err(|e| path_error(file, e))?;
+    let offsets = pattern.find_all(&buffer);
+
+    io::stdout().write_all(
+        search::export(&buffer, &offsets, pattern.len(), config.export_context).as_bytes(),
+    )
+}
+
+/// Adds `path` to `error`, to tell which file it is about.
+fn path_error(path: &str, error: io::Error) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", path, error))
+}
+
+/// Prints the error that stopped a mode running without the editor, and exits with 1 like the editor does. The output
+/// being closed early, like by `head`, isn't an error.
+fn exit_on_error(result: Result<(), io::Error>) {
+    match result {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        _ => {}
+    }
+}
+
+/// Splits the patches given to `--patch` as `OFFSET=BYTES` into their offset and bytes.
+fn split_patches(patches: &[String]) -> Vec<(&str, &str)> {
+    patches
+        .iter()
+        .map(|patch| {
+            patch.split_once('=').unwrap_or_else(|| {
+                Args::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!("invalid patch {}: expected OFFSET=BYTES", patch),
+                    )
+                    .exit()
+            })
+        })
+        .collect()
+}
+
+/// Writes each of `patches`, given as an offset and the bytes to write there, to `file` and saves it.
+fn patch(file: &str, patches: &[(&str, &str)], endianness: Endianness) -> Result<(), io::Error> {
+    let mut editor = FileEditor::new(file).map_err(|e| path_error(file, e))?;
+    let mut writes = Vec::new();
+
+    for &(offset, bytes) in patches {
+        let invalid = |reason: &str| -> ! {
+            Args::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!("invalid patch {} at {}: {}", bytes, offset, reason),
+                )
+                .exit()
+        };
+
+        let Some(offset) = parse_number(offset.trim()) else {
+            invalid("invalid offset");
+        };
+        let Some(bytes) = Pattern::parse(bytes, endianness)
+            .and_then(|pattern| pattern.exact_bytes().map(<[u8]>::to_vec))
+            .filter(|bytes| !bytes.is_empty())
+        else {
+            invalid("invalid bytes");
+        };
+
+        if offset.saturating_add(bytes.len()) > editor.file_size() {
+            invalid("past the end of the file");
+        }
+
+        writes.push((offset, bytes));
+    }
+
+    editor
+        .apply_writes(&writes)
+        .map_err(|e| path_error(file, e))?;
+
+    if !editor.saved {
+        editor.save().map_err(|e| path_error(file, e))?;
+    }
+
+    Ok(())
+}
+
+fn parse_number_arg(input: &str) -> Result<usize, String> {
+    parse_number(input).ok_or_else(|| format!("invalid number: {}", input))
+}
+
+/// Parses a decimal number, or a hexadecimal one with a `0x` prefix, like the offsets and lengths given to `xxd` and
+/// `od`.
+fn parse_size_arg(input: &str) -> Result<usize, String> {
+    match input.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => input.parse(),
+    }
+    .map_err(|_| format!("invalid number: {}", input))
+}
+
+/// Runs the script at `path` on `file`, saving the file if the script changed it.
+fn run_script(file: &str, path: &str, config: Config) -> Result<(), io::Error> {
+    let source = fs::read_to_string(path).map_err(|e| path_error(path, e))?;
+    let mut editor = FileEditor::new(file).map_err(|e| path_error(file, e))?;
+
+    match script::run(&mut editor, &mut None, &source, config.endianness) {
+        Ok(output) => {
+            for line in output {
+                println!("{}", line);
+            }
+        }
+        Err(e) => {
+            eprintln!("error in script: {}", e);
+            process::exit(1);
+        }
+    }
+
+    if !editor.saved {
+        editor.save().map_err(|e| path_error(file, e))?;
+    }
+
+    Ok(())
+}
+
+/// Turns `+POSITION` arguments, as in `hexzen +1f0 file`, into `--goto +POSITION` like `less` does.
+fn expand_goto_args(args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut expanded = Vec::new();
+
+    for (i, arg) in args.enumerate() {
+        if i > 0 && arg.len() > 1 && arg.starts_with('+') {
+            expanded.push(String::from("--goto"));
+        }
+
+        expanded.push(arg);
+    }
+
+    expanded
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Commands>,
+    // always given when there is no subcommand. Only the dump formats take more than one
+    #[arg(required = true, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    files: Vec<String>,
+    #[arg(short, long, help = "prints a hex dump instead of opening the editor")]
+    dump: bool,
+    #[arg(
+        short,
+        long,
+        default_value = "0",
+        value_name = "OFFSET",
+        value_parser = parse_size_arg,
+        help = "offset the hex dump and -i start at, in decimal or in hex after 0x"
+    )]
+    skip: usize,
+    #[arg(
+        short = 'n',
+        long,
+        value_name = "BYTES",
+        value_parser = parse_size_arg,
+        help = "most bytes included in the hex dump and by -i, in decimal or in hex after 0x"
+    )]
+    length: Option<usize>,
+    #[arg(
+        short,
+        long,
+        help = "prints the file as a C array like xxd -i instead of opening the editor"
+    )]
+    include: bool,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "LANGUAGE",
+        help = "prints the file as a rust byte slice or a python or c string instead of opening the editor"
+    )]
+    literal: Option<Language>,
+    #[arg(
+        short,
+        long,
+        help = "prints the file as plain hex digits like xxd -p instead of opening the editor"
+    )]
+    plain: bool,
+    #[arg(
+        short,
+        long,
+        help = "prints a hex dump with binary digits in place of hex digits instead of opening the editor"
+    )]
+    bits: bool,
+    #[arg(
+        short = 'v',
+        long,
+        help = "shows every row of the hex dump instead of a * in place of rows repeating the previous one"
+    )]
+    no_squeeze: bool,
+    #[arg(
+        long,
+        help = "shows each group of the hex dump as a single value in the byte order given by --endian"
+    )]
+    words: bool,
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        help = "shows the matches of a search pattern in the hex dump in reverse video"
+    )]
+    highlight: Option<String>,
+    #[arg(
+        short = 'U',
+        long,
+        help = "uses uppercase hex digits in the hex dump, -i and -p"
+    )]
+    uppercase: bool,
+    #[arg(
+        short,
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        help = "writes the hex dump or the other formats to a file instead of stdout"
+    )]
+    output: Option<String>,
+    #[arg(
+        long,
+        help = "leaves out the name of each file before its hex dump when there are several"
+    )]
+    no_header: bool,
+    #[arg(
+        long,
+        help = "writes the hex dump and the other formats straight to the terminal instead of through $PAGER"
+    )]
+    no_pager: bool,
+    #[arg(long, help = "prints the file as base64 instead of opening the editor")]
+    base64: bool,
+    #[arg(
+        short,
+        long,
+        help = "reads the file as the output of -p or --base64 and prints the bytes it holds"
+    )]
+    reverse: bool,
+    #[arg(
+        long,
+        value_enum,
+        help = "prints each row of the hex dump as a json or csv record instead of opening the editor"
+    )]
+    format: Option<RecordFormat>,
+    #[arg(
+        short = 't',
+        long = "type",
+        value_name = "TYPE",
+        value_parser = NumberType::parse,
+        help = "prints the values of units of the file like od -t, e.g. d1, u2, o4 or x8, instead of opening the editor"
+    )]
+    types: Vec<NumberType>,
+    #[arg(
+        short = 'e',
+        long = "template",
+        value_name = "FORMAT",
+        value_parser = Template::parse,
+        help = "prints the file in a layout given by format units like hexdump -e instead of opening the editor, \
+                can be repeated"
+    )]
+    templates: Vec<Template>,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "RADIX",
+        default_value_t = OffsetRadix::Hex,
+        help = "how the offsets of the hex dump and -t are written"
+    )]
+    offsets: OffsetRadix,
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        help = "prints the offsets of all matches of a search pattern instead of opening the editor"
+    )]
+    find: Option<String>,
+    #[arg(
+        long,
+        value_name = "BYTES",
+        help = "shows the matched bytes and this many bytes around them with --find and exported search results"
+    )]
+    context: Option<usize>,
+    #[arg(
+        long,
+        value_name = "SCRIPT",
+        value_hint = ValueHint::FilePath,
+        help = "runs a rhai script on the file and saves the changes instead of opening the editor"
+    )]
+    script: Option<String>,
+    #[arg(
+        long,
+        value_name = "OFFSET=BYTES",
+        help = "writes bytes at an offset and saves the file instead of opening the editor, can be repeated"
+    )]
+    patch: Vec<String>,
+    #[arg(
+        short,
+        help = "use the unicode replacement character instead of a dot when a character isn't printable ascii"
+    )]
+    unicode_replacement_char: bool,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WHEN",
+        default_value_t = ColorChoice::Auto,
+        help = "when to use colors. auto uses them if stdout is a terminal and NO_COLOR isn't set"
+    )]
+    color: ColorChoice,
+    #[arg(short = 'c', long, help = "same as --color never")]
+    no_colors: bool,
+    #[arg(
+        long,
+        help = "collapses long runs of identical bytes into a single row in the editor"
+    )]
+    collapse: bool,
+    #[arg(
+        long,
+        help = "doesn't apply the built-in structure of the file type, like elf or png, when opening the editor"
+    )]
+    no_auto_struct: bool,
+    #[arg(
+        short,
+        long,
+        default_value_t = 8,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "number of bytes in each space-separated group in the editor and the hex dump"
+    )]
+    group: u16,
+    #[arg(
+        short,
+        long,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "most bytes in each row of the editor, which shows fewer if the terminal is too narrow, and the hex \
+                dump. 16 by default, or 30 with -p and 6 with -b like xxd"
+    )]
+    width: Option<u16>,
+    #[arg(
+        long,
+        default_value_t = 16,
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "number of bytes moved by Ctrl+Up and Ctrl+Down in the editor"
+    )]
+    record_size: u32,
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "minimum number of rows kept above and below the cursor in the editor"
+    )]
+    scroll_off: usize,
+    #[arg(
+        long,
+        default_value_t = 512,
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "alignment used by ]a and [a in the editor"
+    )]
+    align: u32,
+    #[arg(
+        long,
+        default_value_t = 4,
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "fewest printable characters in the strings listed by T and found by ]s and [s in the editor"
+    )]
+    string_length: u32,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Endianness::Little,
+        help = "byte order used when reading values in the editor and with -t and --words"
+    )]
+    endian: Endianness,
+    #[arg(
+        long,
+        default_value_t = 4,
+        value_parser = PossibleValuesParser::new(["2", "4", "8"]).map(|s| s.parse::<usize>().unwrap()),
+        help = "size of the pointer followed by p in the editor"
+    )]
+    pointer_size: usize,
+    #[arg(
+        long,
+        default_value = "0",
+        value_parser = parse_number_arg,
+        help = "address subtracted from pointers followed by p in the editor"
+    )]
+    pointer_base: usize,
+    #[arg(
+        long,
+        help = "stops n and N at the last match instead of wrapping around the file in the editor"
+    )]
+    no_wrap_search: bool,
+    #[arg(
+        long,
+        value_name = "POSITION",
+        allow_hyphen_values = true,
+        help = "opens the editor at a position, given the same way as to j. +POSITION before the file does the same"
+    )]
+    goto: Option<String>,
+}
+
+impl Args {
+    /// Whether a dump format is given instead of opening the editor.
+    fn dumps(&self) -> bool {
+        self.dump
+            || self.bits
+            || !self.types.is_empty()
+            || !self.templates.is_empty()
+            || self.format.is_some()
+            || self.include
+            || self.literal.is_some()
+            || self.reverse
+            || self.base64
+            || self.plain
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    #[command(about = "prints a completion script for a shell")]
+    Completions { shell: Shell },
+    #[command(
+        about = "prints the rows of a hex dump that differ between two files, exiting with 1 if they differ"
+    )]
+    Diff {
+        #[arg(value_hint = ValueHint::FilePath)]
+        a: String,
+        #[arg(value_hint = ValueHint::FilePath)]
+        b: String,
+        #[arg(
+            short = 'C',
+            long,
+            value_name = "ROWS",
+            default_value_t = 0,
+            help = "identical rows shown around the rows that differ"
+        )]
+        context: usize,
+        #[arg(
+            long,
+            value_enum,
+            value_name = "WHEN",
+            default_value_t = ColorChoice::Auto,
+            help = "when to color the bytes that differ"
+        )]
+        color: ColorChoice,
+    },
+    #[command(about = "writes bytes at offsets of a file and saves it, like --patch")]
+    Patch {
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: String,
+        #[arg(
+            long,
+            value_name = "OFFSET",
+            required = true,
+            help = "offset to write the bytes at, paired in order with --bytes, can be repeated"
+        )]
+        at: Vec<String>,
+        #[arg(
+            long,
+            value_name = "BYTES",
+            required = true,
+            help = "bytes to write, given like a search pattern without wildcards, can be repeated"
+        )]
+        bytes: Vec<String>,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = Endianness::Little,
+            help = "byte order of numbers in the bytes, like u32:1"
+        )]
+        endian: Endianness,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Returns whether to use colors in output written to a terminal if `terminal` is set.
+    fn enabled(self, terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                // see https://no-color.org
+                let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+                !no_color && terminal
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+pub(crate) struct Config {
+    pub(crate) replacement_char: char,
+    /// Whether the output of hexzen is colored at all.
+    pub(crate) colors: bool,
+    /// Whether hex dumps use uppercase hex digits.
+    pub(crate) uppercase_hex: bool,
+    /// The radix of the offsets in hex dumps.
+    pub(crate) offset_radix: OffsetRadix,
+    pub(crate) highlight_colors: Option<Colors>,
+    pub(crate) collapse_runs: bool,
+    /// Whether the editor applies the built-in structure definition of the file type when it opens a file.
+    pub(crate) auto_structure: bool,
+    /// The most bytes shown in a row of the editor, when the terminal is wide enough.
+    pub(crate) bytes_per_row: usize,
+    pub(crate) group_size: usize,
+    pub(crate) record_size: usize,
+    pub(crate) scroll_off: usize,
+    pub(crate) alignment: usize,
+    /// The fewest printable characters that make a string.
+    pub(crate) string_length: usize,
+    pub(crate) endianness: Endianness,
+    pub(crate) pointer_size: usize,
+    pub(crate) pointer_base: usize,
+    pub(crate) wrap_search: bool,
+    pub(crate) export_context: Option<usize>,
+    pub(crate) keymap: Keymap,
+    /// Highlights added when a file is opened in the editor.
+    pub(crate) highlight_rules: Vec<Rule>,
+}
+
+impl Config {
+    /// Returns the options of the dump formats given by the config.
+    fn dump_options(&self) -> DumpOptions {
+        DumpOptions {
+            bytes_per_row: self.bytes_per_row,
+            group_size: self.group_size,
+            uppercase_hex: self.uppercase_hex,
+            offset_radix: self.offset_radix,
+            colors: self.colors,
+            replacement_char: self.replacement_char,
+            endianness: self.endianness,
+        }
+    }
+}
+
+/// Runs hexzen with the command line arguments of the process, extended by `plugins`.
+pub fn run(plugins: Plugins) {
+    let args = Args::parse_from(expand_goto_args(env::args()));
+
+    match &args.command {
+        Some(Commands::Completions { shell }) => {
+            let mut command = Args::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(*shell, &mut command, name, &mut io::stdout());
+            return;
+        }
+        Some(Commands::Diff {
+            a,
+            b,
+            context,
+            color,
+        }) => {
+            let colors = color.enabled(io::stdout().is_terminal());
+            style::force_color_output(colors);
+
+            // like cmp, exits with 1 if the files differ and 2 if they can't be read
+            let mut out = BufWriter::new(io::stdout().lock());
+            let result = diff::diff(&mut out, a, b, *context, colors).and_then(|differs| {
+                out.flush()?;
+                Ok(differs)
+            });
+
+            match result {
+                Ok(false) => return,
+                Ok(true) => process::exit(1),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(2);
+                }
+            }
+        }
+        Some(Commands::Patch {
+            file,
+            at,
+            bytes,
+            endian,
+        }) => {
+            if at.len() != bytes.len() {
+                Args::command()
+                    .error(
+                        ErrorKind::WrongNumberOfValues,
+                        "each --at needs a --bytes, and the other way around",
+                    )
+                    .exit();
+            }
+
+            let patches: Vec<(&str, &str)> = at
+                .iter()
+                .map(String::as_str)
+                .zip(bytes.iter().map(String::as_str))
+                .collect();
+            exit_on_error(patch(file, &patches, *endian));
+            return;
+        }
+        None => {}
+    }
+
+    if args.files.len() > 1 && !args.dumps() {
+        Args::command()
+            .error(
+                ErrorKind::TooManyValues,
+                "only the hex dump and the other dump formats take more than one file",
+            )
+            .exit();
+    }
+
+    let file = args.files[0].as_str();
+    let config_file = config_file::load().unwrap_or_else(|e| {
+        eprintln!("error in config file {}", e);
+        process::exit(1);
+    });
+    let keymap = Keymap::new(&config_file.keymap).unwrap_or_else(|e| {
+        eprintln!("error in keymap: {}", e);
+        process::exit(1);
+    });
+    let highlight_rules = config_file
+        .highlights
+        .iter()
+        .map(|rule| {
+            Rule::parse(rule, args.endian).map_err(|e| format!("highlight {}: {}", rule.name, e))
+        })
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| {
+            eprintln!("error in config file: {}", e);
+            process::exit(1);
+        });
+    let terminal = args.output.is_none() && io::stdout().is_terminal();
+    let colors = !args.no_colors && args.color.enabled(terminal);
+    // crossterm leaves out colors if NO_COLOR is set, which --color always overrides
+    style::force_color_output(colors);
+    let config = Config {
+        replacement_char: match args.unicode_replacement_char {
+            true => REPLACEMENT_CHARACTER,
+            false => '.',
+        },
+        colors,
+        uppercase_hex: args.uppercase,
+        offset_radix: args.offsets,
+        highlight_colors: match colors {
+            true => Some(Colors::new(Color::White, Color::DarkGrey)),
+            false => None,
+        },
+        collapse_runs: args.collapse,
+        auto_structure: !args.no_auto_struct,
+        bytes_per_row: args.width.map_or(BYTES_PER_ROW, usize::from),
+        group_size: args.group.into(),
+        record_size: args.record_size as usize,
+        scroll_off: args.scroll_off,
+        alignment: args.align as usize,
+        string_length: args.string_length as usize,
+        endianness: args.endian,
+        pointer_size: args.pointer_size,
+        pointer_base: args.pointer_base,
+        wrap_search: !args.no_wrap_search,
+        export_context: args.context,
+        keymap,
+        highlight_rules,
+    };
+
+    if args.dumps() {
+        match dump(&args.files, &args, config) {
+            Ok(true) => {}
+            Ok(false) => process::exit(1),
+            result => exit_on_error(result.map(|_| ())),
+        }
+    } else if let Some(pattern) = &args.find {
+        exit_on_error(find(file, pattern, config));
+    } else if !args.patch.is_empty() {
+        exit_on_error(patch(file, &split_patches(&args.patch), config.endianness));
+    } else if let Some(script) = &args.script {
+        exit_on_error(run_script(file, script, config));
+    } else {
+        let mut screen = Screen::new(file, config, plugins).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+
+        if let Some(position) = &args.goto {
+            screen.goto(position);
+        }
+
+        let result = screen.screen_loop();
+
+        // the screen gives the terminal back to the shell when it is dropped, which has to happen before the error
+        // is printed for it to be seen
+        drop(screen);
+
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_are_decimal_unless_hex() {
+        assert_eq!(parse_size_arg("16"), Ok(16));
+        assert_eq!(parse_size_arg("0x10"), Ok(16));
+        assert_eq!(parse_size_arg("010"), Ok(10));
+        assert!(parse_size_arg("1f").is_err());
+        assert!(parse_size_arg("0x").is_err());
+        assert!(parse_size_arg("-1").is_err());
+    }
+}
@@ -0,0 +1,129 @@
+/// A run must span at least this many identical rows to be collapsed. The first row of the run is still shown, so the
+/// summary row always replaces at least two rows.
+const MIN_RUN_ROWS: usize = 3;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Run {
+    /// Offset of the first hidden byte, the row before it is the first row of the run and is shown normally.
+    pub start: usize,
+    pub end: usize,
+    pub byte: u8,
+}
+
+/// Row-aligned runs of a single repeated byte, recomputed whenever the buffer or the row size changes.
+#[derive(Default)]
+pub struct RunMap {
+    runs: Vec<Run>,
+    bytes_per_row: usize,
+    generation: Option<u64>,
+}
+
+impl RunMap {
+    pub fn update(&mut self, buffer: &[u8], bytes_per_row: usize, generation: u64) {
+        if self.generation == Some(generation) && self.bytes_per_row == bytes_per_row {
+            return;
+        }
+
+        self.runs.clear();
+        self.bytes_per_row = bytes_per_row;
+        self.generation = Some(generation);
+
+        let mut run_start = 0;
+        let mut run_byte = None;
+
+        for (i, row) in buffer.chunks(bytes_per_row).enumerate() {
+            let byte = if row.len() == bytes_per_row && row.iter().all(|&b| b == row[0]) {
+                Some(row[0])
+            } else {
+                None
+            };
+
+            if byte.is_none() || byte != run_byte {
+                self.push_run(run_start, i, run_byte);
+                run_start = i;
+                run_byte = byte;
+            }
+        }
+
+        self.push_run(run_start, buffer.len() / bytes_per_row, run_byte);
+    }
+
+    fn push_run(&mut self, start_row: usize, end_row: usize, byte: Option<u8>) {
+        if let Some(byte) = byte {
+            if end_row - start_row >= MIN_RUN_ROWS {
+                self.runs.push(Run {
+                    start: (start_row + 1) * self.bytes_per_row,
+                    end: end_row * self.bytes_per_row,
+                    byte,
+                });
+            }
+        }
+    }
+
+    /// Returns the run whose hidden part contains `offset`.
+    pub fn run_at(&self, offset: usize) -> Option<Run> {
+        let i = self.runs.partition_point(|run| run.end <= offset);
+
+        self.runs.get(i).filter(|run| run.start <= offset).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runs(buffer: &[u8]) -> RunMap {
+        let mut runs = RunMap::default();
+        runs.update(buffer, 16, 0);
+        runs
+    }
+
+    #[test]
+    fn hides_all_but_the_first_row_of_a_run() {
+        let buffer = [
+            [1; 16], [0; 16], [0; 16], [0; 16], [0; 16], [0; 16], [2; 16],
+        ]
+        .concat();
+        let runs = runs(&buffer);
+
+        // the 1s and the 2s are a single row each, and the first row of 0s is shown
+        assert!(runs.run_at(0x1f).is_none());
+        assert!(
+            runs.run_at(0x20)
+                == Some(Run {
+                    start: 0x20,
+                    end: 0x60,
+                    byte: 0
+                })
+        );
+        assert!(runs.run_at(0x5f).is_some());
+        assert!(runs.run_at(0x60).is_none());
+    }
+
+    #[test]
+    fn short_and_unaligned_runs_stay() {
+        // two rows of 0s, then 0xff from the middle of a row, filling two rows and part of the last one
+        let mut buffer = vec![0; 32];
+        buffer.extend([5; 8]);
+        buffer.extend([0xff; 48]);
+
+        assert!((0..buffer.len()).all(|offset| runs(&buffer).run_at(offset).is_none()));
+    }
+
+    #[test]
+    fn updates_on_new_generations_and_row_sizes() {
+        let mut runs = RunMap::default();
+        runs.update(&[0; 64], 16, 0);
+        assert!(runs.run_at(0x10).is_some());
+
+        // the same generation is taken to mean the same buffer
+        runs.update(&[1; 64], 16, 0);
+        assert!(runs.run_at(0x10).is_some_and(|run| run.byte == 0));
+
+        runs.update(&[1; 64], 16, 1);
+        assert!(runs.run_at(0x10).is_some_and(|run| run.byte == 1));
+
+        runs.update(&[1; 64], 32, 1);
+        assert!(runs.run_at(0x10).is_none());
+    }
+}
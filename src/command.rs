@@ -0,0 +1,547 @@
+use clap::ValueEnum;
+
+use crate::{cli::Config, hash, parse_number, stats, structure, value::Endianness};
+
+/// Commands entered after `:`.
+pub enum Command {
+    Write,
+    Quit {
+        force: bool,
+    },
+    WriteQuit,
+    SaveAs(String),
+    /// Shows the value of an option if `value` is `None`.
+    Set {
+        option: String,
+        value: Option<String>,
+    },
+    Fill {
+        byte: u8,
+        count: usize,
+    },
+    Goto(String),
+    /// Shows the value of an expression in decimal, hex, octal, binary and as text.
+    Calc(String),
+    /// Writes the value of `expression` at the cursor as an integer of type `ty`, like `u16le`.
+    Put {
+        ty: String,
+        expression: String,
+    },
+    /// Runs the script at the path.
+    Script(String),
+    /// Replaces the selection with the output of a shell command given the selection as input.
+    Filter(String),
+    /// Shows and copies the hash of the selection, or of the whole file, with the algorithm of that name.
+    Hash(String),
+    /// Applies the structure definition `name` at `position`, or at the cursor if not given. Lists the fields of the
+    /// structure applied last if `name` is `None`.
+    Struct {
+        name: Option<String>,
+        position: Option<String>,
+    },
+    /// Identifies the file type again and lists the known file types found in the file.
+    Magic,
+    /// Writes the data of a known type at `position` to `file`, as long as its format tells or up to the next known
+    /// type.
+    Carve {
+        position: String,
+        file: String,
+    },
+    /// Writes the bookmarks and annotations to a JSON file.
+    Export(String),
+    /// Adds the bookmarks and annotations in a JSON file written by `Export`.
+    Import(String),
+    /// Lists the values that point into the file, read with the pointer size and base if not given.
+    Pointers {
+        size: Option<usize>,
+        base: Option<usize>,
+    },
+    /// Lists the bytes that differ from the file as it was loaded.
+    Changes,
+    /// Lists the compressed streams in the file if `position` is `None`, or inflates the stream at `position` into a
+    /// preview, or into `file` if given.
+    Inflate {
+        position: Option<String>,
+        file: Option<String>,
+    },
+    /// Lists the entropy of each block of `block_size` bytes.
+    Entropy {
+        block_size: usize,
+    },
+    /// Shows how often each byte value occurs in the selection, or in the whole file, naming the `top` most common.
+    Histogram {
+        top: usize,
+    },
+    /// Writes the checksum of the bytes from `start` to `end` at `target`, `width` bytes long if given instead of the
+    /// size of the checksum. The positions are expressions, like with `:goto`.
+    Checksum {
+        algorithm: String,
+        start: String,
+        end: String,
+        target: String,
+        width: Option<usize>,
+        endianness: Option<Endianness>,
+    },
+    /// Plays the macro recorded into `register` `count` times.
+    Play {
+        register: char,
+        count: usize,
+    },
+}
+
+/// Command names, for completion.
+const COMMANDS: [&str; 24] = [
+    "calc",
+    "carve",
+    "changes",
+    "checksum",
+    "entropy",
+    "export",
+    "fill",
+    "goto",
+    "hash",
+    "histogram",
+    "import",
+    "inflate",
+    "magic",
+    "play",
+    "pointers",
+    "put",
+    "q",
+    "q!",
+    "saveas",
+    "script",
+    "set",
+    "struct",
+    "w",
+    "wq",
+];
+
+/// Option names used by `:set`, for completion.
+const OPTIONS: [&str; 10] = [
+    "align",
+    "endian",
+    "group",
+    "pointer-base",
+    "pointer-size",
+    "record-size",
+    "scroll-off",
+    "string-length",
+    "width",
+    "wrap-search",
+];
+
+/// Returns whether `ty` is an integer type that `value::encode` can write, like `u8` or `i32be`.
+fn is_int_type(ty: &str) -> bool {
+    let ty = ty
+        .strip_suffix("le")
+        .or_else(|| ty.strip_suffix("be"))
+        .unwrap_or(ty);
+
+    matches!(
+        ty,
+        "u8" | "u16" | "u32" | "u64" | "i8" | "i16" | "i32" | "i64"
+    )
+}
+
+pub fn parse(input: &str) -> Result<Command, String> {
+    let input = input.trim();
+
+    if let Some(command) = input.strip_prefix('!') {
+        return match command.trim() {
+            "" => Err(String::from("usage: !<shell command>")),
+            command => Ok(Command::Filter(command.to_owned())),
+        };
+    }
+
+    let (name, args) = input.split_once(' ').unwrap_or((input, ""));
+    let args = args.trim();
+
+    let command = match name {
+        "w" | "write" => Command::Write,
+        "q" | "quit" => Command::Quit { force: false },
+        "q!" | "quit!" => Command::Quit { force: true },
+        "wq" | "x" => Command::WriteQuit,
+        "saveas" if !args.is_empty() => Command::SaveAs(args.to_owned()),
+        "saveas" => return Err(String::from("usage: saveas <path>")),
+        "set" if !args.is_empty() => match args.split_once('=') {
+            Some((option, value)) => Command::Set {
+                option: option.trim().to_owned(),
+                value: Some(value.trim().to_owned()),
+            },
+            None => Command::Set {
+                option: args.to_owned(),
+                value: None,
+            },
+        },
+        "set" => return Err(String::from("usage: set <option>[=<value>]")),
+        "fill" => {
+            let mut args = args.split_whitespace();
+            let byte = args.next().and_then(|byte| {
+                u8::from_str_radix(byte.strip_prefix("0x").unwrap_or(byte), 16).ok()
+            });
+            let count = args.next().and_then(parse_number);
+
+            match (byte, count, args.next()) {
+                (Some(byte), Some(count), None) => Command::Fill { byte, count },
+                _ => return Err(String::from("usage: fill <byte> <count>")),
+            }
+        }
+        "goto" if !args.is_empty() => Command::Goto(args.to_owned()),
+        "goto" => return Err(String::from("usage: goto <position>")),
+        "calc" if !args.is_empty() => Command::Calc(args.to_owned()),
+        "calc" => return Err(String::from("usage: calc <expression>")),
+        "put" => match args.split_once(' ') {
+            Some((ty, expression)) if is_int_type(ty) => Command::Put {
+                ty: ty.to_owned(),
+                expression: expression.trim().to_owned(),
+            },
+            _ => {
+                return Err(String::from(
+                    "usage: put <u8-u64|i8-i64>[le|be] <expression>",
+                ))
+            }
+        },
+        "hash" if hash::ALGORITHMS.contains(&args) => Command::Hash(args.to_owned()),
+        "hash" => return Err(format!("usage: hash <{}>", hash::ALGORITHMS.join("|"))),
+        "checksum" => {
+            let usage = || {
+                String::from("usage: checksum <algorithm> <start> <end> <target> [width] [le|be]")
+            };
+            let args: Vec<&str> = args.split_whitespace().collect();
+            let [algorithm, start, end, target, ref options @ ..] = args[..] else {
+                return Err(usage());
+            };
+
+            if !hash::CHECKSUMS.contains(&algorithm) {
+                return Err(format!("unknown checksum: {}", algorithm));
+            }
+
+            let mut width = None;
+            let mut endianness = None;
+
+            for option in options {
+                match *option {
+                    "le" => endianness = Some(Endianness::Little),
+                    "be" => endianness = Some(Endianness::Big),
+                    "1" | "2" | "4" | "8" => width = option.parse().ok(),
+                    _ => return Err(usage()),
+                }
+            }
+
+            Command::Checksum {
+                algorithm: algorithm.to_owned(),
+                start: start.to_owned(),
+                end: end.to_owned(),
+                target: target.to_owned(),
+                width,
+                endianness,
+            }
+        }
+        "magic" => Command::Magic,
+        "changes" => Command::Changes,
+        "export" if !args.is_empty() => Command::Export(args.to_owned()),
+        "export" => return Err(String::from("usage: export <path>")),
+        "import" if !args.is_empty() => Command::Import(args.to_owned()),
+        "import" => return Err(String::from("usage: import <path>")),
+        "carve" => match args.split_once(' ') {
+            Some((position, file)) if !file.trim().is_empty() => Command::Carve {
+                position: position.to_owned(),
+                file: file.trim().to_owned(),
+            },
+            _ => return Err(String::from("usage: carve <position> <file>")),
+        },
+        "inflate" => match args.split_once(' ') {
+            Some((position, file)) => Command::Inflate {
+                position: Some(position.to_owned()),
+                file: Some(file.trim().to_owned()),
+            },
+            None => Command::Inflate {
+                position: Some(args.to_owned()).filter(|position| !position.is_empty()),
+                file: None,
+            },
+        },
+        "struct" => match args.split_once(' ') {
+            Some((name, position)) => Command::Struct {
+                name: Some(name.to_owned()),
+                position: Some(position.trim().to_owned()),
+            },
+            None => Command::Struct {
+                name: Some(args.to_owned()).filter(|name| !name.is_empty()),
+                position: None,
+            },
+        },
+        "entropy" => {
+            let block_size = match args {
+                "" => Some(stats::DEFAULT_BLOCK_SIZE),
+                size => parse_number(size).filter(|&size| size > 0),
+            };
+
+            match block_size {
+                Some(block_size) => Command::Entropy { block_size },
+                None => return Err(String::from("usage: entropy [block size]")),
+            }
+        }
+        "histogram" => {
+            let top = match args {
+                "" => Some(stats::DEFAULT_TOP),
+                top => top.parse().ok(),
+            };
+
+            match top {
+                Some(top) => Command::Histogram { top },
+                None => return Err(String::from("usage: histogram [count]")),
+            }
+        }
+        "pointers" => {
+            let usage = || String::from("usage: pointers [2|4|8] [base]");
+            let mut args = args.split_whitespace();
+
+            let size = match args.next() {
+                Some(size @ ("2" | "4" | "8")) => Some(size.parse().unwrap()),
+                Some(_) => return Err(usage()),
+                None => None,
+            };
+            let base = match args.next() {
+                Some(base) => Some(parse_number(base).ok_or_else(usage)?),
+                None => None,
+            };
+
+            if args.next().is_some() {
+                return Err(usage());
+            }
+
+            Command::Pointers { size, base }
+        }
+        "script" if !args.is_empty() => Command::Script(args.to_owned()),
+        "script" => return Err(String::from("usage: script <path>")),
+        "play" => {
+            let mut args = args.split_whitespace();
+            let register = args.next().and_then(|register| {
+                let mut chars = register.chars();
+                chars
+                    .next()
+                    .filter(|c| c.is_ascii_alphabetic() && chars.next().is_none())
+            });
+            let count = match args.next() {
+                Some(count) => count.parse().ok(),
+                None => Some(1),
+            };
+
+            match (register, count, args.next()) {
+                (Some(register), Some(count), None) => Command::Play { register, count },
+                _ => return Err(String::from("usage: play <register> [count]")),
+            }
+        }
+        "" => return Err(String::from("no command")),
+        _ => return Err(format!("unknown command: {}", name)),
+    };
+
+    Ok(command)
+}
+
+/// Completes the command name, or the option name after `set`, the algorithm after `hash` and `checksum` or the
+/// built-in structure after `struct`, at the end of `input`. A unique match is completed along with the separator that
+/// follows it. Returns `None` if there is nothing to add.
+pub fn complete(input: &str) -> Option<String> {
+    let (prefix, word, candidates, separator): (&str, &str, &[&str], &str) = match input
+        .split_once(' ')
+    {
+        Some(("set", option)) if !option.contains(['=', ' ']) => ("set ", option, &OPTIONS, "="),
+        Some(("hash", name)) if !name.contains(' ') => ("hash ", name, &hash::ALGORITHMS, ""),
+        Some(("struct", name)) if !name.contains(' ') => ("struct ", name, &structure::NAMES, " "),
+        Some(("checksum", name)) if !name.contains(' ') => {
+            ("checksum ", name, &hash::CHECKSUMS, " ")
+        }
+        Some(_) => return None,
+        None => ("", input, &COMMANDS, " "),
+    };
+
+    let matches: Vec<&str> = candidates
+        .iter()
+        .copied()
+        .filter(|candidate| candidate.starts_with(word))
+        .collect();
+
+    if let [candidate] = matches[..] {
+        return Some(format!("{}{}{}", prefix, candidate, separator));
+    }
+
+    // the longest prefix shared by all matches
+    let common = matches
+        .iter()
+        .skip(1)
+        .fold(*matches.first()?, |common, candidate| {
+            let len = common
+                .bytes()
+                .zip(candidate.bytes())
+                .take_while(|(a, b)| a == b)
+                .count();
+            &common[..len]
+        });
+
+    if common.len() > word.len() {
+        Some(format!("{}{}", prefix, common))
+    } else {
+        None
+    }
+}
+
+/// Changes the option `name` to `value`, parsed the same way as the matching command line argument.
+pub fn set_option(config: &mut Config, name: &str, value: &str) -> Result<(), String> {
+    let invalid = || format!("invalid value for {}: {}", name, value);
+    let count = || {
+        value
+            .parse::<usize>()
+            .ok()
+            .filter(|&count| count > 0)
+            .ok_or_else(invalid)
+    };
+
+    match name {
+        "align" => config.alignment = count()?,
+        "endian" => config.endianness = Endianness::from_str(value, true).map_err(|_| invalid())?,
+        "group" => config.group_size = count()?,
+        "pointer-base" => config.pointer_base = parse_number(value).ok_or_else(invalid)?,
+        "pointer-size" => {
+            config.pointer_size = match value {
+                "2" | "4" | "8" => value.parse().unwrap(),
+                _ => return Err(invalid()),
+            }
+        }
+        "record-size" => config.record_size = count()?,
+        "scroll-off" => config.scroll_off = value.parse().map_err(|_| invalid())?,
+        "string-length" => config.string_length = count()?,
+        "width" => config.bytes_per_row = count()?,
+        "wrap-search" => config.wrap_search = value.parse().map_err(|_| invalid())?,
+        _ => return Err(format!("unknown option: {}", name)),
+    }
+
+    Ok(())
+}
+
+pub fn get_option(config: &Config, name: &str) -> Result<String, String> {
+    let value = match name {
+        "align" => config.alignment.to_string(),
+        "endian" => match config.endianness {
+            Endianness::Little => String::from("little"),
+            Endianness::Big => String::from("big"),
+        },
+        "group" => config.group_size.to_string(),
+        "pointer-base" => format!("{:#x}", config.pointer_base),
+        "pointer-size" => config.pointer_size.to_string(),
+        "record-size" => config.record_size.to_string(),
+        "scroll-off" => config.scroll_off.to_string(),
+        "string-length" => config.string_length.to_string(),
+        "width" => config.bytes_per_row.to_string(),
+        "wrap-search" => config.wrap_search.to_string(),
+        _ => return Err(format!("unknown option: {}", name)),
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_aliases() {
+        assert!(matches!(parse("w"), Ok(Command::Write)));
+        assert!(matches!(parse(" write "), Ok(Command::Write)));
+        assert!(matches!(parse("q!"), Ok(Command::Quit { force: true })));
+        assert!(matches!(parse("x"), Ok(Command::WriteQuit)));
+        assert!(matches!(parse(""), Err(e) if e == "no command"));
+        assert!(matches!(parse("nope"), Err(e) if e == "unknown command: nope"));
+    }
+
+    #[test]
+    fn set() {
+        assert!(matches!(
+            parse("set width = 8"),
+            Ok(Command::Set { option, value: Some(value) }) if option == "width" && value == "8"
+        ));
+        assert!(matches!(
+            parse("set endian"),
+            Ok(Command::Set { option, value: None }) if option == "endian"
+        ));
+        assert!(parse("set").is_err());
+    }
+
+    #[test]
+    fn fill() {
+        assert!(matches!(
+            parse("fill ff 0d16"),
+            Ok(Command::Fill {
+                byte: 0xff,
+                count: 16
+            })
+        ));
+        assert!(parse("fill 100 1").is_err());
+        assert!(parse("fill ff").is_err());
+        assert!(parse("fill ff 1 2").is_err());
+    }
+
+    #[test]
+    fn put() {
+        assert!(matches!(
+            parse("put u16le . + 2"),
+            Ok(Command::Put { ty, expression }) if ty == "u16le" && expression == ". + 2"
+        ));
+        assert!(parse("put f32 1").is_err());
+    }
+
+    #[test]
+    fn checksum_options() {
+        assert!(matches!(
+            parse("checksum crc32 0 end 10 2 be"),
+            Ok(Command::Checksum {
+                width: Some(2),
+                endianness: Some(Endianness::Big),
+                ..
+            })
+        ));
+        assert!(parse("checksum crc32 0 end").is_err());
+        assert!(parse("checksum crc32 0 end 10 3").is_err());
+    }
+
+    #[test]
+    fn optional_arguments() {
+        assert!(matches!(
+            parse("struct"),
+            Ok(Command::Struct {
+                name: None,
+                position: None
+            })
+        ));
+        assert!(matches!(
+            parse("inflate 40 out.bin"),
+            Ok(Command::Inflate { position: Some(position), file: Some(file) })
+                if position == "40" && file == "out.bin"
+        ));
+        assert!(matches!(
+            parse("pointers 4 1000"),
+            Ok(Command::Pointers {
+                size: Some(4),
+                base: Some(0x1000)
+            })
+        ));
+        assert!(parse("pointers 3").is_err());
+        assert!(matches!(
+            parse("play a 3"),
+            Ok(Command::Play {
+                register: 'a',
+                count: 3
+            })
+        ));
+        assert!(parse("play 1").is_err());
+    }
+
+    #[test]
+    fn filter() {
+        assert!(
+            matches!(parse("!tr a-z A-Z"), Ok(Command::Filter(command)) if command == "tr a-z A-Z")
+        );
+        assert!(parse("!").is_err());
+    }
+}
@@ -0,0 +1,52 @@
+use std::{collections::HashMap, fs, io::ErrorKind, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::keymap::Action;
+
+/// Settings read from `config.toml` in the `hexzen` directory of the user's config directory.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Keys mapped to the actions they run, replacing the default bindings of those keys.
+    pub keymap: HashMap<String, Action>,
+    /// Written as `[[highlight]]` tables.
+    #[serde(rename = "highlight")]
+    pub highlights: Vec<HighlightRule>,
+}
+
+/// Highlights the matches of `pattern`, or the bytes from `start` to `end`, in a layer named `name`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HighlightRule {
+    pub name: String,
+    pub pattern: Option<String>,
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+    /// Picked from the palette if not given.
+    pub color: Option<String>,
+}
+
+pub fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("hexzen").join("config.toml"))
+}
+
+/// The directory `:struct` looks for structure definitions in by name.
+pub fn structs_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("hexzen").join("structs"))
+}
+
+/// Reads the config file, giving the defaults if there is none.
+pub fn load() -> Result<ConfigFile, String> {
+    let Some(path) = path() else {
+        return Ok(ConfigFile::default());
+    };
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(ConfigFile::default()),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+
+    toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,180 @@
+use std::mem;
+
+use crossterm::style::Colors;
+
+use crate::{collapse::Run, layout::Layout};
+
+/// What a byte of the grid is drawn as: its value, its highlight, and whether the highlight continues over the space
+/// after it into the next byte.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub byte: u8,
+    pub colors: Option<Colors>,
+    pub joined: bool,
+}
+
+/// What a row of the grid was drawn from.
+#[derive(Default)]
+struct Row {
+    /// Whether the row was drawn since the grid was last forgotten.
+    drawn: bool,
+    offset: usize,
+    run: Option<Run>,
+    /// The bytes of the row up to the end of the file, or none for a collapsed row.
+    cells: Vec<Cell>,
+}
+
+/// The rows of the grid as they were last drawn, so that a redraw only draws the rows that changed since, like the row
+/// of a byte that was edited or of bytes whose highlight moved, and copies the others from the frame presented
+/// before. Anything that draws over the grid or clears the screen forgets them, so that the next grid is drawn in
+/// full.
+#[derive(Default)]
+pub struct Damage {
+    layout: Option<Layout>,
+    rows: Vec<Row>,
+    /// The cells of the row being drawn, which are swapped with the ones of the row they replace to reuse both.
+    cells: Vec<Cell>,
+}
+
+impl Damage {
+    /// Forgets what was drawn.
+    pub fn invalidate(&mut self) {
+        self.layout = None;
+    }
+
+    /// Starts drawing a grid of `rows` rows in `layout`, forgetting what was drawn if either changed.
+    pub fn start(&mut self, layout: Layout, rows: usize) {
+        if self.layout != Some(layout) || self.rows.len() != rows {
+            self.layout = Some(layout);
+            self.rows.resize_with(rows, Row::default);
+
+            for row in &mut self.rows {
+                row.drawn = false;
+            }
+        }
+    }
+
+    /// Starts building the cells of the next row.
+    pub fn clear_cells(&mut self) {
+        self.cells.clear();
+    }
+
+    pub fn push_cell(&mut self, cell: Cell) {
+        self.cells.push(cell);
+    }
+
+    /// Records that screen row `row` of the grid shows the row at `offset`, or the collapsed `run`, with the cells
+    /// pushed since [`Damage::clear_cells`], and returns whether it showed something else before and has to be drawn.
+    pub fn update(&mut self, row: usize, offset: usize, run: Option<Run>) -> bool {
+        let drawn = &mut self.rows[row];
+
+        if drawn.drawn && drawn.offset == offset && drawn.run == run && drawn.cells == self.cells {
+            return false;
+        }
+
+        drawn.drawn = true;
+        drawn.offset = offset;
+        drawn.run = run;
+        mem::swap(&mut drawn.cells, &mut self.cells);
+
+        true
+    }
+
+    /// Returns the cells screen row `row` of the grid was last drawn with.
+    pub fn cells(&self, row: usize) -> &[Cell] {
+        &self.rows[row].cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Color;
+
+    use super::*;
+
+    fn cell(byte: u8) -> Cell {
+        Cell {
+            byte,
+            colors: None,
+            joined: false,
+        }
+    }
+
+    /// Records `bytes` for screen row `row`, returning whether it has to be drawn.
+    fn update(damage: &mut Damage, row: usize, offset: usize, bytes: &[u8]) -> bool {
+        damage.clear_cells();
+        for &byte in bytes {
+            damage.push_cell(cell(byte));
+        }
+
+        damage.update(row, offset, None)
+    }
+
+    #[test]
+    fn only_changed_rows_are_drawn() {
+        let layout = Layout::new(80, 16, 8).unwrap();
+        let mut damage = Damage::default();
+
+        damage.start(layout, 2);
+        assert!(update(&mut damage, 0, 0, &[1, 2]));
+        assert!(update(&mut damage, 1, 16, &[3]));
+
+        damage.start(layout, 2);
+        assert!(!update(&mut damage, 0, 0, &[1, 2]));
+        assert!(update(&mut damage, 1, 16, &[4]));
+        assert!(damage.cells(1) == [cell(4)]);
+
+        // scrolled by a row
+        damage.start(layout, 2);
+        assert!(update(&mut damage, 0, 16, &[4]));
+        assert!(update(&mut damage, 1, 32, &[]));
+    }
+
+    #[test]
+    fn highlights_and_runs_damage_rows() {
+        let layout = Layout::new(80, 16, 8).unwrap();
+        let mut damage = Damage::default();
+        let run = Run {
+            start: 16,
+            end: 64,
+            byte: 0,
+        };
+
+        damage.start(layout, 1);
+        update(&mut damage, 0, 0, &[1]);
+
+        damage.start(layout, 1);
+        damage.clear_cells();
+        damage.push_cell(Cell {
+            colors: Some(Colors::new(Color::Black, Color::Grey)),
+            ..cell(1)
+        });
+        assert!(damage.update(0, 0, None));
+
+        damage.start(layout, 1);
+        damage.clear_cells();
+        assert!(damage.update(0, 0, Some(run)));
+
+        damage.start(layout, 1);
+        damage.clear_cells();
+        assert!(!damage.update(0, 0, Some(run)));
+    }
+
+    #[test]
+    fn invalidating_or_resizing_draws_everything() {
+        let layout = Layout::new(80, 16, 8).unwrap();
+        let mut damage = Damage::default();
+
+        damage.start(layout, 1);
+        update(&mut damage, 0, 0, &[1]);
+        damage.invalidate();
+        damage.start(layout, 1);
+        assert!(update(&mut damage, 0, 0, &[1]));
+
+        damage.start(Layout::new(40, 16, 8).unwrap(), 1);
+        assert!(update(&mut damage, 0, 0, &[1]));
+
+        damage.start(Layout::new(40, 16, 8).unwrap(), 2);
+        assert!(update(&mut damage, 0, 0, &[1]));
+    }
+}
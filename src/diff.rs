@@ -0,0 +1,150 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+};
+
+use crossterm::style::{Color, Stylize};
+
+use crate::{
+    dump::{open_window, read_full},
+    hex, BYTES_PER_ROW,
+};
+
+/// Prints the rows of a hex dump that differ between `a` and `b`, the rows of `a` marked with `-` and those of `b` with
+/// `+`, along with up to `context` identical rows around them. Omitted rows are replaced by a single `*`. If `colors`
+/// is set, the differing bytes are red in `a` and green in `b`. Returns whether the files differ.
+pub fn diff(
+    out: &mut impl Write,
+    a: &str,
+    b: &str,
+    context: usize,
+    colors: bool,
+) -> Result<bool, io::Error> {
+    let open = |file: &str| {
+        open_window(file, 0, None).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", file, e)))
+    };
+    let mut reader_a = open(a)?;
+    let mut reader_b = open(b)?;
+
+    let mut row_a = vec![0; BYTES_PER_ROW];
+    let mut row_b = vec![0; BYTES_PER_ROW];
+    let mut offset = 0;
+
+    // identical rows are kept until it is known whether a difference follows them closely enough to show them
+    let mut before = VecDeque::new();
+    let mut after = 0;
+    let mut omitted = false;
+    let mut differs = false;
+
+    loop {
+        let len_a = read_full(&mut reader_a, &mut row_a)?;
+        let len_b = read_full(&mut reader_b, &mut row_b)?;
+        if len_a == 0 && len_b == 0 {
+            break;
+        }
+
+        let (bytes_a, bytes_b) = (&row_a[..len_a], &row_b[..len_b]);
+
+        if bytes_a == bytes_b {
+            if after > 0 {
+                write_row(out, ' ', offset, bytes_a, None, colors)?;
+                after -= 1;
+            } else {
+                before.push_back((offset, bytes_a.to_vec()));
+
+                if before.len() > context {
+                    before.pop_front();
+                    omitted = true;
+                }
+            }
+        } else {
+            if !differs {
+                writeln!(out, "--- {}\n+++ {}", a, b)?;
+                differs = true;
+            }
+
+            if omitted {
+                writeln!(out, "*")?;
+                omitted = false;
+            }
+
+            for (offset, bytes) in before.drain(..) {
+                write_row(out, ' ', offset, &bytes, None, colors)?;
+            }
+
+            if !bytes_a.is_empty() {
+                write_row(
+                    out,
+                    '-',
+                    offset,
+                    bytes_a,
+                    Some((bytes_b, Color::Red)),
+                    colors,
+                )?;
+            }
+            if !bytes_b.is_empty() {
+                write_row(
+                    out,
+                    '+',
+                    offset,
+                    bytes_b,
+                    Some((bytes_a, Color::Green)),
+                    colors,
+                )?;
+            }
+
+            after = context;
+        }
+
+        offset += len_a.max(len_b);
+    }
+
+    if differs && (omitted || !before.is_empty()) {
+        writeln!(out, "*")?;
+    }
+
+    Ok(differs)
+}
+
+/// Prints a row of the hex dump starting with `marker`. If `other` is given, the bytes that differ from it are colored.
+fn write_row(
+    out: &mut impl Write,
+    marker: char,
+    offset: usize,
+    bytes: &[u8],
+    other: Option<(&[u8], Color)>,
+    colors: bool,
+) -> Result<(), io::Error> {
+    let paint = |text: String, col: usize| match other {
+        Some((other, color)) if colors && other.get(col) != bytes.get(col) => {
+            text.with(color).to_string()
+        }
+        _ => text,
+    };
+
+    write!(out, "{}{}   ", marker, hex(offset, 8, false))?;
+
+    for col in 0..BYTES_PER_ROW {
+        if col == BYTES_PER_ROW / 2 {
+            write!(out, " ")?;
+        }
+
+        match bytes.get(col) {
+            Some(&byte) => write!(out, "{} ", paint(hex(byte.into(), 2, false), col))?,
+            None => write!(out, "   ")?,
+        }
+    }
+
+    write!(out, "  ")?;
+
+    for (col, &byte) in bytes.iter().enumerate() {
+        let c = match byte {
+            32..=126 => byte as char,
+            _ => '.',
+        };
+
+        write!(out, "{}", paint(c.to_string(), col))?;
+    }
+
+    writeln!(out)
+}
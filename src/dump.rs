@@ -0,0 +1,869 @@
+//! The formats files are printed in instead of opening the editor: hex dumps like those of hexdump and xxd, the
+//! values of units like `od -t`, records for other programs and literals for pasting into code. Each function reads
+//! at most `length` bytes of a file starting at `skip`, or of stdin if the file is `-`.
+
+use std::{
+    fs::{self, File},
+    io::{self, stdin, BufReader, Read, Seek, SeekFrom, Write},
+    mem,
+    ops::Range,
+};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use clap::ValueEnum;
+use crossterm::style::{Color, Stylize};
+
+use crate::{hex, search::Pattern, value, Endianness, BYTES_PER_ROW};
+
+/// Characters in each line printed by `base64`, the same as by the `base64` tool.
+const BASE64_LINE_LENGTH: usize = 76;
+
+/// How the hex dump and the values of units are laid out.
+#[derive(Clone, Copy, Debug)]
+pub struct DumpOptions {
+    pub bytes_per_row: usize,
+    /// Number of bytes in each space-separated group of the hex dump.
+    pub group_size: usize,
+    pub uppercase_hex: bool,
+    /// The radix of the offset at the start of each row.
+    pub offset_radix: OffsetRadix,
+    /// Whether bytes are colored by their kind, like hexyl does.
+    pub colors: bool,
+    /// Shown in place of characters that aren't printable ascii.
+    pub replacement_char: char,
+    /// The byte order of the groups shown as single values and of the units.
+    pub endianness: Endianness,
+}
+
+impl Default for DumpOptions {
+    /// The layout of `hexzen --dump`, without colors.
+    fn default() -> DumpOptions {
+        DumpOptions {
+            bytes_per_row: BYTES_PER_ROW,
+            group_size: 8,
+            uppercase_hex: false,
+            offset_radix: OffsetRadix::Hex,
+            colors: false,
+            replacement_char: '.',
+            endianness: Endianness::Little,
+        }
+    }
+}
+
+/// Reads `file`, or stdin if it is `-`.
+pub(crate) fn read_input(file: &str) -> Result<Vec<u8>, io::Error> {
+    if file == "-" {
+        let mut buf = Vec::new();
+        stdin().lock().read_to_end(&mut buf)?;
+        Ok(buf)
+    } else {
+        fs::read(file)
+    }
+}
+
+/// Reads at most `length` bytes of `file` starting at `skip`, without reading the rest of the file unless it is stdin.
+fn read_window(file: &str, skip: usize, length: Option<usize>) -> Result<Vec<u8>, io::Error> {
+    let mut buffer = Vec::new();
+    open_window(file, skip, length)?.read_to_end(&mut buffer)?;
+
+    Ok(buffer)
+}
+
+/// Opens `file`, or stdin if it is `-`, for reading at most `length` bytes starting at `skip`.
+pub(crate) fn open_window(
+    file: &str,
+    skip: usize,
+    length: Option<usize>,
+) -> Result<Box<dyn Read>, io::Error> {
+    let length = length.map_or(u64::MAX, |length| length as u64);
+
+    if file == "-" {
+        let mut stdin = stdin().lock();
+        io::copy(&mut (&mut stdin).take(skip as u64), &mut io::sink())?;
+        Ok(Box::new(stdin.take(length)))
+    } else {
+        let mut file = File::open(file)?;
+        file.seek(SeekFrom::Start(skip as u64))?;
+        Ok(Box::new(BufReader::new(file.take(length))))
+    }
+}
+
+/// Fills `buffer` from `reader`, returning how many bytes were read, which is less than its length only at the end.
+pub(crate) fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> Result<usize, io::Error> {
+    let mut filled = 0;
+
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(filled)
+}
+
+/// Prints a hex dump of at most `length` bytes of `file` starting at `skip`, with the offsets they have in the file.
+/// If `bits` is set, the bytes are shown as binary digits instead. If `words` is set, each group is shown as a single
+/// value in the byte order of `options`. If `squeeze` is set, rows repeating the previous one are replaced by a single
+/// `*`. The matches of `highlight` are shown in reverse video. The file is read a row at a time, so that piped input is
+/// dumped as it arrives.
+#[allow(clippy::too_many_arguments)]
+pub fn hexdump(
+    out: &mut impl Write,
+    file: &str,
+    skip: usize,
+    length: Option<usize>,
+    bits: bool,
+    words: bool,
+    squeeze: bool,
+    highlight: Option<&Pattern>,
+    options: &DumpOptions,
+) -> Result<(), io::Error> {
+    // matches can span rows, so the window is read at once to find them
+    let (mut reader, matches): (Box<dyn Read>, Vec<Range<usize>>) = match highlight {
+        Some(pattern) => {
+            let buffer = read_window(file, skip, length)?;
+            let matches = pattern
+                .find_all(&buffer)
+                .into_iter()
+                .map(|i| skip + i..skip + i + pattern.len())
+                .collect();
+
+            (Box::new(io::Cursor::new(buffer)), matches)
+        }
+        None => (open_window(file, skip, length)?, Vec::new()),
+    };
+    let highlighted = |offset: usize| {
+        let i = matches.partition_point(|range| range.end <= offset);
+        matches.get(i).is_some_and(|range| range.start <= offset)
+    };
+
+    let width = options.bytes_per_row;
+    let digits = if bits { 8 } else { 2 };
+
+    let mut header = String::from("           ");
+    if words {
+        for col in (0..width).step_by(options.group_size) {
+            let group_digits = digits * options.group_size.min(width - col);
+            header.push_str(&format!(
+                " {:<group_digits$}",
+                hex(col % 0x100, 2, options.uppercase_hex)
+            ));
+        }
+    } else {
+        for col in 0..width {
+            if col > 0 && col % options.group_size == 0 {
+                header.push(' ');
+            }
+
+            header.push_str(&format!(
+                " {:<digits$}",
+                hex(col % 0x100, 2, options.uppercase_hex)
+            ));
+        }
+    }
+    writeln!(out, "{}\n", header.trim_end())?;
+
+    // colors the bytes by their kind and dims the offsets, like hexyl does
+    let paint = |text: String, color: Option<Color>| match (options.colors, color) {
+        (false, _) => text,
+        (true, Some(color)) => text.with(color).to_string(),
+        (true, None) => text.dim().to_string(),
+    };
+    let paint_byte =
+        |text: String, byte: u8, offset: usize| match options.colors && highlighted(offset) {
+            true => text.with(byte_color(byte)).reverse().to_string(),
+            false => paint(text, Some(byte_color(byte))),
+        };
+
+    let mut previous = vec![0; width];
+    let mut row = vec![0; width];
+    let mut next = vec![0; width];
+    let mut len = read_full(&mut reader, &mut row)?;
+    let mut offset = skip;
+    let mut squeezed = false;
+
+    while len > 0 {
+        // the next row is read ahead because the last row is always shown, so that the dump ends at the right offset
+        let next_len = read_full(&mut reader, &mut next)?;
+
+        // rows holding a match are always shown
+        if squeeze
+            && offset > skip
+            && next_len > 0
+            && row == previous
+            && !(offset..offset + len).any(highlighted)
+        {
+            if !squeezed {
+                writeln!(out, "*")?;
+                squeezed = true;
+            }
+        } else {
+            squeezed = false;
+
+            write!(
+                out,
+                " {}   ",
+                paint(
+                    options.offset_radix.format(offset, options.uppercase_hex),
+                    None
+                )
+            )?;
+
+            let cell = |col: usize| match row.get(col).filter(|_| col < len) {
+                None => format!("{:digits$}", ""),
+                Some(&c) => {
+                    let text = match bits {
+                        true => format!("{:08b}", c),
+                        false => hex(c.into(), 2, options.uppercase_hex),
+                    };
+
+                    paint_byte(text, c, offset + col)
+                }
+            };
+
+            if words {
+                for start in (0..width).step_by(options.group_size) {
+                    let group = start..width.min(start + options.group_size);
+
+                    // the missing bytes of a group cut off by the end of the file are blank
+                    match options.endianness {
+                        Endianness::Little => group
+                            .rev()
+                            .try_for_each(|col| write!(out, "{}", cell(col)))?,
+                        Endianness::Big => group
+                            .into_iter()
+                            .try_for_each(|col| write!(out, "{}", cell(col)))?,
+                    }
+
+                    write!(out, " ")?;
+                }
+            } else {
+                for col in 0..width {
+                    if col > 0 && col % options.group_size == 0 {
+                        write!(out, " ")?;
+                    }
+
+                    write!(out, "{} ", cell(col))?;
+                }
+            }
+
+            write!(out, "  ")?;
+
+            for (col, &byte) in row[..len].iter().enumerate() {
+                let mut c = byte as char;
+
+                if !(32..=126).contains(&byte) {
+                    c = options.replacement_char;
+                }
+
+                write!(out, "{}", paint_byte(c.to_string(), byte, offset + col))?;
+            }
+
+            writeln!(out)?;
+        }
+
+        // rotates the rows so that none of them is reallocated
+        mem::swap(&mut previous, &mut row);
+        mem::swap(&mut row, &mut next);
+        offset += len;
+        len = next_len;
+    }
+
+    Ok(())
+}
+
+/// Returns the color a byte is shown in by the hex dump.
+fn byte_color(byte: u8) -> Color {
+    match byte {
+        0 => Color::DarkGrey,
+        _ if byte.is_ascii_whitespace() => Color::Green,
+        b' '..=b'~' => Color::Cyan,
+        _ if byte.is_ascii() => Color::Magenta,
+        _ => Color::Yellow,
+    }
+}
+
+/// Prints at most `length` bytes of `file` starting at `skip` as a C array in the same format as `xxd -i`.
+pub fn include(
+    out: &mut impl Write,
+    file: &str,
+    skip: usize,
+    length: Option<usize>,
+    uppercase: bool,
+) -> Result<(), io::Error> {
+    let buffer = read_window(file, skip, length)?;
+    let lines: Vec<String> = buffer
+        .chunks(12)
+        .map(|chunk| {
+            let bytes: Vec<String> = chunk
+                .iter()
+                .map(|&byte| format!("0x{}", hex(byte.into(), 2, uppercase)))
+                .collect();
+            format!("  {}", bytes.join(", "))
+        })
+        .collect();
+
+    // like xxd, only the bytes are printed for stdin since there is no name for the array
+    if file == "-" {
+        if !lines.is_empty() {
+            writeln!(out, "{}", lines.join(",\n"))?;
+        }
+
+        return Ok(());
+    }
+
+    let mut name: String = file
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert_str(0, "__");
+    }
+
+    writeln!(out, "unsigned char {}[] = {{", name)?;
+    if !lines.is_empty() {
+        writeln!(out, "{}", lines.join(",\n"))?;
+    }
+    writeln!(out, "}};")?;
+    writeln!(out, "unsigned int {}_len = {};", name, buffer.len())?;
+
+    Ok(())
+}
+
+/// Prints at most `length` bytes of `file` starting at `skip` as a literal of `language`, for pasting into code. Rust
+/// gets a byte slice like `-i` gives, while Python and C get strings split into lines of 16 bytes.
+pub fn literal(
+    out: &mut impl Write,
+    file: &str,
+    skip: usize,
+    length: Option<usize>,
+    language: Language,
+    uppercase: bool,
+) -> Result<(), io::Error> {
+    let buffer = read_window(file, skip, length)?;
+
+    if let Language::Rust = language {
+        if buffer.is_empty() {
+            return writeln!(out, "&[]");
+        }
+
+        writeln!(out, "&[")?;
+        for chunk in buffer.chunks(12) {
+            let bytes: Vec<String> = chunk
+                .iter()
+                .map(|&byte| format!("0x{},", hex(byte.into(), 2, uppercase)))
+                .collect();
+            writeln!(out, "    {}", bytes.join(" "))?;
+        }
+        return writeln!(out, "]");
+    }
+
+    let prefix = match language {
+        Language::Python => "b",
+        _ => "",
+    };
+    let lines: Vec<String> = buffer
+        .chunks(BYTES_PER_ROW)
+        .map(|chunk| {
+            let mut line = format!("{}\"", prefix);
+
+            for (i, &byte) in chunk.iter().enumerate() {
+                match byte {
+                    b'"' | b'\\' => line.extend(['\\', byte as char]),
+                    b'\n' => line.push_str("\\n"),
+                    b'\r' => line.push_str("\\r"),
+                    b'\t' => line.push_str("\\t"),
+                    32..=126 => line.push(byte as char),
+                    _ => {
+                        line.push_str(&format!("\\x{}", hex(byte.into(), 2, uppercase)));
+
+                        // hex escapes in C take as many digits as follow them, so the string is split before a digit
+                        let digit_follows = chunk.get(i + 1).is_some_and(u8::is_ascii_hexdigit);
+                        if let (Language::C, true) = (language, digit_follows) {
+                            line.push_str("\"\"");
+                        }
+                    }
+                }
+            }
+
+            line.push('"');
+            line
+        })
+        .collect();
+
+    match (language, lines.len()) {
+        (_, 0) => writeln!(out, "{}\"\"", prefix),
+        (_, 1) => writeln!(out, "{}", lines[0]),
+        // python only joins strings on several lines within parentheses
+        (Language::Python, _) => writeln!(out, "(\n    {}\n)", lines.join("\n    ")),
+        _ => writeln!(out, "{}", lines.join("\n")),
+    }
+}
+
+/// Prints at most `length` bytes of `file` starting at `skip` as hex digits, `width` bytes to a line.
+pub fn plain(
+    out: &mut impl Write,
+    file: &str,
+    skip: usize,
+    length: Option<usize>,
+    width: usize,
+    uppercase: bool,
+) -> Result<(), io::Error> {
+    let buffer = read_window(file, skip, length)?;
+
+    for chunk in buffer.chunks(width) {
+        for &byte in chunk {
+            write!(out, "{}", hex(byte.into(), 2, uppercase))?;
+        }
+
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Prints at most `length` bytes of `file` starting at `skip` as base64, wrapped like by the `base64` tool.
+pub fn base64(
+    out: &mut impl Write,
+    file: &str,
+    skip: usize,
+    length: Option<usize>,
+) -> Result<(), io::Error> {
+    let buffer = read_window(file, skip, length)?;
+    let encoded = BASE64_STANDARD.encode(buffer);
+
+    for line in encoded.as_bytes().chunks(BASE64_LINE_LENGTH) {
+        out.write_all(line)?;
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the values of the units of at most `length` bytes of `file` starting at `skip` like `od`, a line for each of
+/// `types` in each row. If `squeeze` is set, rows repeating the previous one are replaced by a single `*`.
+pub fn numbers(
+    out: &mut impl Write,
+    file: &str,
+    skip: usize,
+    length: Option<usize>,
+    types: &[NumberType],
+    squeeze: bool,
+    options: &DumpOptions,
+) -> Result<(), io::Error> {
+    let mut reader = open_window(file, skip, length)?;
+    let width = options.bytes_per_row;
+
+    // like in od, the columns of all types line up, each byte taking as much space as it needs in the widest type,
+    // which can be a fraction of a column. The column a unit ends at is rounded up
+    let (columns, bytes) = types
+        .iter()
+        .map(|ty| (ty.digits() + 1, ty.size))
+        .max_by(|(a, a_size), (b, b_size)| (a * b_size).cmp(&(b * a_size)))
+        .unwrap_or((1, 1));
+    let end_column = |byte: usize| (byte * columns).div_ceil(bytes);
+
+    let mut previous = vec![0; width];
+    let mut row = vec![0; width];
+    let mut next = vec![0; width];
+    let mut len = read_full(&mut reader, &mut row)?;
+    let mut offset = skip;
+    let mut squeezed = false;
+
+    while len > 0 {
+        let next_len = read_full(&mut reader, &mut next)?;
+
+        if squeeze && offset > skip && next_len > 0 && row == previous {
+            if !squeezed {
+                writeln!(out, "*")?;
+                squeezed = true;
+            }
+        } else {
+            squeezed = false;
+
+            for (i, ty) in types.iter().enumerate() {
+                match i {
+                    0 => write!(
+                        out,
+                        "{}",
+                        options.offset_radix.format(offset, options.uppercase_hex)
+                    )?,
+                    _ => write!(out, "{:8}", "")?,
+                }
+
+                // like in od, a unit cut off by the end of the input is padded with zeros
+                let units = len.div_ceil(ty.size);
+                row[len..].fill(0);
+
+                for (i, unit) in row.chunks(ty.size).enumerate().take(units) {
+                    let value = value::read_uint(unit, options.endianness);
+                    let start = i * ty.size;
+                    let width = end_column(start + ty.size) - end_column(start) - 1;
+                    write!(out, " {:>width$}", ty.format(value, options.uppercase_hex))?;
+                }
+
+                writeln!(out)?;
+            }
+        }
+
+        mem::swap(&mut previous, &mut row);
+        mem::swap(&mut row, &mut next);
+        offset += len;
+        len = next_len;
+    }
+
+    // od ends with the offset of the end of the input
+    writeln!(
+        out,
+        "{}",
+        options.offset_radix.format(offset, options.uppercase_hex)
+    )
+}
+
+/// Prints a record of the offset, hex digits and characters of each row of at most `length` bytes of `files` starting
+/// at `skip`, for reading by other programs. The records name their file if there are several.
+pub fn records(
+    out: &mut impl Write,
+    files: &[String],
+    skip: usize,
+    length: Option<usize>,
+    format: RecordFormat,
+    options: &DumpOptions,
+) -> Result<(), io::Error> {
+    let named = files.len() > 1;
+
+    match (format, named) {
+        (RecordFormat::Json, _) => write!(out, "[")?,
+        (RecordFormat::Csv, false) => writeln!(out, "offset,hex,ascii")?,
+        (RecordFormat::Csv, true) => writeln!(out, "file,offset,hex,ascii")?,
+    }
+
+    let mut first = true;
+
+    for file in files {
+        let mut reader = open_window(file, skip, length)?;
+        let mut row = vec![0; options.bytes_per_row];
+        let mut offset = skip;
+
+        loop {
+            let len = read_full(&mut reader, &mut row)?;
+            if len == 0 {
+                break;
+            }
+
+            let bytes = &row[..len];
+            let hex = bytes
+                .iter()
+                .map(|&byte| hex(byte.into(), 2, options.uppercase_hex))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = bytes
+                .iter()
+                .map(|&byte| match byte {
+                    32..=126 => byte as char,
+                    _ => options.replacement_char,
+                })
+                .collect::<String>();
+
+            match format {
+                RecordFormat::Json => {
+                    write!(out, "{}\n  {{", if first { "" } else { "," })?;
+                    if named {
+                        write!(out, "\"file\": {}, ", json_string(file))?;
+                    }
+                    write!(
+                        out,
+                        "\"offset\": {}, \"hex\": \"{}\", \"ascii\": {}}}",
+                        offset,
+                        hex,
+                        json_string(&ascii)
+                    )?;
+                }
+                RecordFormat::Csv => {
+                    if named {
+                        write!(out, "{},", csv_field(file))?;
+                    }
+                    writeln!(out, "{},{},{}", offset, hex, csv_field(&ascii))?;
+                }
+            }
+
+            first = false;
+            offset += len;
+        }
+    }
+
+    if let RecordFormat::Json = format {
+        writeln!(out, "{}]", if first { "" } else { "\n" })?;
+    }
+
+    Ok(())
+}
+
+/// Quotes `text` as a JSON string.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Quotes `text` as a CSV field if it holds a comma, quote or line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// How `-t` writes the value of a unit.
+#[derive(Clone, Copy, Debug)]
+pub enum NumberKind {
+    Signed,
+    Unsigned,
+    Octal,
+    Hex,
+}
+
+/// How `-t` prints the units of a row.
+#[derive(Clone, Copy, Debug)]
+pub struct NumberType {
+    pub kind: NumberKind,
+    /// In bytes.
+    pub size: usize,
+}
+
+impl NumberType {
+    /// Parses an od type like `d1`, `u2`, `o4` or `x8`. The size is 4 bytes if it isn't given.
+    pub fn parse(input: &str) -> Result<NumberType, String> {
+        let invalid = || format!("invalid type: {}", input);
+
+        let mut chars = input.chars();
+        let kind = match chars.next() {
+            Some('d') => NumberKind::Signed,
+            Some('u') => NumberKind::Unsigned,
+            Some('o') => NumberKind::Octal,
+            Some('x') => NumberKind::Hex,
+            _ => return Err(invalid()),
+        };
+        let size = match chars.as_str() {
+            "" => 4,
+            size => size.parse().map_err(|_| invalid())?,
+        };
+
+        if ![1, 2, 4, 8].contains(&size) {
+            return Err(invalid());
+        }
+
+        Ok(NumberType { kind, size })
+    }
+
+    /// Returns the width of the widest value of this type.
+    pub fn digits(self) -> usize {
+        let bits = 8 * self.size as u32;
+
+        match self.kind {
+            NumberKind::Signed => (i64::MIN >> (64 - bits)).to_string().len(),
+            NumberKind::Unsigned => (u64::MAX >> (64 - bits)).to_string().len(),
+            NumberKind::Octal => bits.div_ceil(3) as usize,
+            NumberKind::Hex => 2 * self.size,
+        }
+    }
+
+    /// Formats `value`, read from a unit of this size. Octal and hex values are padded with zeros.
+    pub fn format(self, value: u64, uppercase: bool) -> String {
+        let digits = self.digits();
+
+        match self.kind {
+            NumberKind::Signed => {
+                // sign-extend from the size of the unit
+                let shift = 64 - 8 * self.size;
+                (((value << shift) as i64) >> shift).to_string()
+            }
+            NumberKind::Unsigned => value.to_string(),
+            NumberKind::Octal => format!("{:0digits$o}", value),
+            NumberKind::Hex => hex(value as usize, digits, uppercase),
+        }
+    }
+}
+
+/// The languages `literal` writes literals of.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Language {
+    Rust,
+    Python,
+    C,
+}
+
+/// The formats of the records written by `records`.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum RecordFormat {
+    Json,
+    Csv,
+}
+
+/// How the offsets at the start of rows are written.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum OffsetRadix {
+    Hex,
+    Decimal,
+    Octal,
+}
+
+impl OffsetRadix {
+    /// Formats `offset` as at least 8 digits, padded with zeros.
+    pub fn format(self, offset: usize, uppercase: bool) -> String {
+        match self {
+            OffsetRadix::Hex => hex(offset, 8, uppercase),
+            OffsetRadix::Decimal => format!("{:08}", offset),
+            OffsetRadix::Octal => format!("{:08o}", offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    /// A file holding the input of a test, removed when the test ends.
+    struct Input(String);
+
+    impl Input {
+        fn new(name: &str, bytes: &[u8]) -> Input {
+            let path = env::temp_dir().join(format!("hexzen-dump-{}-{}", process::id(), name));
+            fs::write(&path, bytes).unwrap();
+
+            Input(path.to_str().unwrap().to_owned())
+        }
+    }
+
+    impl Drop for Input {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn output(dump: impl FnOnce(&mut Vec<u8>) -> Result<(), io::Error>) -> String {
+        let mut out = Vec::new();
+        dump(&mut out).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn hexdump_rows() {
+        let Input(file) = &Input::new("rows", b"hello, world!\x00\x01\xffab");
+        let dump = output(|out| {
+            hexdump(
+                out,
+                file,
+                0,
+                None,
+                false,
+                false,
+                true,
+                None,
+                &DumpOptions::default(),
+            )
+        });
+
+        assert_eq!(
+            dump.lines().collect::<Vec<_>>(),
+            [
+                "            00 01 02 03 04 05 06 07  08 09 0a 0b 0c 0d 0e 0f",
+                "",
+                " 00000000   68 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 00 01 ff   hello, world!...",
+                " 00000010   61 62                                              ab",
+            ]
+        );
+    }
+
+    #[test]
+    fn hexdump_window_and_squeeze() {
+        let Input(file) = &Input::new("squeeze", &[0; 64]);
+        let dump = output(|out| {
+            hexdump(
+                out,
+                file,
+                8,
+                Some(48),
+                false,
+                false,
+                true,
+                None,
+                &DumpOptions::default(),
+            )
+        });
+
+        let rows: Vec<_> = dump.lines().skip(2).collect();
+
+        assert_eq!(rows.len(), 3);
+        assert!(rows[0].starts_with(" 00000008 "));
+        assert_eq!(rows[1], "*");
+        // the last row is shown even when it repeats, to show where the dump ends
+        assert!(rows[2].starts_with(" 00000028 "));
+    }
+
+    #[test]
+    fn include_like_xxd() {
+        let Input(file) = &Input::new("include", &[0, 1, 2, 0xff]);
+        let dump = output(|out| include(out, file, 1, Some(2), false));
+        let name = file.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+
+        assert_eq!(
+            dump,
+            format!(
+                "unsigned char {name}[] = {{\n  0x01, 0x02\n}};\nunsigned int {name}_len = 2;\n"
+            )
+        );
+    }
+
+    #[test]
+    fn plain_and_base64() {
+        let Input(file) = &Input::new("plain", b"\xde\xad\xbe\xefhexzen");
+
+        assert_eq!(
+            output(|out| plain(out, file, 0, None, 4, false)),
+            "deadbeef\n6865787a\n656e\n"
+        );
+        assert_eq!(
+            output(|out| plain(out, file, 0, Some(2), 4, true)),
+            "DEAD\n"
+        );
+        assert_eq!(output(|out| base64(out, file, 4, None)), "aGV4emVu\n");
+    }
+
+    #[test]
+    fn number_types() {
+        assert!(matches!(
+            NumberType::parse("u2"),
+            Ok(NumberType {
+                kind: NumberKind::Unsigned,
+                size: 2
+            })
+        ));
+        assert!(matches!(
+            NumberType::parse("x"),
+            Ok(NumberType {
+                kind: NumberKind::Hex,
+                size: 4
+            })
+        ));
+        assert!(NumberType::parse("u3").is_err());
+        assert!(NumberType::parse("f4").is_err());
+    }
+}
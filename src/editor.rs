@@ -0,0 +1,424 @@
+//! Editing the bytes of a file in memory, with undo and redo and tracking of which bytes have changed.
+
+use std::{collections::BTreeMap, fs, io, sync::Arc};
+
+/// A file loaded into memory for editing, along with where the editor shows it.
+pub struct FileEditor {
+    /// Shared with background searches, and copied on write while one is running.
+    pub(crate) buffer: Arc<Vec<u8>>,
+    pub(crate) filename: String,
+    pub(crate) offset: usize,
+    pub(crate) cursor_nibble: usize,
+    pub(crate) saved: bool,
+    /// Incremented on every change to `buffer`, so views derived from the contents know when to recompute.
+    pub(crate) generation: u64,
+    /// The saved value of every byte that has been changed since the last save.
+    modified: BTreeMap<usize, u8>,
+    /// The loaded value of every byte that differs from the file as it was loaded, kept across saves.
+    original: BTreeMap<usize, u8>,
+    /// Each entry is a change made by a single command, undone and redone as a whole.
+    undo_stack: Vec<Vec<Edit>>,
+    redo_stack: Vec<Vec<Edit>>,
+}
+
+pub(crate) struct Edit {
+    position: usize,
+    prev_byte: u8,
+    new_byte: u8,
+}
+
+impl FileEditor {
+    /// Reads `filename` to edit it.
+    pub fn new(filename: &str) -> Result<FileEditor, io::Error> {
+        Ok(FileEditor::from_bytes(filename, fs::read(filename)?))
+    }
+
+    /// Edits `buffer` as the contents of `filename`, which is only written to when saving.
+    pub fn from_bytes(filename: &str, buffer: Vec<u8>) -> FileEditor {
+        FileEditor {
+            buffer: Arc::new(buffer),
+            filename: filename.to_owned(),
+            offset: 0,
+            cursor_nibble: 0,
+            saved: true,
+            generation: 0,
+            modified: BTreeMap::new(),
+            original: BTreeMap::new(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+
+    /// The size of the buffer, which edits never change.
+    pub fn file_size(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The contents of the file with the changes made so far.
+    pub fn bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// Whether the contents are the same as when the file was last saved, or loaded if it hasn't been.
+    pub fn is_saved(&self) -> bool {
+        self.saved
+    }
+
+    /// Returns a number that changes whenever the contents do, so that views derived from them know when to recompute.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns the offset of the byte at the cursor.
+    pub fn cursor(&self) -> usize {
+        self.cursor_nibble / 2
+    }
+
+    /// Moves the cursor to the high nibble of the byte at `position`, which must be in the file.
+    pub fn set_cursor(&mut self, position: usize) -> Result<(), io::Error> {
+        self.check_range(position, 1)?;
+        self.cursor_nibble = 2 * position;
+
+        Ok(())
+    }
+
+    /// Writes `nibble` to the nibble at the cursor as a single change. The cursor stays where it is. Fails if the
+    /// cursor isn't on a byte, which only happens in an empty file.
+    pub fn write_nibble(&mut self, nibble: u8) -> Result<(), io::Error> {
+        let position = self.cursor_nibble / 2;
+        self.check_range(position, 1)?;
+        let byte = self.buffer[position];
+
+        let new_byte = if self.cursor_nibble.is_multiple_of(2) {
+            (byte & 0x0f) | (nibble << 4)
+        } else {
+            (byte & 0xf0) | (nibble & 0x0f)
+        };
+
+        self.push_undo(vec![Edit {
+            position,
+            prev_byte: byte,
+            new_byte,
+        }]);
+
+        self.set_byte(position, new_byte);
+
+        Ok(())
+    }
+
+    /// Writes `bytes` at each of `positions` as a single change, returning the number of positions where that changed
+    /// anything. Nothing is written if the bytes don't fit at any of the positions.
+    pub fn replace_all(&mut self, positions: &[usize], bytes: &[u8]) -> Result<usize, io::Error> {
+        for &position in positions {
+            self.check_range(position, bytes.len())?;
+        }
+
+        let mut edits = Vec::new();
+        let mut changed = 0;
+
+        for &position in positions {
+            let edit_count = edits.len();
+
+            for (i, &new_byte) in bytes.iter().enumerate() {
+                let prev_byte = self.buffer[position + i];
+
+                if prev_byte != new_byte {
+                    edits.push(Edit {
+                        position: position + i,
+                        prev_byte,
+                        new_byte,
+                    });
+                    self.set_byte(position + i, new_byte);
+                }
+            }
+
+            if edits.len() > edit_count {
+                changed += 1;
+            }
+        }
+
+        if !edits.is_empty() {
+            self.push_undo(edits);
+        }
+
+        Ok(changed)
+    }
+
+    /// Writes the bytes of each `(position, bytes)` pair in order, as a single change. Nothing is written if any of
+    /// them doesn't fit in the file.
+    pub fn apply_writes(&mut self, writes: &[(usize, Vec<u8>)]) -> Result<(), io::Error> {
+        for (position, bytes) in writes {
+            self.check_range(*position, bytes.len())?;
+        }
+
+        let mut edits = Vec::new();
+
+        for (position, bytes) in writes {
+            for (i, &new_byte) in bytes.iter().enumerate() {
+                let prev_byte = self.buffer[position + i];
+
+                if prev_byte != new_byte {
+                    edits.push(Edit {
+                        position: position + i,
+                        prev_byte,
+                        new_byte,
+                    });
+                    self.set_byte(position + i, new_byte);
+                }
+            }
+        }
+
+        if !edits.is_empty() {
+            self.push_undo(edits);
+        }
+
+        Ok(())
+    }
+
+    /// Fails unless the `len` bytes from `position` are all in the file.
+    fn check_range(&self, position: usize, len: usize) -> Result<(), io::Error> {
+        match position.checked_add(len) {
+            Some(end) if end <= self.file_size() => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} bytes at {:#x} don't fit in the file", len, position),
+            )),
+        }
+    }
+
+    fn set_byte(&mut self, position: usize, byte: u8) {
+        let saved_byte = *self
+            .modified
+            .entry(position)
+            .or_insert(self.buffer[position]);
+
+        if saved_byte == byte {
+            self.modified.remove(&position);
+        }
+
+        let loaded_byte = *self
+            .original
+            .entry(position)
+            .or_insert(self.buffer[position]);
+
+        if loaded_byte == byte {
+            self.original.remove(&position);
+        }
+
+        Arc::make_mut(&mut self.buffer)[position] = byte;
+        self.saved = false;
+        self.generation += 1;
+    }
+
+    /// Sets every modified byte back to its saved value as a single change.
+    pub fn discard_changes(&mut self) {
+        let writes: Vec<_> = self
+            .modified
+            .iter()
+            .map(|(&position, &byte)| (position, vec![byte]))
+            .collect();
+
+        // the modified bytes are all in the file
+        let _ = self.apply_writes(&writes);
+        self.saved = true;
+    }
+
+    /// Returns the runs of consecutive bytes that differ from the file as it was loaded, with their loaded values.
+    pub fn changes_since_load(&self) -> Vec<(usize, Vec<u8>)> {
+        let mut runs: Vec<(usize, Vec<u8>)> = Vec::new();
+
+        for (&position, &byte) in &self.original {
+            match runs.last_mut() {
+                Some((start, bytes)) if *start + bytes.len() == position => bytes.push(byte),
+                _ => runs.push((position, vec![byte])),
+            }
+        }
+
+        runs
+    }
+
+    /// Returns the first modified offset after `position`.
+    pub fn next_modified(&self, position: usize) -> Option<usize> {
+        self.modified
+            .range(position + 1..)
+            .next()
+            .map(|(&offset, _)| offset)
+    }
+
+    /// Returns the last modified offset before `position`.
+    pub fn prev_modified(&self, position: usize) -> Option<usize> {
+        self.modified
+            .range(..position)
+            .next_back()
+            .map(|(&offset, _)| offset)
+    }
+
+    pub(crate) fn push_undo(&mut self, edits: Vec<Edit>) {
+        self.undo_stack.push(edits);
+        self.redo_stack.clear();
+    }
+
+    /// Undoes the last change and moves the cursor to it, returning whether there was one.
+    pub fn undo(&mut self) -> bool {
+        if let Some(edits) = self.undo_stack.pop() {
+            for edit in edits.iter().rev() {
+                self.set_byte(edit.position, edit.prev_byte);
+            }
+            self.cursor_nibble = 2 * edits[0].position;
+            self.redo_stack.push(edits);
+
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Redoes the last undone change and moves the cursor to it, returning whether there was one.
+    pub fn redo(&mut self) -> bool {
+        if let Some(edits) = self.redo_stack.pop() {
+            for edit in &edits {
+                self.set_byte(edit.position, edit.new_byte);
+            }
+            self.cursor_nibble = 2 * edits[0].position;
+            self.undo_stack.push(edits);
+
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Writes the buffer to `filename` and keeps editing that file.
+    pub fn save_as(&mut self, filename: &str) -> Result<(), io::Error> {
+        fs::write(filename, &self.buffer[..])?;
+        self.filename = filename.to_owned();
+        self.saved = true;
+        self.modified.clear();
+
+        Ok(())
+    }
+
+    /// Writes the buffer to the file it was loaded from.
+    pub fn save(&mut self) -> Result<(), io::Error> {
+        let filename = self.filename.clone();
+        self.save_as(&filename)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+
+    fn editor(bytes: &[u8]) -> FileEditor {
+        FileEditor::from_bytes("test.bin", bytes.to_vec())
+    }
+
+    #[test]
+    fn write_nibble_undo_redo() {
+        let mut editor = editor(&[0x00, 0x11]);
+
+        editor.set_cursor(1).unwrap();
+        editor.write_nibble(0xa).unwrap();
+        editor.cursor_nibble += 1;
+        editor.write_nibble(0xb).unwrap();
+        assert_eq!(editor.bytes(), [0x00, 0xab]);
+        assert!(!editor.is_saved());
+
+        assert!(editor.undo());
+        assert_eq!(editor.bytes(), [0x00, 0xa1]);
+        assert!(editor.undo());
+        assert_eq!(editor.bytes(), [0x00, 0x11]);
+        assert!(!editor.undo());
+
+        assert!(editor.redo());
+        assert!(editor.redo());
+        assert_eq!(editor.bytes(), [0x00, 0xab]);
+        assert!(!editor.redo());
+    }
+
+    #[test]
+    fn new_change_clears_redo() {
+        let mut editor = editor(&[0; 4]);
+
+        editor.replace_all(&[0], &[1]).unwrap();
+        editor.undo();
+        editor.replace_all(&[1], &[2]).unwrap();
+
+        assert!(!editor.redo());
+        assert_eq!(editor.bytes(), [0, 2, 0, 0]);
+    }
+
+    #[test]
+    fn replace_all_is_a_single_change() {
+        let mut editor = editor(b"abcabcabc");
+
+        // the last position already holds the bytes, so it doesn't count
+        let changed = editor.replace_all(&[0, 3], b"xy").unwrap();
+        assert_eq!(changed, 2);
+        assert_eq!(editor.replace_all(&[6], b"ab").unwrap(), 0);
+        assert_eq!(editor.bytes(), b"xycxycabc");
+
+        assert!(editor.undo());
+        assert_eq!(editor.bytes(), b"abcabcabc");
+        assert_eq!(editor.cursor(), 0);
+        assert!(!editor.undo());
+    }
+
+    #[test]
+    fn out_of_range_writes_change_nothing() {
+        let mut editor = editor(&[0; 4]);
+
+        assert!(editor.replace_all(&[0, 3], &[1, 1]).is_err());
+        assert!(editor
+            .apply_writes(&[(0, vec![1]), (usize::MAX, vec![1])])
+            .is_err());
+        assert!(editor.set_cursor(4).is_err());
+
+        assert_eq!(editor.bytes(), [0; 4]);
+        assert!(editor.is_saved());
+        assert!(!editor.undo());
+    }
+
+    #[test]
+    fn empty_file_has_nothing_to_write() {
+        let mut editor = editor(&[]);
+
+        assert!(editor.write_nibble(1).is_err());
+        assert!(editor.set_cursor(0).is_err());
+    }
+
+    #[test]
+    fn opens_empty_file() {
+        let path = env::temp_dir().join(format!("hexzen-editor-{}-empty", process::id()));
+        fs::write(&path, []).unwrap();
+
+        let mut editor = FileEditor::new(path.to_str().unwrap()).unwrap();
+        let written = editor.write_nibble(1);
+        let saved = editor.save();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(editor.file_size(), 0);
+        assert!(written.is_err());
+        assert!(saved.is_ok());
+    }
+
+    #[test]
+    fn undo_clears_changes_since_load() {
+        let mut editor = editor(&[1, 2, 3, 4]);
+
+        editor
+            .apply_writes(&[(1, vec![9, 9]), (3, vec![8])])
+            .unwrap();
+        assert_eq!(editor.changes_since_load(), [(1, vec![2, 3, 4])]);
+
+        editor.undo();
+        assert!(editor.changes_since_load().is_empty());
+        assert!(editor.next_modified(0).is_none());
+    }
+}
@@ -0,0 +1,225 @@
+use std::{iter::Peekable, str::CharIndices};
+
+use crate::parse_number;
+
+/// Evaluates an arithmetic expression made of numbers, symbols, `+ - * / %` and parentheses. Numbers are parsed with
+/// `parse_number`, and any other word, as well as `.`, is resolved with `lookup`. Comparisons with `== != < <= > >=`,
+/// which give 1 if true and 0 if not, bind less tightly than the arithmetic.
+pub fn evaluate(input: &str, lookup: &dyn Fn(&str) -> Option<usize>) -> Result<i128, String> {
+    let mut parser = Parser {
+        input,
+        chars: input.char_indices().peekable(),
+        lookup,
+    };
+
+    let value = parser.comparison()?;
+
+    match parser.next_token() {
+        Some((_, c)) => Err(format!("unexpected '{}'", c)),
+        None => Ok(value),
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    lookup: &'a dyn Fn(&str) -> Option<usize>,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn next_token(&mut self) -> Option<(usize, char)> {
+        self.skip_whitespace();
+        self.chars.next()
+    }
+
+    fn comparison(&mut self) -> Result<i128, String> {
+        let value = self.expr()?;
+
+        let op = match self.peek() {
+            Some(first @ ('=' | '!' | '<' | '>')) => {
+                self.chars.next();
+
+                match self.chars.next_if(|&(_, c)| c == '=') {
+                    Some(_) => format!("{}=", first),
+                    None => first.to_string(),
+                }
+            }
+            _ => return Ok(value),
+        };
+        let rhs = self.expr()?;
+
+        let result = match op.as_str() {
+            "==" => value == rhs,
+            "!=" => value != rhs,
+            "<" => value < rhs,
+            "<=" => value <= rhs,
+            ">" => value > rhs,
+            ">=" => value >= rhs,
+            _ => return Err(format!("unexpected '{}'", op)),
+        };
+
+        Ok(result.into())
+    }
+
+    fn expr(&mut self) -> Result<i128, String> {
+        let mut value = self.term()?;
+
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.chars.next();
+            let rhs = self.term()?;
+
+            value = match op {
+                '+' => value.checked_add(rhs),
+                _ => value.checked_sub(rhs),
+            }
+            .ok_or("overflow")?;
+        }
+
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<i128, String> {
+        let mut value = self.unary()?;
+
+        while let Some(op @ ('*' | '/' | '%')) = self.peek() {
+            self.chars.next();
+            let rhs = self.unary()?;
+
+            value = match op {
+                '*' => value.checked_mul(rhs).ok_or("overflow")?,
+                '/' => value.checked_div(rhs).ok_or("division by zero")?,
+                _ => value.checked_rem(rhs).ok_or("division by zero")?,
+            };
+        }
+
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<i128, String> {
+        if self.peek() == Some('-') {
+            self.chars.next();
+            Ok(self.unary()?.checked_neg().ok_or("overflow")?)
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<i128, String> {
+        match self.next_token() {
+            Some((_, '(')) => {
+                let value = self.comparison()?;
+
+                match self.next_token() {
+                    Some((_, ')')) => Ok(value),
+                    _ => Err(String::from("expected ')'")),
+                }
+            }
+            Some((_, '.')) => self.symbol("."),
+            Some((start, c)) if c.is_alphanumeric() || c == '_' => {
+                let mut end = start + c.len_utf8();
+
+                while let Some((i, c)) = self
+                    .chars
+                    .next_if(|&(_, c)| c.is_alphanumeric() || c == '_')
+                {
+                    end = i + c.len_utf8();
+                }
+
+                let word = &self.input[start..end];
+
+                match parse_number(word) {
+                    Some(value) => Ok(value as i128),
+                    None if is_number(word) => Err(format!("overflow: {} is too large", word)),
+                    None => self.symbol(word),
+                }
+            }
+            Some((_, c)) => Err(format!("unexpected '{}'", c)),
+            None => Err(String::from("unexpected end of expression")),
+        }
+    }
+
+    fn symbol(&self, name: &str) -> Result<i128, String> {
+        (self.lookup)(name)
+            .map(|value| value as i128)
+            .ok_or_else(|| format!("unknown symbol {}", name))
+    }
+}
+
+/// Returns whether `word` is written like a number that `parse_number` reads, whatever its size.
+fn is_number(word: &str) -> bool {
+    let (digits, radix) = match word.strip_prefix("0d") {
+        Some(digits) => (digits, 10),
+        None => (word.strip_prefix("0x").unwrap_or(word), 16),
+    };
+
+    !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(input: &str) -> Result<i128, String> {
+        evaluate(input, &|name| match name {
+            "." => Some(0x10),
+            "end" => Some(0x100),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn numbers_are_hex_unless_decimal() {
+        assert_eq!(eval("10"), Ok(0x10));
+        assert_eq!(eval("0x10"), Ok(0x10));
+        assert_eq!(eval("0d10"), Ok(10));
+    }
+
+    #[test]
+    fn precedence_and_parentheses() {
+        assert_eq!(eval("2 + 3 * 4"), Ok(0xe));
+        assert_eq!(eval("(2 + 3) * 4"), Ok(0x14));
+        assert_eq!(eval("-2 + 5"), Ok(3));
+        assert_eq!(eval("end - . / 2"), Ok(0xf8));
+        assert_eq!(eval("end % 3"), Ok(0x100 % 3));
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(eval(". < end"), Ok(1));
+        assert_eq!(eval("1 + 1 == 2"), Ok(1));
+        assert_eq!(eval("end != 100"), Ok(0));
+        assert_eq!(eval("3 >= 4"), Ok(0));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(eval("nope"), Err(String::from("unknown symbol nope")));
+        assert_eq!(eval("1 / 0"), Err(String::from("division by zero")));
+        assert_eq!(eval("(1 + 2"), Err(String::from("expected ')'")));
+        assert_eq!(
+            eval("1 +"),
+            Err(String::from("unexpected end of expression"))
+        );
+        assert_eq!(eval("1 2"), Err(String::from("unexpected '2'")));
+    }
+
+    #[test]
+    fn overflow() {
+        assert_eq!(
+            eval("0d99999999999999999999"),
+            Err(String::from(
+                "overflow: 0d99999999999999999999 is too large"
+            ))
+        );
+        assert!(eval("ffffffffffffffff * ffffffffffffffff * ffffffffffffffff").is_err());
+    }
+}
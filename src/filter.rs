@@ -0,0 +1,70 @@
+use std::{
+    env,
+    io::Write,
+    process::{Child, Command, Stdio},
+    thread,
+};
+
+/// Runs `command` with the shell, giving it `input` on stdin, and returns what it wrote to stdout.
+pub fn run(command: &str, input: &[u8]) -> Result<Vec<u8>, String> {
+    let mut child = shell(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("unable to run command: {}", e))?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    let output = thread::scope(|scope| {
+        // written from another thread so that a command writing a lot before reading all of its input can't block
+        // both ends of the pipes
+        scope.spawn(move || {
+            // a command that stops reading early isn't an error by itself
+            let _ = stdin.write_all(input);
+        });
+
+        child.wait_with_output()
+    })
+    .map_err(|e| format!("unable to run command: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        return Err(match stderr.lines().next() {
+            Some(line) => format!("command failed: {}", line),
+            None => format!("command failed: {}", output.status),
+        });
+    }
+
+    Ok(output.stdout)
+}
+
+/// Starts `$PAGER`, or `less` if it isn't set, reading from a pipe. Like git, less is told to quit if the output fits
+/// on the screen and to show colors, unless `$LESS` is set.
+pub fn pager() -> Option<Child> {
+    let pager = env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.is_empty())
+        .unwrap_or_else(|| "less".to_string());
+
+    let mut command = shell(&pager);
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+
+    command.stdin(Stdio::piped()).spawn().ok()
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
@@ -0,0 +1,371 @@
+use std::{
+    cmp,
+    io::{self, Write},
+    str,
+};
+
+use crossterm::{
+    cursor, queue,
+    style::{self, Colors},
+    terminal,
+};
+use unicode_width::UnicodeWidthChar;
+
+/// The two hex digits of every byte, to draw bytes without formatting them.
+const HEX_DIGITS: [[u8; 2]; 256] = {
+    let digits = b"0123456789abcdef";
+    let mut table = [[0; 2]; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        table[i] = [digits[i >> 4], digits[i & 0xf]];
+        i += 1;
+    }
+
+    table
+};
+
+/// The escape sequences that reset the style and reverse the colors, which crossterm would format into a new string
+/// every time they are written.
+const RESET: &[u8] = b"\x1b[0m";
+const REVERSE: &[u8] = b"\x1b[7m";
+
+/// Stands in the second column of a wide character, which the character itself fills on the terminal.
+const WIDE_TAIL: char = '\0';
+
+#[derive(Clone, Copy, PartialEq, Default)]
+struct Style {
+    colors: Option<Colors>,
+    reverse: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    c: char,
+    style: Style,
+}
+
+const BLANK: Cell = Cell {
+    c: ' ',
+    style: Style {
+        colors: None,
+        reverse: false,
+    },
+};
+
+/// The contents of the terminal, which a frame is drawn into before it is presented. Drawing works like on the
+/// terminal, writing text at a position that moves along with it, in the colors last set. Presenting a frame only
+/// writes the cells that differ from the frame presented before it, so that the terminal never shows a half drawn
+/// frame and the colors are only set where they change.
+pub struct Frame {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    x: usize,
+    y: usize,
+    style: Style,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize) -> Frame {
+        Frame {
+            width,
+            height,
+            cells: vec![BLANK; width * height],
+            x: 0,
+            y: 0,
+            style: Style::default(),
+        }
+    }
+
+    /// Blanks every cell, to draw the next frame from scratch.
+    pub fn clear(&mut self) {
+        self.cells.fill(BLANK);
+        self.x = 0;
+        self.y = 0;
+        self.style = Style::default();
+    }
+
+    pub fn move_to(&mut self, x: usize, y: usize) {
+        self.x = x;
+        self.y = y;
+    }
+
+    pub fn set_colors(&mut self, colors: Colors) {
+        self.style.colors = Some(colors);
+    }
+
+    pub fn reset_colors(&mut self) {
+        self.style.colors = None;
+    }
+
+    pub fn set_reverse(&mut self, reverse: bool) {
+        self.style.reverse = reverse;
+    }
+
+    /// Copies row `y` from `other`, which has the same size, like when the row would be drawn the same way again.
+    pub fn copy_row(&mut self, other: &Frame, y: usize) {
+        let row = y * self.width..(y + 1) * self.width;
+        self.cells[row.clone()].copy_from_slice(&other.cells[row]);
+    }
+
+    /// Writes `text` from the current position, cutting it off at the edge of the frame instead of wrapping it.
+    pub fn print(&mut self, text: &str) {
+        for c in text.chars() {
+            self.put(c);
+        }
+    }
+
+    /// Writes a single character from the current position, unless it would be cut off by the edge of the frame.
+    pub fn put(&mut self, c: char) {
+        let width = c.width().unwrap_or(0);
+
+        if width == 0 || self.y >= self.height {
+            return;
+        }
+
+        if self.x + width > self.width {
+            self.x = self.width;
+            return;
+        }
+
+        let row = self.y * self.width;
+        self.cells[row + self.x] = Cell {
+            c,
+            style: self.style,
+        };
+
+        if width == 2 {
+            self.cells[row + self.x + 1] = Cell {
+                c: WIDE_TAIL,
+                style: self.style,
+            };
+        }
+
+        self.x += width;
+    }
+
+    /// Writes `byte` as two hex digits.
+    pub fn print_byte(&mut self, byte: u8) {
+        let [high, low] = HEX_DIGITS[byte as usize];
+
+        self.put(high as char);
+        self.put(low as char);
+    }
+
+    /// Writes `value` in hex, padded with zeros to at least `digits` digits, like `{:0digits$x}` would.
+    pub fn print_hex(&mut self, value: usize, digits: usize) {
+        let bits = (usize::BITS - value.leading_zeros()) as usize;
+        let digits = cmp::max(digits, bits.div_ceil(4));
+
+        // the bytes of the value from the most significant one drawn, which is only half drawn for an odd digit count
+        for i in (0..digits.div_ceil(2)).rev() {
+            let byte = value.checked_shr(8 * i as u32).unwrap_or(0) as u8;
+
+            if i == digits / 2 && digits % 2 == 1 {
+                self.put(HEX_DIGITS[byte as usize][1] as char);
+            } else {
+                self.print_byte(byte);
+            }
+        }
+    }
+
+    /// Writes the cells that differ from `previous` to `out`, or all of them if the terminal may not show `previous`,
+    /// and leaves the terminal cursor at `cursor`.
+    pub fn present(
+        &self,
+        out: &mut impl Write,
+        previous: Option<&Frame>,
+        cursor: (usize, usize),
+    ) -> Result<(), io::Error> {
+        let previous = previous.filter(|previous| previous.cells.len() == self.cells.len());
+
+        queue!(out, terminal::BeginSynchronizedUpdate)?;
+
+        if previous.is_none() {
+            queue!(out, terminal::Clear(terminal::ClearType::All))?;
+        }
+
+        // where the terminal cursor is and the style it writes in, if they are known
+        let mut position = None;
+        let mut style = None;
+
+        for (i, &cell) in self.cells.iter().enumerate() {
+            let unchanged = match previous {
+                Some(previous) => previous.cells[i] == cell,
+                None => cell == BLANK,
+            };
+
+            if unchanged || cell.c == WIDE_TAIL {
+                continue;
+            }
+
+            let (x, y) = (i % self.width, i / self.width);
+
+            if position != Some((x, y)) {
+                queue!(out, cursor::MoveTo(x as u16, y as u16))?;
+            }
+
+            if style != Some(cell.style) {
+                if style.is_none_or(|style| style != Style::default()) {
+                    out.write_all(RESET)?;
+                }
+
+                if let Some(colors) = cell.style.colors {
+                    queue!(out, style::SetColors(colors))?;
+                }
+
+                if cell.style.reverse {
+                    out.write_all(REVERSE)?;
+                }
+
+                style = Some(cell.style);
+            }
+
+            out.write_all(cell.c.encode_utf8(&mut [0; 4]).as_bytes())?;
+            position = Some((x + cell.c.width().unwrap_or(1), y));
+        }
+
+        if style.is_some_and(|style| style != Style::default()) {
+            out.write_all(RESET)?;
+        }
+
+        queue!(
+            out,
+            cursor::MoveTo(cursor.0 as u16, cursor.1 as u16),
+            terminal::EndSynchronizedUpdate
+        )?;
+
+        out.flush()
+    }
+}
+
+/// Lets `write!` draw text into the frame.
+impl Write for Frame {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        let text = str::from_utf8(buf).map_err(io::Error::other)?;
+        self.print(text);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Color;
+
+    use super::*;
+
+    const BEGIN: &str = "\x1b[?2026h";
+    const END: &str = "\x1b[?2026l";
+
+    fn frame(text: &str) -> Frame {
+        let mut frame = Frame::new(4, 2);
+        frame.print(text);
+        frame
+    }
+
+    fn present(frame: &Frame, previous: Option<&Frame>) -> String {
+        let mut out = Vec::new();
+        frame.present(&mut out, previous, (0, 1)).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// The characters of row `y`.
+    fn row(frame: &Frame, y: usize) -> String {
+        frame.cells[y * frame.width..(y + 1) * frame.width]
+            .iter()
+            .filter(|cell| cell.c != WIDE_TAIL)
+            .map(|cell| cell.c)
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_frame_only_moves_the_cursor() {
+        assert_eq!(
+            present(&frame("ab"), Some(&frame("ab"))),
+            format!("{}\x1b[2;1H{}", BEGIN, END)
+        );
+    }
+
+    #[test]
+    fn only_changed_cells_are_written() {
+        // the style of the terminal isn't known before the first cell is written, so it is reset first
+        assert_eq!(
+            present(&frame("abd"), Some(&frame("abc"))),
+            format!("{}\x1b[1;3H\x1b[0md\x1b[2;1H{}", BEGIN, END)
+        );
+
+        // neighboring cells are written without moving in between
+        assert_eq!(
+            present(&frame("xy"), Some(&frame("ab"))),
+            format!("{}\x1b[1;1H\x1b[0mxy\x1b[2;1H{}", BEGIN, END)
+        );
+    }
+
+    #[test]
+    fn unknown_terminal_is_cleared_and_drawn_in_full() {
+        assert_eq!(
+            present(&frame("a b"), None),
+            format!(
+                "{}\x1b[2J\x1b[1;1H\x1b[0ma\x1b[1;3Hb\x1b[2;1H{}",
+                BEGIN, END
+            )
+        );
+
+        // a frame of another size can't be compared with
+        let other = Frame::new(2, 2);
+        assert!(present(&frame("a"), Some(&other)).contains("\x1b[2J"));
+    }
+
+    #[test]
+    fn styles_are_set_where_they_change_and_reset() {
+        let mut frame = Frame::new(4, 1);
+        frame.set_colors(Colors::new(Color::White, Color::Red));
+        frame.print("ab");
+        frame.reset_colors();
+        frame.set_reverse(true);
+        frame.print("c");
+
+        let output = present(&frame, Some(&Frame::new(4, 1)));
+        let colors = "\x1b[38;5;15;48;5;9m";
+        assert_eq!(
+            output,
+            format!(
+                "{}\x1b[1;1H\x1b[0m{}ab\x1b[0m\x1b[7mc\x1b[0m\x1b[2;1H{}",
+                BEGIN, colors, END
+            )
+        );
+    }
+
+    #[test]
+    fn text_is_cut_off_at_the_edge() {
+        let mut frame = frame("abcdef");
+        assert_eq!(row(&frame, 0), "abcd");
+        assert_eq!(row(&frame, 1), "    ");
+
+        // a wide character that doesn't fit isn't split
+        frame.move_to(1, 1);
+        frame.print("日本");
+        assert_eq!(row(&frame, 1), " 日 ");
+
+        frame.clear();
+        assert_eq!(row(&frame, 0), "    ");
+    }
+
+    #[test]
+    fn hex_numbers() {
+        let mut frame = Frame::new(16, 1);
+        frame.print_hex(0x1f0, 8);
+        frame.put(' ');
+        frame.print_hex(0x123, 2);
+        frame.put(' ');
+        frame.print_byte(0xa);
+
+        assert_eq!(row(&frame, 0), "000001f0 123 0a ");
+    }
+}
@@ -0,0 +1,76 @@
+use crc::{
+    Algorithm, Crc, CRC_16_ARC, CRC_16_IBM_3740, CRC_16_MODBUS, CRC_16_XMODEM, CRC_32_BZIP2,
+    CRC_32_ISCSI, CRC_32_ISO_HDLC, CRC_32_MPEG_2,
+};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// Names of the hashes `:hash` computes, for completion.
+pub const ALGORITHMS: [&str; 4] = ["crc32", "md5", "sha1", "sha256"];
+
+/// Returns the hash of `bytes` in hex with the algorithm named `name`, or `None` if there is no such algorithm.
+pub fn hash(name: &str, bytes: &[u8]) -> Option<String> {
+    let digest = match name {
+        // the crc32 of zip, png and gzip
+        "crc32" => {
+            let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(bytes);
+            return Some(format!("{:08x}", crc));
+        }
+        "md5" => Md5::digest(bytes).to_vec(),
+        "sha1" => Sha1::digest(bytes).to_vec(),
+        "sha256" => Sha256::digest(bytes).to_vec(),
+        _ => return None,
+    };
+
+    Some(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Names of the checksums `:checksum` computes, for completion.
+pub const CHECKSUMS: [&str; 12] = [
+    "crc16",
+    "crc16-ccitt",
+    "crc16-modbus",
+    "crc16-xmodem",
+    "crc32",
+    "crc32-bzip2",
+    "crc32-mpeg2",
+    "crc32c",
+    "sum8",
+    "sum16",
+    "sum32",
+    "xor8",
+];
+
+/// Returns the checksum of `bytes` with the algorithm named `name` along with its size in bytes, or `None` if there is
+/// no such algorithm. The sums add up the bytes and keep as many low bits as fit in their size.
+pub fn checksum(name: &str, bytes: &[u8]) -> Option<(u64, usize)> {
+    let crc16 =
+        |algorithm: &'static Algorithm<u16>| (Crc::<u16>::new(algorithm).checksum(bytes).into(), 2);
+    let crc32 =
+        |algorithm: &'static Algorithm<u32>| (Crc::<u32>::new(algorithm).checksum(bytes).into(), 4);
+    let sum = || {
+        bytes
+            .iter()
+            .fold(0u64, |sum, &byte| sum.wrapping_add(byte.into()))
+    };
+
+    let checksum = match name {
+        "crc16" => crc16(&CRC_16_ARC),
+        // also known as CRC-16/CCITT-FALSE
+        "crc16-ccitt" => crc16(&CRC_16_IBM_3740),
+        "crc16-modbus" => crc16(&CRC_16_MODBUS),
+        "crc16-xmodem" => crc16(&CRC_16_XMODEM),
+        "crc32" => crc32(&CRC_32_ISO_HDLC),
+        "crc32-bzip2" => crc32(&CRC_32_BZIP2),
+        "crc32-mpeg2" => crc32(&CRC_32_MPEG_2),
+        "crc32c" => crc32(&CRC_32_ISCSI),
+        "sum8" => (sum() & 0xff, 1),
+        "sum16" => (sum() & 0xffff, 2),
+        "sum32" => (sum() & 0xffff_ffff, 4),
+        "xor8" => (bytes.iter().fold(0, |xor, &byte| xor ^ byte).into(), 1),
+        _ => return None,
+    };
+
+    Some(checksum)
+}
@@ -0,0 +1,215 @@
+use std::ops::Range;
+
+use crossterm::style::Color;
+
+use crate::{config_file::HighlightRule, search::Pattern, value::Endianness};
+
+/// Background colors given to layers in the order they are added.
+const PALETTE: [Color; 6] = [
+    Color::DarkBlue,
+    Color::DarkGreen,
+    Color::DarkMagenta,
+    Color::DarkCyan,
+    Color::DarkRed,
+    Color::DarkYellow,
+];
+
+/// A named set of highlighted byte ranges, drawn in a single color or, if the layer is striped, in two colors that
+/// alternate from one range to the next.
+pub struct HighlightLayer {
+    pub name: String,
+    pub color: Color,
+    stripe_color: Option<Color>,
+    /// Sorted by offset, without overlaps.
+    ranges: Vec<Range<usize>>,
+}
+
+impl HighlightLayer {
+    /// Returns the color of `offset` and the number of bytes highlighted in that color from there, or `None` if
+    /// `offset` isn't highlighted.
+    pub fn color_at(&self, offset: usize) -> Option<(Color, usize)> {
+        let i = self.ranges.partition_point(|range| range.end <= offset);
+        let color = match self.stripe_color {
+            Some(stripe_color) if i % 2 == 1 => stripe_color,
+            _ => self.color,
+        };
+
+        self.ranges
+            .get(i)
+            .filter(|range| range.start <= offset)
+            .map(|range| (color, range.end - offset))
+    }
+
+    pub fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+}
+
+/// Highlight layers that stay until they are removed, drawn below the current search results. Later layers are drawn
+/// over earlier ones.
+#[derive(Default)]
+pub struct Highlights {
+    layers: Vec<HighlightLayer>,
+    next_color: usize,
+}
+
+impl Highlights {
+    /// Adds a layer highlighting `ranges`, which must not overlap, in the next color of the palette. Replaces any
+    /// layer with the same name.
+    pub fn add(&mut self, name: &str, ranges: Vec<Range<usize>>) {
+        let color = PALETTE[self.next_color % PALETTE.len()];
+        self.next_color += 1;
+
+        self.add_colored(name, ranges, color);
+    }
+
+    /// Like `add`, but in the given color.
+    pub fn add_colored(&mut self, name: &str, ranges: Vec<Range<usize>>, color: Color) {
+        self.push(name, ranges, color, None);
+    }
+
+    /// Like `add`, but with every other range in the color after the next one, so that adjacent ranges can be told
+    /// apart.
+    pub fn add_striped(&mut self, name: &str, ranges: Vec<Range<usize>>) {
+        let color = PALETTE[self.next_color % PALETTE.len()];
+        let stripe_color = PALETTE[(self.next_color + 1) % PALETTE.len()];
+        self.next_color += 2;
+
+        self.push(name, ranges, color, Some(stripe_color));
+    }
+
+    fn push(
+        &mut self,
+        name: &str,
+        mut ranges: Vec<Range<usize>>,
+        color: Color,
+        stripe_color: Option<Color>,
+    ) {
+        ranges.sort_by_key(|range| range.start);
+        self.layers.retain(|layer| layer.name != name);
+
+        self.layers.push(HighlightLayer {
+            name: name.to_owned(),
+            color,
+            stripe_color,
+            ranges,
+        });
+    }
+
+    /// Adds a layer for each rule that highlights anything in `buffer`.
+    pub fn add_rules(&mut self, rules: &[Rule], buffer: &[u8]) {
+        for rule in rules {
+            let ranges = rule.ranges(buffer);
+
+            if ranges.is_empty() {
+                continue;
+            }
+
+            match rule.color {
+                Some(color) => self.add_colored(&rule.name, ranges, color),
+                None => self.add(&rule.name, ranges),
+            }
+        }
+    }
+
+    pub fn remove(&mut self, i: usize) {
+        self.layers.remove(i);
+    }
+
+    pub fn list(&self) -> &[HighlightLayer] {
+        &self.layers
+    }
+
+    /// Returns the color of the topmost layer highlighting `offset` and the number of bytes it highlights from there.
+    pub fn color_at(&self, offset: usize) -> Option<(Color, usize)> {
+        self.layers
+            .iter()
+            .rev()
+            .find_map(|layer| layer.color_at(offset))
+    }
+}
+
+enum RuleTarget {
+    Pattern(Pattern),
+    Range(Range<usize>),
+}
+
+/// A highlight rule from the config file, highlighting either the matches of a pattern or a range of offsets.
+pub struct Rule {
+    name: String,
+    target: RuleTarget,
+    color: Option<Color>,
+}
+
+impl Rule {
+    /// Checks a rule read from the config file, parsing its pattern with `endianness` as the default byte order.
+    pub fn parse(rule: &HighlightRule, endianness: Endianness) -> Result<Rule, String> {
+        let target = match (&rule.pattern, rule.start, rule.end) {
+            (Some(pattern), None, None) => RuleTarget::Pattern(
+                Pattern::parse(pattern, endianness)
+                    .filter(|pattern| !pattern.is_empty())
+                    .ok_or_else(|| format!("invalid pattern: {}", pattern))?,
+            ),
+            (None, Some(start), Some(end)) if start < end => RuleTarget::Range(start..end),
+            (None, Some(start), Some(end)) => {
+                return Err(format!("empty range: {:#x} to {:#x}", start, end))
+            }
+            _ => {
+                return Err(String::from(
+                    "a highlight needs either a pattern or a start and an end",
+                ))
+            }
+        };
+        let color = match &rule.color {
+            Some(color) => {
+                Some(parse_color(color).ok_or_else(|| format!("invalid color: {}", color))?)
+            }
+            None => None,
+        };
+
+        Ok(Rule {
+            name: rule.name.clone(),
+            target,
+            color,
+        })
+    }
+
+    fn ranges(&self, buffer: &[u8]) -> Vec<Range<usize>> {
+        match &self.target {
+            RuleTarget::Pattern(pattern) => pattern
+                .find_all(buffer)
+                .into_iter()
+                .map(|offset| offset..offset + pattern.len())
+                .collect(),
+            RuleTarget::Range(range) => {
+                let end = range.end.min(buffer.len());
+                let range = range.start..end;
+
+                if range.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![range]
+                }
+            }
+        }
+    }
+}
+
+/// Parses a color name like `dark-blue` or `red`, or an RGB color like `#ff8000`.
+pub fn parse_color(s: &str) -> Option<Color> {
+    if let Some(hex) = s.strip_prefix('#') {
+        let rgb = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)?;
+
+        return Some(Color::Rgb {
+            r: (rgb >> 16) as u8,
+            g: (rgb >> 8) as u8,
+            b: rgb as u8,
+        });
+    }
+
+    Color::try_from(s.replace('-', "_").as_str())
+        .ok()
+        .filter(|&color| color != Color::Reset)
+}
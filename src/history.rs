@@ -0,0 +1,149 @@
+use std::{fs, io, path::PathBuf};
+
+/// Older entries are dropped once a history grows past this many.
+const MAX_ENTRIES: usize = 100;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HistoryKind {
+    Search,
+    Goto,
+    Command,
+}
+
+/// Previous inputs of a prompt, oldest first, persisted in the data directory between sessions.
+pub struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// Loads the history of `kind`. A missing or unreadable history file gives an empty history, since losing it is
+    /// not worth refusing to open the editor over.
+    pub fn load(kind: HistoryKind) -> History {
+        let name = match kind {
+            HistoryKind::Search => "search_history",
+            HistoryKind::Goto => "goto_history",
+            HistoryKind::Command => "command_history",
+        };
+        let path = dirs::data_dir().map(|dir| dir.join("hexzen").join(name));
+        let entries = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|contents| contents.lines().map(String::from).collect())
+            .unwrap_or_default();
+
+        History { entries, path }
+    }
+
+    pub fn save(&self) -> Result<(), io::Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let contents = self
+            .entries
+            .iter()
+            .map(|entry| format!("{}\n", entry))
+            .collect::<String>();
+
+        fs::write(path, contents)
+    }
+
+    /// Adds `entry` as the most recent entry, moving it to the end if it is already in the history.
+    pub fn push(&mut self, entry: &str) {
+        if entry.is_empty() {
+            return;
+        }
+
+        self.entries.retain(|e| e != entry);
+        self.entries.push(entry.to_owned());
+
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+    }
+
+    pub fn get(&self, i: usize) -> Option<&str> {
+        self.entries.get(i).map(String::as_str)
+    }
+
+    /// Returns the index of the newest entry before `before` that contains `query`.
+    pub fn find(&self, query: &str, before: usize) -> Option<usize> {
+        self.entries[..before.min(self.entries.len())]
+            .iter()
+            .rposition(|entry| entry.contains(query))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn history(entries: &[&str]) -> History {
+        let mut history = History {
+            entries: Vec::new(),
+            path: None,
+        };
+        for entry in entries {
+            history.push(entry);
+        }
+
+        history
+    }
+
+    fn entries(history: &History) -> Vec<&str> {
+        (0..history.len()).filter_map(|i| history.get(i)).collect()
+    }
+
+    #[test]
+    fn repeated_entries_move_to_the_end() {
+        let history = history(&["header", "", "ff 00", "header"]);
+
+        assert_eq!(entries(&history), ["ff 00", "header"]);
+    }
+
+    #[test]
+    fn oldest_entries_are_dropped() {
+        let inputs: Vec<String> = (0..=MAX_ENTRIES).map(|i| i.to_string()).collect();
+        let inputs: Vec<&str> = inputs.iter().map(String::as_str).collect();
+        let history = history(&inputs);
+
+        assert_eq!(history.len(), MAX_ENTRIES);
+        assert_eq!(history.get(0), Some("1"));
+        assert_eq!(history.get(MAX_ENTRIES - 1), Some(inputs[MAX_ENTRIES]));
+    }
+
+    #[test]
+    fn finds_the_newest_match_before() {
+        let history = history(&["ff 00", "0x100", "ff 01", "end"]);
+
+        assert_eq!(history.find("ff", 4), Some(2));
+        assert_eq!(history.find("ff", 2), Some(0));
+        assert_eq!(history.find("ff", 0), None);
+        assert_eq!(history.find("0x", 10), Some(1));
+        assert_eq!(history.find("nope", 4), None);
+    }
+
+    #[test]
+    fn saves_one_entry_per_line() {
+        let dir = env::temp_dir().join(format!("hexzen-test-history-{}", process::id()));
+        let path = dir.join("search_history");
+        let mut history = history(&["ff 00", "header"]);
+        history.path = Some(path.clone());
+
+        history.save().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ff 00\nheader\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,148 @@
+use std::io::{self, Read, Write};
+
+use flate2::bufread::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use memchr::{memchr_iter, memmem};
+
+/// Inflated data is cut off at this size, so that a small stream that inflates to gigabytes can't take all memory.
+pub const MAX_INFLATED_LEN: usize = 0x1000_0000;
+
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b\x08";
+const ZIP_LOCAL_HEADER: &[u8] = b"PK\x03\x04";
+const ZIP_LOCAL_HEADER_LEN: usize = 30;
+/// The compression method of a ZIP entry compressed with deflate.
+const ZIP_DEFLATED: u16 = 8;
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Zlib,
+    Gzip,
+    /// Raw deflate data without a header or a checksum, as in ZIP entries.
+    Deflate,
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Zlib => "zlib",
+            Format::Gzip => "gzip",
+            Format::Deflate => "deflate",
+        }
+    }
+
+    /// Returns the format of the stream at the start of `bytes`, taking it for raw deflate data if it has no header.
+    pub fn detect(bytes: &[u8]) -> Format {
+        if bytes.starts_with(GZIP_MAGIC) {
+            Format::Gzip
+        } else if is_zlib_header(bytes) {
+            Format::Zlib
+        } else {
+            Format::Deflate
+        }
+    }
+}
+
+/// A compressed stream that inflated without errors.
+pub struct Stream {
+    pub offset: usize,
+    pub format: Format,
+    /// How many bytes of the file the stream takes, up to where inflating stopped if it was cut off.
+    pub compressed_len: usize,
+    pub inflated_len: usize,
+    /// Whether inflating stopped at `MAX_INFLATED_LEN` before the end of the stream.
+    pub cut_off: bool,
+}
+
+/// Inflates the stream in `format` at `offset` in `buffer` into `out`. Returns `None` if it isn't valid compressed
+/// data, including when it ends early or, for zlib and gzip, when its checksum doesn't match.
+pub fn inflate(
+    buffer: &[u8],
+    offset: usize,
+    format: Format,
+    out: &mut impl Write,
+) -> Option<Stream> {
+    let bytes = buffer.get(offset..)?;
+    let limit = MAX_INFLATED_LEN as u64 + 1;
+
+    // the decoders read from the slice as far as the stream goes, so what is left of it gives the compressed length
+    let (inflated_len, rest) = match format {
+        Format::Zlib => {
+            let mut decoder = ZlibDecoder::new(bytes);
+            let len = io::copy(&mut decoder.by_ref().take(limit), out).ok()?;
+            (len, decoder.into_inner().len())
+        }
+        Format::Gzip => {
+            let mut decoder = GzDecoder::new(bytes);
+            let len = io::copy(&mut decoder.by_ref().take(limit), out).ok()?;
+            (len, decoder.into_inner().len())
+        }
+        Format::Deflate => {
+            let mut decoder = DeflateDecoder::new(bytes);
+            let len = io::copy(&mut decoder.by_ref().take(limit), out).ok()?;
+            (len, decoder.into_inner().len())
+        }
+    };
+
+    Some(Stream {
+        offset,
+        format,
+        compressed_len: bytes.len() - rest,
+        inflated_len: (inflated_len as usize).min(MAX_INFLATED_LEN),
+        cut_off: inflated_len == limit,
+    })
+}
+
+/// Returns whether `bytes` start with the header of a zlib stream with the usual 32 KiB window and no preset
+/// dictionary, whose two bytes are a multiple of 31.
+fn is_zlib_header(bytes: &[u8]) -> bool {
+    match bytes {
+        [0x78, flags, ..] => (0x7800 + u16::from(*flags)) % 31 == 0 && flags & 0x20 == 0,
+        _ => false,
+    }
+}
+
+/// Returns the offset of the deflate data of the ZIP entry whose local header is at the start of `bytes`, if it is
+/// compressed with deflate.
+fn zip_entry_data(bytes: &[u8]) -> Option<usize> {
+    let header = bytes.get(..ZIP_LOCAL_HEADER_LEN)?;
+    let u16_at = |i: usize| u16::from_le_bytes([header[i], header[i + 1]]);
+
+    if u16_at(8) != ZIP_DEFLATED {
+        return None;
+    }
+
+    Some(ZIP_LOCAL_HEADER_LEN + usize::from(u16_at(26)) + usize::from(u16_at(28)))
+}
+
+/// Returns the zlib and gzip streams in `buffer`, and the deflate data of ZIP entries, sorted by offset. Candidates are
+/// found by their headers and kept if they inflate without errors, which the checksums of zlib and gzip make unlikely
+/// to happen by chance. The data inside a stream isn't searched.
+pub fn find_streams(buffer: &[u8]) -> Vec<Stream> {
+    let mut candidates: Vec<(usize, Format)> = memchr_iter(0x78, buffer)
+        .filter(|&offset| is_zlib_header(&buffer[offset..]))
+        .map(|offset| (offset, Format::Zlib))
+        .chain(memmem::find_iter(buffer, GZIP_MAGIC).map(|offset| (offset, Format::Gzip)))
+        .chain(
+            memmem::find_iter(buffer, ZIP_LOCAL_HEADER).filter_map(|offset| {
+                zip_entry_data(&buffer[offset..]).map(|data| (offset + data, Format::Deflate))
+            }),
+        )
+        .filter(|&(offset, _)| offset < buffer.len())
+        .collect();
+    candidates.sort_by_key(|&(offset, _)| offset);
+
+    let mut streams = Vec::new();
+    let mut end = 0;
+
+    for (offset, format) in candidates {
+        if offset < end {
+            continue;
+        }
+
+        if let Some(stream) = inflate(buffer, offset, format, &mut io::sink()) {
+            end = offset + stream.compressed_len;
+            streams.push(stream);
+        }
+    }
+
+    streams
+}
@@ -0,0 +1,334 @@
+use crate::{
+    plugin::Decoder,
+    value::{self, Endianness},
+};
+
+/// Decoders shown in the inspector before the ones added by plugins.
+pub const DECODERS: [&dyn Decoder; 19] = [
+    &IntDecoder::new("u8", 1, false),
+    &IntDecoder::new("i8", 1, true),
+    &IntDecoder::new("u16", 2, false),
+    &IntDecoder::new("i16", 2, true),
+    &IntDecoder::new("u32", 4, false),
+    &IntDecoder::new("i32", 4, true),
+    &IntDecoder::new("u64", 8, false),
+    &IntDecoder::new("i64", 8, true),
+    &FloatDecoder {
+        name: "f32",
+        size: 4,
+    },
+    &FloatDecoder {
+        name: "f64",
+        size: 8,
+    },
+    &TimeDecoder {
+        name: "unix32",
+        kind: TimeKind::Unix32,
+    },
+    &TimeDecoder {
+        name: "unix64",
+        kind: TimeKind::Unix64,
+    },
+    &TimeDecoder {
+        name: "filetime",
+        kind: TimeKind::FileTime,
+    },
+    &TimeDecoder {
+        name: "dos time",
+        kind: TimeKind::Dos,
+    },
+    &UuidDecoder {
+        name: "uuid",
+        mixed_endian: false,
+    },
+    &UuidDecoder {
+        name: "guid",
+        mixed_endian: true,
+    },
+    &VarintDecoder {
+        name: "uleb128",
+        kind: VarintKind::Unsigned,
+    },
+    &VarintDecoder {
+        name: "sleb128",
+        kind: VarintKind::Signed,
+    },
+    &VarintDecoder {
+        name: "zigzag",
+        kind: VarintKind::ZigZag,
+    },
+];
+
+struct IntDecoder {
+    name: &'static str,
+    size: usize,
+    signed: bool,
+}
+
+impl IntDecoder {
+    const fn new(name: &'static str, size: usize, signed: bool) -> IntDecoder {
+        IntDecoder { name, size, signed }
+    }
+}
+
+impl Decoder for IntDecoder {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn decode(&self, bytes: &[u8], endianness: Endianness) -> Option<String> {
+        let value = value::read_uint(bytes.get(..self.size)?, endianness);
+
+        if self.signed {
+            // sign-extend from the size of the value
+            let shift = 64 - 8 * self.size;
+            Some((((value << shift) as i64) >> shift).to_string())
+        } else {
+            Some(format!("{} ({:#x})", value, value))
+        }
+    }
+}
+
+struct FloatDecoder {
+    name: &'static str,
+    size: usize,
+}
+
+impl Decoder for FloatDecoder {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn decode(&self, bytes: &[u8], endianness: Endianness) -> Option<String> {
+        let bits = value::read_uint(bytes.get(..self.size)?, endianness);
+
+        // the debug format switches to scientific notation for very large and small values
+        match self.size {
+            4 => Some(format!("{:?}", f32::from_bits(bits as u32))),
+            _ => Some(format!("{:?}", f64::from_bits(bits))),
+        }
+    }
+}
+
+/// Reads 16 bytes as a UUID, regardless of the configured endianness.
+struct UuidDecoder {
+    name: &'static str,
+    /// Whether the first three groups are little endian, as in Microsoft GUIDs, instead of the big endian of RFC 4122.
+    mixed_endian: bool,
+}
+
+impl Decoder for UuidDecoder {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn decode(&self, bytes: &[u8], _endianness: Endianness) -> Option<String> {
+        let mut bytes: [u8; 16] = bytes.get(..16)?.try_into().unwrap();
+
+        if self.mixed_endian {
+            bytes[..4].reverse();
+            bytes[4..6].reverse();
+            bytes[6..8].reverse();
+        }
+
+        let hex = |range: std::ops::Range<usize>| -> String {
+            bytes[range]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect()
+        };
+
+        Some(format!(
+            "{}-{}-{}-{}-{}",
+            hex(0..4),
+            hex(4..6),
+            hex(6..8),
+            hex(8..10),
+            hex(10..16)
+        ))
+    }
+}
+
+/// The most bytes a variable-length integer of up to 64 bits takes.
+const MAX_VARINT_LEN: usize = 10;
+
+enum VarintKind {
+    /// ULEB128, as used by DWARF and WebAssembly, which is also how protobuf encodes its varints.
+    Unsigned,
+    /// SLEB128, sign-extended from the last bit of the last byte.
+    Signed,
+    /// A protobuf sint, whose sign is moved to the lowest bit.
+    ZigZag,
+}
+
+/// Reads a little endian base 128 integer, 7 bits per byte with the highest bit set on all but the last byte. Shows
+/// its length, as the data that follows it starts there.
+struct VarintDecoder {
+    name: &'static str,
+    kind: VarintKind,
+}
+
+impl Decoder for VarintDecoder {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn decode(&self, bytes: &[u8], _endianness: Endianness) -> Option<String> {
+        let len = bytes
+            .iter()
+            .take(MAX_VARINT_LEN)
+            .position(|byte| byte & 0x80 == 0)?
+            + 1;
+
+        let mut value = 0u64;
+        for (i, byte) in bytes[..len].iter().enumerate() {
+            let bits = u64::from(byte & 0x7f);
+
+            // only the lowest bit of the tenth byte fits in 64 bits
+            if (bits << (7 * i)) >> (7 * i) != bits {
+                return None;
+            }
+
+            value |= bits << (7 * i);
+        }
+
+        let bits = 7 * len;
+        let value = match self.kind {
+            VarintKind::Unsigned => format!("{} ({:#x})", value, value),
+            VarintKind::Signed if bits < 64 => {
+                let shift = 64 - bits;
+                (((value << shift) as i64) >> shift).to_string()
+            }
+            VarintKind::Signed => (value as i64).to_string(),
+            VarintKind::ZigZag => ((value >> 1) as i64 ^ -((value & 1) as i64)).to_string(),
+        };
+
+        let unit = if len == 1 { "byte" } else { "bytes" };
+        Some(format!("{}, {} {}", value, len, unit))
+    }
+}
+
+enum TimeKind {
+    /// Signed seconds since 1970.
+    Unix32,
+    Unix64,
+    /// 100-nanosecond intervals since 1601, as used by Windows and NTFS.
+    FileTime,
+    /// A 16-bit time followed by a 16-bit date, as used by FAT and ZIP, in local time with 2-second precision.
+    Dos,
+}
+
+struct TimeDecoder {
+    name: &'static str,
+    kind: TimeKind,
+}
+
+/// Seconds from 1601, the start of FILETIME, to 1970.
+const FILETIME_UNIX_EPOCH: i64 = 11_644_473_600;
+
+impl Decoder for TimeDecoder {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn decode(&self, bytes: &[u8], endianness: Endianness) -> Option<String> {
+        match self.kind {
+            TimeKind::Unix32 => {
+                let seconds = value::read_uint(bytes.get(..4)?, endianness) as u32 as i32;
+                format_time(seconds.into(), 0).map(|time| time + " UTC")
+            }
+            TimeKind::Unix64 => {
+                let seconds = value::read_uint(bytes.get(..8)?, endianness) as i64;
+                format_time(seconds, 0).map(|time| time + " UTC")
+            }
+            TimeKind::FileTime => {
+                let intervals = value::read_uint(bytes.get(..8)?, endianness);
+                let seconds = (intervals / 10_000_000) as i64 - FILETIME_UNIX_EPOCH;
+                let nanos = (intervals % 10_000_000) as u32 * 100;
+                format_time(seconds, nanos).map(|time| time + " UTC")
+            }
+            TimeKind::Dos => {
+                let time = value::read_uint(bytes.get(..2)?, endianness);
+                let date = value::read_uint(bytes.get(2..4)?, endianness);
+
+                let (year, month, day) = (1980 + (date >> 9), (date >> 5) & 0xf, date & 0x1f);
+                let (hour, minute, second) = (time >> 11, (time >> 5) & 0x3f, (time & 0x1f) * 2);
+
+                if !(1..=12).contains(&month)
+                    || day == 0
+                    || day > days_in_month(year as i64, month as u32).into()
+                    || hour > 23
+                    || minute > 59
+                    || second > 59
+                {
+                    return None;
+                }
+
+                Some(format!(
+                    "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                    year, month, day, hour, minute, second
+                ))
+            }
+        }
+    }
+}
+
+/// Formats a time given in seconds since 1970 as a date and time, with the fraction of a second if there is one.
+/// Returns `None` for years outside of 0 to 9999, which are more likely to be other data than a time.
+fn format_time(seconds: i64, nanos: u32) -> Option<String> {
+    let days = seconds.div_euclid(86400);
+    let time = seconds.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+
+    if !(0..=9999).contains(&year) {
+        return None;
+    }
+
+    let fraction = match nanos {
+        0 => String::new(),
+        nanos => format!(".{:07}", nanos / 100),
+    };
+
+    Some(format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}{}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        fraction
+    ))
+}
+
+/// Converts days since 1970-01-01 to a year, month and day of the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // shifted so that years start in March and the leap day is the last day of a year, in eras of 400 years
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
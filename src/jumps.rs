@@ -0,0 +1,46 @@
+const MAX_JUMPS: usize = 100;
+
+/// Cursor positions from before each jump, navigable back and forward like vim's jump list.
+#[derive(Default)]
+pub struct JumpList {
+    positions: Vec<usize>,
+    i: usize,
+}
+
+impl JumpList {
+    /// Records `position` as the place the cursor jumped away from, dropping any positions ahead of the current one.
+    pub fn push(&mut self, position: usize) {
+        self.positions.truncate(self.i);
+        self.positions.push(position);
+
+        if self.positions.len() > MAX_JUMPS {
+            self.positions.remove(0);
+        }
+
+        self.i = self.positions.len();
+    }
+
+    /// Returns the previous position in the list. `current` is remembered when going back from the newest position,
+    /// so that it can be returned to with `forward`.
+    pub fn back(&mut self, current: usize) -> Option<usize> {
+        if self.i == 0 {
+            return None;
+        }
+
+        if self.i == self.positions.len() {
+            self.positions.push(current);
+        }
+
+        self.i -= 1;
+        Some(self.positions[self.i])
+    }
+
+    pub fn forward(&mut self) -> Option<usize> {
+        if self.i + 1 >= self.positions.len() {
+            return None;
+        }
+
+        self.i += 1;
+        Some(self.positions[self.i])
+    }
+}
@@ -0,0 +1,1079 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    iter::Peekable,
+    str::{CharIndices, Chars},
+};
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    structure::{Definition, Expr, Field, Struct},
+    value::Endianness,
+};
+
+/// Keys of attributes that change how data is read in ways structure definitions can't express.
+const UNSUPPORTED_KEYS: [&str; 8] = [
+    "consume",
+    "eos-error",
+    "include",
+    "io",
+    "pad-right",
+    "process",
+    "repeat-until",
+    "terminator",
+];
+
+/// A type of a Kaitai Struct definition, the outermost one describing the file itself. Keys that don't change what is
+/// read, like `doc`, are ignored.
+#[derive(Deserialize)]
+struct Type {
+    #[serde(default)]
+    meta: Meta,
+    #[serde(default)]
+    seq: Vec<Attribute>,
+    /// Attributes keyed by their id, read from a position of their own. Only those of the outermost type are read.
+    #[serde(default)]
+    instances: Map<String, Value>,
+    #[serde(default)]
+    types: HashMap<String, Type>,
+    /// Names of values keyed by the value, each a name or a map with the name as its `id`.
+    #[serde(default)]
+    enums: HashMap<String, Map<String, Value>>,
+    params: Option<Value>,
+}
+
+#[derive(Default, Deserialize)]
+struct Meta {
+    id: Option<String>,
+    /// `le` or `be`.
+    endian: Option<Value>,
+    #[serde(default)]
+    imports: Vec<Value>,
+}
+
+#[derive(Clone, Deserialize)]
+struct Attribute {
+    id: Option<String>,
+    #[serde(rename = "type")]
+    ty: Option<Value>,
+    size: Option<Value>,
+    #[serde(rename = "size-eos", default)]
+    size_eos: bool,
+    contents: Option<Value>,
+    /// `expr` or `eos`.
+    repeat: Option<String>,
+    #[serde(rename = "repeat-expr")]
+    repeat_expr: Option<Value>,
+    #[serde(rename = "if")]
+    condition: Option<Value>,
+    #[serde(rename = "enum")]
+    enum_name: Option<String>,
+    pos: Option<Value>,
+    /// Makes an instance computed instead of read.
+    value: Option<Value>,
+    #[serde(flatten)]
+    other: Map<String, Value>,
+}
+
+/// Reads a Kaitai Struct definition (`.ksy`) into a structure definition. Its types become structures and its enums
+/// name the values of fields, while integer and float types, byte arrays, fixed-size strings, `contents`, `repeat`
+/// with `expr` or `eos`, `if`, `size`, types switched on integers and the instances of the outermost type with a `pos`
+/// are supported. Anything else that changes what is read is an error.
+pub fn parse(source: &str) -> Result<Definition, String> {
+    let root: Type = serde_json::from_value(yaml(source)?).map_err(|e| e.to_string())?;
+
+    if !root.meta.imports.is_empty() {
+        return Err(String::from("imports aren't supported"));
+    }
+
+    let mut converter = Converter {
+        enums: HashMap::new(),
+        structs: HashMap::new(),
+    };
+    converter.collect_enums(&root)?;
+
+    let name = root.meta.id.as_deref().unwrap_or("root");
+    let mut fields = converter.fields(name, &root.seq)?;
+
+    for (id, instance) in &root.instances {
+        let instance: Attribute =
+            serde_json::from_value(instance.clone()).map_err(|e| format!("{}: {}", id, e))?;
+
+        // computed instances read nothing
+        if instance.value.is_some() {
+            continue;
+        }
+
+        let pos = instance
+            .pos
+            .as_ref()
+            .ok_or_else(|| format!("{}: instances without a pos aren't supported", id))?;
+        let offset = converter.expr(pos)?;
+
+        for field in converter.switch(name, id.clone(), &instance)? {
+            fields.push(Field {
+                offset: Some(offset.clone()),
+                ..field
+            });
+        }
+    }
+
+    for (name, ty) in &root.types {
+        converter.add_type(name, ty)?;
+    }
+
+    Ok(Definition {
+        endian: endian(&root.meta)?,
+        fields,
+        structs: converter.structs,
+    })
+}
+
+struct Converter {
+    /// The values of each enum by the names of the values.
+    enums: HashMap<String, HashMap<String, u64>>,
+    structs: HashMap<String, Struct>,
+}
+
+impl Converter {
+    /// Adds the enums of `ty` and the types inside it. Enums are known by their own names wherever they are defined.
+    fn collect_enums(&mut self, ty: &Type) -> Result<(), String> {
+        for (name, values) in &ty.enums {
+            let mut names = HashMap::new();
+
+            for (key, value) in values {
+                let number =
+                    integer(key).ok_or_else(|| format!("enum {}: invalid value {}", name, key))?;
+                let value_name = match value {
+                    Value::String(value_name) => value_name,
+                    Value::Object(value) => match value.get("id") {
+                        Some(Value::String(value_name)) => value_name,
+                        _ => return Err(format!("enum {}: {} has no id", name, key)),
+                    },
+                    _ => return Err(format!("enum {}: invalid name of {}", name, key)),
+                };
+
+                names.insert(value_name.clone(), number);
+            }
+
+            if self.enums.insert(name.clone(), names).is_some() {
+                return Err(format!("enum {} is defined more than once", name));
+            }
+        }
+
+        ty.types.values().try_for_each(|ty| self.collect_enums(ty))
+    }
+
+    /// Adds `ty` as the structure `name` along with the types inside it.
+    fn add_type(&mut self, name: &str, ty: &Type) -> Result<(), String> {
+        if ty.params.is_some() {
+            return Err(format!("{}: parameters aren't supported", name));
+        }
+
+        let strukt = Struct {
+            endian: endian(&ty.meta)?,
+            fields: self.fields(name, &ty.seq)?,
+        };
+
+        if self.structs.insert(name.to_owned(), strukt).is_some() {
+            return Err(format!("type {} is defined more than once", name));
+        }
+
+        for (name, ty) in &ty.types {
+            self.add_type(name, ty)?;
+        }
+
+        Ok(())
+    }
+
+    /// Converts the attributes of the `seq` of the type `parent`.
+    fn fields(&mut self, parent: &str, attributes: &[Attribute]) -> Result<Vec<Field>, String> {
+        let mut fields = Vec::new();
+
+        for (i, attribute) in attributes.iter().enumerate() {
+            let name = attribute
+                .id
+                .clone()
+                .unwrap_or_else(|| format!("unnamed{}", i));
+            fields.extend(self.switch(parent, name, attribute)?);
+        }
+
+        Ok(fields)
+    }
+
+    /// Converts an attribute whose type is chosen by a `switch-on` into a field for each case, read if the value
+    /// switched on matches the case, or if it matches none of them for the `_` case. Without a `_` case, an attribute
+    /// with a size is read as bytes then. Attributes with a single type convert to a single field.
+    fn switch(
+        &mut self,
+        parent: &str,
+        name: String,
+        attribute: &Attribute,
+    ) -> Result<Vec<Field>, String> {
+        let Some(Value::Object(switch)) = &attribute.ty else {
+            return Ok(vec![self.field(parent, name, attribute)?]);
+        };
+
+        let on = match switch.get("switch-on") {
+            Some(on) => self.condition(on)?,
+            None => return Err(format!("{}: switch-on is missing", name)),
+        };
+        let Some(Value::Object(cases)) = switch.get("cases") else {
+            return Err(format!("{}: cases are missing", name));
+        };
+
+        let mut types = Vec::new();
+        let mut others = Vec::new();
+        let mut default = None;
+
+        for (case, ty) in cases {
+            if case == "_" {
+                default = Some(ty.clone());
+                continue;
+            }
+
+            if case.starts_with(['"', '\'']) {
+                return Err(format!("{}: cases of strings aren't supported", name));
+            }
+
+            let case = self.translate(case)?;
+            // comparisons bind less tightly than arithmetic, so each is in parentheses to be multiplied
+            types.push((Some(ty.clone()), format!("(({}) == ({}))", on, case)));
+            others.push(format!("(({}) != ({}))", on, case));
+        }
+
+        let otherwise = match others.is_empty() {
+            true => String::from("1"),
+            false => others.join(" * "),
+        };
+        match default {
+            Some(ty) => types.push((Some(ty), otherwise)),
+            None if attribute.size.is_some() || attribute.size_eos => types.push((None, otherwise)),
+            None => (),
+        }
+
+        types
+            .into_iter()
+            .map(|(ty, case)| {
+                let attribute = Attribute {
+                    ty,
+                    ..attribute.clone()
+                };
+                let field = self.field(parent, name.clone(), &attribute)?;
+                let condition = match field.condition {
+                    Some(condition) => format!("(({}) != 0) * {}", condition, case),
+                    None => case,
+                };
+
+                Ok(Field {
+                    condition: Some(condition),
+                    ..field
+                })
+            })
+            .collect()
+    }
+
+    fn field(
+        &mut self,
+        parent: &str,
+        name: String,
+        attribute: &Attribute,
+    ) -> Result<Field, String> {
+        if let Some(key) = UNSUPPORTED_KEYS
+            .iter()
+            .find(|key| attribute.other.contains_key(**key))
+        {
+            return Err(format!("{}: {} isn't supported", name, key));
+        }
+
+        let mut field = Field {
+            name,
+            ty: String::from("u8"),
+            count: None,
+            offset: None,
+            size: None,
+            condition: None,
+            endian: None,
+            values: HashMap::new(),
+        };
+
+        match (&attribute.contents, &attribute.ty) {
+            (Some(contents), _) => {
+                let len = contents_len(contents)
+                    .ok_or_else(|| format!("{}: invalid contents", field.name))?;
+                field.count = Some(Expr::Number(len));
+            }
+            (None, None) => field.count = Some(self.byte_count(&field.name, attribute)?),
+            (None, Some(Value::String(ty))) => match ty.as_str() {
+                "str" => {
+                    field.ty = String::from("char");
+                    field.count = Some(self.byte_count(&field.name, attribute)?);
+                }
+                "strz" => {
+                    return Err(format!(
+                        "{}: null-terminated strings aren't supported",
+                        field.name
+                    ))
+                }
+                ty => match primitive(ty) {
+                    Some((ty, endian)) => {
+                        field.ty = ty.to_owned();
+                        field.endian = endian;
+                    }
+                    None if ty.contains('(') => {
+                        return Err(format!("{}: parameters aren't supported", field.name))
+                    }
+                    None if ty.starts_with('b') && ty[1..].parse::<u32>().is_ok() => {
+                        return Err(format!(
+                            "{}: bit-sized integers aren't supported",
+                            field.name
+                        ))
+                    }
+                    None => {
+                        // types are known by their own names wherever they are defined
+                        field.ty = ty.rsplit("::").next().unwrap_or(ty).to_owned();
+                        field.size = attribute
+                            .size
+                            .as_ref()
+                            .map(|size| self.expr(size))
+                            .transpose()?;
+                    }
+                },
+            },
+            (None, Some(ty)) => return Err(format!("{}: invalid type {}", field.name, ty)),
+        }
+
+        if let Some(enum_name) = &attribute.enum_name {
+            let enum_name = enum_name.rsplit("::").next().unwrap_or(enum_name);
+            let values = self
+                .enums
+                .get(enum_name)
+                .ok_or_else(|| format!("{}: unknown enum {}", field.name, enum_name))?;
+
+            field.values = values
+                .iter()
+                .map(|(name, value)| (format!("{:#x}", value), name.clone()))
+                .collect();
+        }
+
+        let count = match attribute.repeat.as_deref() {
+            None => None,
+            Some("expr") => {
+                let count = attribute
+                    .repeat_expr
+                    .as_ref()
+                    .ok_or_else(|| format!("{}: repeat-expr is missing", field.name))?;
+                Some(self.expr(count)?)
+            }
+            Some("eos") => Some(Expr::Text(String::from("*"))),
+            Some(repeat) => {
+                return Err(format!(
+                    "{}: repeat: {} isn't supported",
+                    field.name, repeat
+                ))
+            }
+        };
+
+        let mut field = match count {
+            // an array of byte arrays or strings becomes an array of structures holding one each
+            Some(count) if field.count.is_some() => {
+                let element = format!("{}_{}", parent, field.name);
+                let outer = Field {
+                    name: field.name.clone(),
+                    ty: element.clone(),
+                    count: Some(count),
+                    offset: None,
+                    size: None,
+                    condition: None,
+                    endian: None,
+                    values: HashMap::new(),
+                };
+
+                self.structs.insert(
+                    element,
+                    Struct {
+                        endian: None,
+                        fields: vec![field],
+                    },
+                );
+                outer
+            }
+            Some(count) => Field {
+                count: Some(count),
+                ..field
+            },
+            None => field,
+        };
+
+        if let Some(condition) = &attribute.condition {
+            field.condition = Some(self.condition(condition)?);
+        }
+
+        Ok(field)
+    }
+
+    /// Returns how many bytes a byte array or string is, from its `size` or `size-eos`.
+    fn byte_count(&self, name: &str, attribute: &Attribute) -> Result<Expr, String> {
+        match &attribute.size {
+            Some(size) => self.expr(size),
+            None if attribute.size_eos => Ok(Expr::Text(String::from("*"))),
+            None => Err(format!("{}: no size or type", name)),
+        }
+    }
+
+    /// Translates an expression into the text of one.
+    fn condition(&self, value: &Value) -> Result<String, String> {
+        self.expr(value).map(Expr::into_text)
+    }
+
+    fn expr(&self, value: &Value) -> Result<Expr, String> {
+        match value {
+            Value::Number(number) => number
+                .as_u64()
+                .and_then(|number| usize::try_from(number).ok())
+                .map(Expr::Number)
+                .ok_or_else(|| format!("invalid number {}", number)),
+            Value::Bool(value) => Ok(Expr::Number((*value).into())),
+            Value::String(text) => self.translate(text).map(Expr::Text),
+            value => Err(format!("invalid expression {}", value)),
+        }
+    }
+
+    /// Translates a Kaitai expression into a goto expression. Numbers, which are decimal in Kaitai, are written in hex,
+    /// references to enum values like `chunk_type::iend` are replaced by the value, and paths to fields like
+    /// `_root.header.len` by the name of the field, since expressions know the fields read last by their names.
+    fn translate(&self, input: &str) -> Result<String, String> {
+        let mut output = String::new();
+        let mut chars = input.char_indices().peekable();
+
+        while let Some((start, c)) = chars.next() {
+            if !(c.is_alphanumeric() || c == '_') {
+                output.push(c);
+                continue;
+            }
+
+            let word = &input[start..word_end(start, &mut chars)];
+
+            if c.is_ascii_digit() {
+                let number = integer(word).ok_or_else(|| format!("invalid number {}", word))?;
+                write!(output, "{:#x}", number).unwrap();
+            } else if let Some((enum_path, name)) = word.rsplit_once("::") {
+                let enum_name = enum_path.rsplit("::").next().unwrap_or(enum_path);
+                let value = self
+                    .enums
+                    .get(enum_name)
+                    .and_then(|values| values.get(name))
+                    .ok_or_else(|| format!("unknown enum value {}", word))?;
+                write!(output, "{:#x}", value).unwrap();
+            } else if ["and", "or", "not"].contains(&word) {
+                return Err(format!("{} isn't supported in expressions", word));
+            } else {
+                let path = word
+                    .split('.')
+                    .skip_while(|part| ["_root", "_parent"].contains(part));
+
+                if let Some(part) = path.clone().find(|part| part.starts_with('_')) {
+                    return Err(format!("{} isn't supported in expressions", part));
+                }
+
+                let name = path
+                    .last()
+                    .ok_or_else(|| format!("invalid expression {}", input))?;
+                output.push_str(name);
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+/// Returns where the word starting at `start`, which can be a path joined by `.` or `::`, ends.
+fn word_end(start: usize, chars: &mut Peekable<CharIndices>) -> usize {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut end = start + 1;
+
+    loop {
+        // a `.` or `:` is only part of the word if more of it follows
+        let mut ahead = chars.clone().skip_while(|&(_, c)| c == '.' || c == ':');
+
+        match (chars.peek(), ahead.next()) {
+            (Some(&(i, c)), _) if is_word(c) => end = i + c.len_utf8(),
+            (Some(&(_, '.' | ':')), Some((i, c))) if is_word(c) => end = i + c.len_utf8(),
+            _ => return end,
+        }
+
+        while chars.next_if(|&(i, _)| i < end).is_some() {}
+    }
+}
+
+fn endian(meta: &Meta) -> Result<Option<Endianness>, String> {
+    match &meta.endian {
+        None => Ok(None),
+        Some(Value::String(endian)) if endian == "le" => Ok(Some(Endianness::Little)),
+        Some(Value::String(endian)) if endian == "be" => Ok(Some(Endianness::Big)),
+        Some(Value::Object(_)) => Err(String::from("endianness switches aren't supported")),
+        Some(endian) => Err(format!("invalid endianness {}", endian)),
+    }
+}
+
+/// Returns the structure type of the Kaitai integer or float type `ty`, like `u4le` or `f8`, and its byte order if the
+/// type gives one.
+fn primitive(ty: &str) -> Option<(&'static str, Option<Endianness>)> {
+    let (ty, endian) = match (ty.strip_suffix("le"), ty.strip_suffix("be")) {
+        (Some(ty), _) => (ty, Some(Endianness::Little)),
+        (_, Some(ty)) => (ty, Some(Endianness::Big)),
+        _ => (ty, None),
+    };
+
+    let ty = match ty {
+        "u1" => "u8",
+        "u2" => "u16",
+        "u4" => "u32",
+        "u8" => "u64",
+        "s1" => "i8",
+        "s2" => "i16",
+        "s4" => "i32",
+        "s8" => "i64",
+        "f4" => "f32",
+        "f8" => "f64",
+        _ => return None,
+    };
+
+    Some((ty, endian))
+}
+
+/// Returns the length of the bytes `contents` requires, given as a string or a list of bytes and strings.
+fn contents_len(contents: &Value) -> Option<usize> {
+    match contents {
+        Value::String(text) => Some(text.len()),
+        Value::Number(_) => Some(1),
+        Value::Array(items) => items.iter().map(contents_len).sum(),
+        _ => None,
+    }
+}
+
+/// Parses a Kaitai integer: decimal, or hex, octal or binary with a `0x`, `0o` or `0b` prefix, with optional `_`
+/// between digits. Negative numbers wrap around.
+fn integer(text: &str) -> Option<u64> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    let text = text.replace('_', "");
+
+    let (digits, radix) = match text.get(..2) {
+        Some("0x" | "0X") => (&text[2..], 16),
+        Some("0o") => (&text[2..], 8),
+        Some("0b") => (&text[2..], 2),
+        _ => (text.as_str(), 10),
+    };
+
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+
+    let value = u64::from_str_radix(digits, radix).ok()?;
+
+    Some(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+/// A line of YAML without its comment.
+struct Line {
+    number: usize,
+    indent: usize,
+    text: String,
+}
+
+/// Reads the subset of YAML that Kaitai Struct definitions are written in: mappings and sequences by indentation,
+/// flow sequences and mappings in brackets, quoted and plain scalars and block scalars. Plain integers become numbers,
+/// `true` and `false` booleans, and other scalars strings.
+fn yaml(source: &str) -> Result<Value, String> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let text = strip_comment(line).trim_end();
+            let trimmed = text.trim_start();
+
+            match trimmed {
+                "" | "---" | "..." => None,
+                trimmed => Some(Line {
+                    number: i + 1,
+                    indent: text.len() - trimmed.len(),
+                    text: trimmed.to_owned(),
+                }),
+            }
+        })
+        .collect();
+
+    let mut parser = Yaml { lines, i: 0 };
+
+    let Some(indent) = parser.lines.first().map(|line| line.indent) else {
+        return Ok(Value::Object(Map::new()));
+    };
+    let value = parser.block(indent)?;
+
+    match parser.lines.get(parser.i) {
+        Some(line) => Err(format!("line {}: unexpected indentation", line.number)),
+        None => Ok(value),
+    }
+}
+
+/// Removes a comment, which starts with a `#` after whitespace outside quotes, from the end of `line`.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut previous = ' ';
+
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '#' && previous.is_whitespace() => return &line[..i],
+            // quotes only start a scalar, unlike the apostrophe in "don't"
+            None if (c == '"' || c == '\'')
+                && (previous.is_whitespace() || "[{,:-".contains(previous)) =>
+            {
+                quote = Some(c)
+            }
+            None => (),
+        }
+
+        previous = c;
+    }
+
+    line
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Returns where the `:` separating the key of a mapping from its value is in `text`, if it is a key and a value.
+fn separator(text: &str) -> Option<usize> {
+    let start = match text.chars().next()? {
+        '[' | '{' => return None,
+        quote @ ('"' | '\'') => text[1..].find(quote)? + 2,
+        _ => 0,
+    };
+
+    text[start..]
+        .match_indices(':')
+        .map(|(i, _)| start + i)
+        .find(|&i| text[i + 1..].chars().next().is_none_or(char::is_whitespace))
+}
+
+struct Yaml {
+    lines: Vec<Line>,
+    /// The next line to read.
+    i: usize,
+}
+
+impl Yaml {
+    /// Reads the mapping, sequence or scalar whose lines are indented by `indent`.
+    fn block(&mut self, indent: usize) -> Result<Value, String> {
+        let line = &self.lines[self.i];
+
+        if is_item(&line.text) {
+            self.sequence(indent)
+        } else if separator(&line.text).is_some() {
+            self.mapping(indent)
+        } else {
+            let (number, text) = (line.number, line.text.clone());
+            self.i += 1;
+            scalar(&self.continued(text, indent)?, number)
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<Value, String> {
+        let mut items = Vec::new();
+
+        while let Some(line) = self.lines.get(self.i) {
+            if line.indent != indent || !is_item(&line.text) {
+                break;
+            }
+
+            let rest = line.text[1..].trim_start();
+
+            if rest.is_empty() {
+                self.i += 1;
+
+                items.push(match self.lines.get(self.i) {
+                    Some(next) if next.indent > indent => self.block(next.indent)?,
+                    _ => Value::Null,
+                });
+            } else {
+                // the item is read as if it started on a line of its own, indented up to where it starts
+                let offset = line.text.len() - rest.len();
+                let rest = rest.to_owned();
+                let line = &mut self.lines[self.i];
+                line.indent += offset;
+                line.text = rest;
+
+                items.push(self.block(indent + offset)?);
+            }
+        }
+
+        Ok(Value::Array(items))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Value, String> {
+        let mut map = Map::new();
+
+        while let Some(line) = self.lines.get(self.i) {
+            if line.indent < indent || (line.indent == indent && is_item(&line.text)) {
+                break;
+            }
+
+            let number = line.number;
+            if line.indent > indent {
+                return Err(format!("line {}: unexpected indentation", number));
+            }
+
+            let separator =
+                separator(&line.text).ok_or_else(|| format!("line {}: expected a key", number))?;
+            let key = match scalar(&line.text[..separator], number)? {
+                Value::String(key) => key,
+                key => key.to_string(),
+            };
+            let value = line.text[separator + 1..].trim().to_owned();
+            self.i += 1;
+
+            let value = if value.is_empty() {
+                match self.lines.get(self.i) {
+                    Some(next) if next.indent > indent => self.block(next.indent)?,
+                    // a sequence can be indented as far as the key it is the value of
+                    Some(next) if next.indent == indent && is_item(&next.text) => {
+                        self.sequence(indent)?
+                    }
+                    _ => Value::Null,
+                }
+            } else if value.starts_with(['|', '>']) {
+                self.block_scalar(indent)
+            } else {
+                scalar(&self.continued(value, indent + 1)?, number)?
+            };
+
+            if map.insert(key.clone(), value).is_some() {
+                return Err(format!("line {}: {} is given twice", number, key));
+            }
+        }
+
+        Ok(Value::Object(map))
+    }
+
+    /// Appends the lines indented by at least `indent` to `text`, as a scalar continued on the next lines, which can't
+    /// hold a key.
+    fn continued(&mut self, mut text: String, indent: usize) -> Result<String, String> {
+        while let Some(line) = self.lines.get(self.i).filter(|line| line.indent >= indent) {
+            if separator(&line.text).is_some() {
+                return Err(format!("line {}: unexpected indentation", line.number));
+            }
+
+            text.push(' ');
+            text.push_str(&line.text);
+            self.i += 1;
+        }
+
+        Ok(text)
+    }
+
+    /// Reads the lines of a block scalar after a key indented by `indent`.
+    fn block_scalar(&mut self, indent: usize) -> Value {
+        let mut lines = Vec::new();
+
+        while let Some(line) = self.lines.get(self.i).filter(|line| line.indent > indent) {
+            lines.push(line.text.as_str());
+            self.i += 1;
+        }
+
+        Value::String(lines.join("\n"))
+    }
+}
+
+/// Reads a scalar or a flow sequence or mapping that takes all of `text`.
+fn scalar(text: &str, number: usize) -> Result<Value, String> {
+    let mut flow = Flow {
+        chars: text.chars().peekable(),
+    };
+    let value = flow
+        .value(false)
+        .map_err(|e| format!("line {}: {}", number, e))?;
+
+    flow.skip_whitespace();
+    match flow.chars.next() {
+        Some(c) => Err(format!("line {}: unexpected '{}'", number, c)),
+        None => Ok(value),
+    }
+}
+
+struct Flow<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Flow<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    /// Reads a value, which ends at `,`, `]`, `}` or a `:` separating a key inside a flow collection.
+    fn value(&mut self, in_flow: bool) -> Result<Value, String> {
+        self.skip_whitespace();
+
+        match self.chars.peek() {
+            Some('[') => {
+                self.chars.next();
+                let mut items = Vec::new();
+
+                while !self.end_of_collection(']')? {
+                    items.push(self.value(true)?);
+                }
+
+                Ok(Value::Array(items))
+            }
+            Some('{') => {
+                self.chars.next();
+                let mut map = Map::new();
+
+                while !self.end_of_collection('}')? {
+                    let key = match self.value(true)? {
+                        Value::String(key) => key,
+                        key => key.to_string(),
+                    };
+
+                    self.skip_whitespace();
+                    if self.chars.next() != Some(':') {
+                        return Err(String::from("expected ':'"));
+                    }
+
+                    map.insert(key, self.value(true)?);
+                }
+
+                Ok(Value::Object(map))
+            }
+            Some(&quote @ ('"' | '\'')) => {
+                self.chars.next();
+                self.quoted(quote).map(Value::String)
+            }
+            _ => {
+                let mut text = String::new();
+
+                while let Some(&c) = self.chars.peek() {
+                    let ends = in_flow
+                        && (",[]{}".contains(c)
+                            || (c == ':'
+                                && self.chars.clone().nth(1).is_none_or(char::is_whitespace)));
+                    if ends {
+                        break;
+                    }
+
+                    text.push(c);
+                    self.chars.next();
+                }
+
+                Ok(plain(text.trim()))
+            }
+        }
+    }
+
+    /// Skips the `,` between the items of a collection, returning whether `close` ends it.
+    fn end_of_collection(&mut self, close: char) -> Result<bool, String> {
+        self.skip_whitespace();
+        self.chars.next_if_eq(&',');
+        self.skip_whitespace();
+
+        match self.chars.peek() {
+            Some(&c) if c == close => {
+                self.chars.next();
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => Err(format!("expected '{}'", close)),
+        }
+    }
+
+    /// Reads a string up to `quote`. Double-quoted strings have escapes, while single-quoted ones write a quote twice.
+    fn quoted(&mut self, quote: char) -> Result<String, String> {
+        let mut text = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some('\'') if quote == '\'' && self.chars.next_if_eq(&'\'').is_some() => {
+                    text.push('\'')
+                }
+                Some(c) if c == quote => return Ok(text),
+                Some('\\') if quote == '"' => {
+                    let c = match self.chars.next() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('0') => '\0',
+                        Some(c @ ('\\' | '"' | '/')) => c,
+                        Some(prefix @ ('x' | 'u')) => {
+                            let len = if prefix == 'x' { 2 } else { 4 };
+                            let digits: String = self.chars.by_ref().take(len).collect();
+                            u32::from_str_radix(&digits, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| format!("invalid escape \\{}{}", prefix, digits))?
+                        }
+                        Some(c) => return Err(format!("unknown escape \\{}", c)),
+                        None => return Err(String::from("unterminated string")),
+                    };
+                    text.push(c);
+                }
+                Some(c) => text.push(c),
+                None => return Err(String::from("unterminated string")),
+            }
+        }
+    }
+}
+
+/// Types a plain scalar.
+fn plain(text: &str) -> Value {
+    match text {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        "" | "~" | "null" => Value::Null,
+        _ => match integer(text) {
+            Some(number) if !text.starts_with('-') => number.into(),
+            _ => Value::String(text.to_owned()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `source` and applies it to `buffer`, returning the path of each field read followed by its value if it
+    /// has one.
+    fn fields(source: &str, buffer: &[u8]) -> Vec<String> {
+        let structure = parse(source)
+            .unwrap()
+            .apply("test", buffer, 0, Endianness::Little);
+        assert_eq!(structure.error, None);
+
+        structure
+            .nodes
+            .into_iter()
+            .map(|node| match node.value {
+                Some(value) => format!("{} = {}", node.path, value),
+                None => node.path,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_integers_in_their_byte_order() {
+        let source = "\
+meta:
+  id: header
+  endian: be
+seq:
+  - id: magic
+    contents: [0x7f, 'E']
+  - id: length
+    type: u2
+  - id: flags
+    type: u4le
+  - id: kind
+    type: u1
+    enum: kinds
+enums:
+  kinds:
+    1: one
+    2: two
+";
+        let buffer = [0x7f, b'E', 0x01, 0x02, 0x03, 0x00, 0x00, 0x00, 0x02];
+
+        assert_eq!(
+            fields(source, &buffer),
+            [
+                "magic = 7f 45",
+                "length = 258 (0x102)",
+                "flags = 3 (0x3)",
+                "kind = 2 (0x2) two",
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_types_and_repeats() {
+        let source = "\
+seq:
+  - id: count
+    type: u1
+  - id: points
+    type: point
+    repeat: expr
+    repeat-expr: count
+types:
+  point:
+    seq:
+      - id: x
+        type: s1
+      - id: y
+        type: s1
+";
+        let buffer = [2, 1, 0xff, 3, 4];
+
+        assert_eq!(
+            fields(source, &buffer),
+            [
+                "count = 2 (0x2)",
+                "points",
+                "points[0]",
+                "points[0].x = 1",
+                "points[0].y = -1",
+                "points[1]",
+                "points[1].x = 3",
+                "points[1].y = 4",
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_yaml_flow_collections_and_comments() {
+        let source = "\
+# a comment
+meta: {id: test, endian: le}
+seq:
+  - {id: a, type: u2}   # another
+  - id: 'b'
+    size: 2
+";
+
+        assert_eq!(fields(source, &[1, 0, 2, 3]), ["a = 1 (0x1)", "b = 02 03"]);
+    }
+
+    #[test]
+    fn unsupported_definitions_are_errors() {
+        let error = |source: &str| parse(source).err();
+
+        assert_eq!(
+            error("meta:\n  imports: [other]\n"),
+            Some(String::from("imports aren't supported"))
+        );
+        assert_eq!(
+            error("seq:\n  - id: a\n    type: u1\n    process: xor(1)\n"),
+            Some(String::from("a: process isn't supported"))
+        );
+        assert_eq!(
+            error("seq:\n  - id: a\n    type: b3\n"),
+            Some(String::from("a: bit-sized integers aren't supported"))
+        );
+        assert_eq!(
+            error("seq:\n  - id: a\n   type: u1\n"),
+            Some(String::from("line 3: unexpected indentation"))
+        );
+    }
+}
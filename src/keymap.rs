@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+/// Editor commands that can be bound to keys, named in kebab-case in the config file.
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    PageUp,
+    PageDown,
+    RowStart,
+    RowEnd,
+    ScrollUp,
+    ScrollDown,
+    GroupLeft,
+    GroupRight,
+    RecordUp,
+    RecordDown,
+    FileStart,
+    FileEnd,
+    JumpBack,
+    JumpForward,
+    ToggleMode,
+    NormalMode,
+    Undo,
+    Redo,
+    Save,
+    Quit,
+    Command,
+    Goto,
+    Search,
+    SearchBackward,
+    NextMatch,
+    PrevMatch,
+    CountMatches,
+    SearchMany,
+    Replace,
+    SearchResults,
+    HighlightResults,
+    ListHighlights,
+    ExportResults,
+    Bookmark,
+    ListBookmarks,
+    /// Comments on the selection, or the byte at the cursor, or removes the annotation at the cursor.
+    Annotate,
+    FollowPointer,
+    Inspect,
+    ListStrings,
+    /// Lists the fields of the structure applied last.
+    ListFields,
+    ToggleCollapse,
+    /// Stops hexzen until the shell resumes it, like Ctrl+Z usually does.
+    Suspend,
+    ToggleSelection,
+    /// Zeroes the selection, or the byte at the cursor.
+    ZeroBytes,
+    /// Followed by the name of the mark.
+    SetMark,
+    /// Followed by the name of the mark.
+    JumpToMark,
+    /// Followed by the kind of position to jump to, like `c` for modified bytes.
+    NextPrefix,
+    PrevPrefix,
+    /// Followed by where to put the cursor row, like `z` for the center.
+    ScrollPrefix,
+    /// Followed by the register to record into, or stops recording.
+    RecordMacro,
+    /// Followed by the register to play, or by `@` for the last one played.
+    PlayMacro,
+    /// Removes a default binding.
+    None,
+}
+
+/// Bindings used unless the config file overrides them, written the same way as in the config file.
+const DEFAULT_BINDINGS: [(&str, Action); 63] = [
+    ("left", Action::MoveLeft),
+    ("backspace", Action::MoveLeft),
+    ("right", Action::MoveRight),
+    ("up", Action::MoveUp),
+    ("down", Action::MoveDown),
+    ("pageup", Action::PageUp),
+    ("pagedown", Action::PageDown),
+    ("home", Action::RowStart),
+    ("end", Action::RowEnd),
+    ("alt+up", Action::ScrollUp),
+    ("alt+down", Action::ScrollDown),
+    ("ctrl+e", Action::ScrollDown),
+    ("ctrl+left", Action::GroupLeft),
+    ("ctrl+right", Action::GroupRight),
+    ("ctrl+up", Action::RecordUp),
+    ("ctrl+down", Action::RecordDown),
+    ("ctrl+home", Action::FileStart),
+    ("ctrl+end", Action::FileEnd),
+    ("ctrl+o", Action::JumpBack),
+    ("ctrl+n", Action::JumpForward),
+    ("tab", Action::ToggleMode),
+    ("esc", Action::NormalMode),
+    ("u", Action::Undo),
+    ("ctrl+z", Action::Undo),
+    ("r", Action::Redo),
+    ("ctrl+y", Action::Redo),
+    ("w", Action::Save),
+    ("ctrl+s", Action::Save),
+    ("q", Action::Quit),
+    ("ctrl+q", Action::Quit),
+    (":", Action::Command),
+    ("j", Action::Goto),
+    ("/", Action::Search),
+    ("ctrl+f", Action::Search),
+    ("?", Action::SearchBackward),
+    ("n", Action::NextMatch),
+    ("N", Action::PrevMatch),
+    ("#", Action::CountMatches),
+    ("&", Action::SearchMany),
+    ("R", Action::Replace),
+    ("S", Action::SearchResults),
+    ("H", Action::HighlightResults),
+    ("L", Action::ListHighlights),
+    ("X", Action::ExportResults),
+    ("k", Action::Bookmark),
+    ("K", Action::ListBookmarks),
+    ("o", Action::Annotate),
+    ("p", Action::FollowPointer),
+    ("i", Action::Inspect),
+    ("T", Action::ListStrings),
+    ("t", Action::ListFields),
+    ("s", Action::ToggleCollapse),
+    ("v", Action::ToggleSelection),
+    ("delete", Action::ZeroBytes),
+    ("g", Action::FileStart),
+    ("G", Action::FileEnd),
+    ("m", Action::SetMark),
+    ("'", Action::JumpToMark),
+    ("]", Action::NextPrefix),
+    ("[", Action::PrevPrefix),
+    ("z", Action::ScrollPrefix),
+    ("Q", Action::RecordMacro),
+    ("@", Action::PlayMacro),
+];
+
+/// A key together with the Ctrl and Alt modifiers held with it. Shift is part of the character for character keys.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    /// Parses a key like `a`, `G`, `ctrl+s`, `alt+left` or `shift+f5`.
+    pub fn parse(s: &str) -> Option<KeyBinding> {
+        // the key itself may be a plus
+        let (modifiers, key) = match s.strip_suffix("++") {
+            Some(modifiers) => (Some(modifiers), "+"),
+            None if s == "+" => (None, s),
+            None => match s.rsplit_once('+') {
+                Some((modifiers, key)) => (Some(modifiers), key),
+                None => (None, s),
+            },
+        };
+
+        let mut binding = KeyBinding {
+            code: parse_key(key)?,
+            modifiers: KeyModifiers::NONE,
+        };
+
+        for modifier in modifiers
+            .into_iter()
+            .flat_map(|modifiers| modifiers.split('+'))
+        {
+            binding.modifiers |= match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+        }
+
+        Some(binding.normalized())
+    }
+
+    pub fn from_event(event: &KeyEvent) -> KeyBinding {
+        KeyBinding {
+            code: event.code,
+            modifiers: event.modifiers,
+        }
+        .normalized()
+    }
+
+    /// Folds Shift into character keys, which terminals report both as an uppercase character and with Shift held,
+    /// and drops modifiers that can't be bound.
+    fn normalized(mut self) -> KeyBinding {
+        self.modifiers &= KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT;
+
+        match self.code {
+            KeyCode::Char(c) if self.modifiers.contains(KeyModifiers::SHIFT) => {
+                self.code = KeyCode::Char(c.to_ascii_uppercase());
+                self.modifiers -= KeyModifiers::SHIFT;
+            }
+            KeyCode::Tab if self.modifiers.contains(KeyModifiers::SHIFT) => {
+                self.code = KeyCode::BackTab;
+                self.modifiers -= KeyModifiers::SHIFT;
+            }
+            KeyCode::BackTab => self.modifiers -= KeyModifiers::SHIFT,
+            _ => {}
+        }
+
+        self
+    }
+}
+
+fn parse_key(key: &str) -> Option<KeyCode> {
+    let mut chars = key.chars();
+
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+
+    let code = match key.to_ascii_lowercase().as_str() {
+        "space" => KeyCode::Char(' '),
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" | "ins" => KeyCode::Insert,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        f => KeyCode::F(
+            f.strip_prefix('f')?
+                .parse()
+                .ok()
+                .filter(|&n| (1..=24).contains(&n))?,
+        ),
+    };
+
+    Some(code)
+}
+
+/// Maps keys pressed in the editor to actions.
+pub struct Keymap {
+    bindings: HashMap<KeyBinding, Action>,
+}
+
+impl Keymap {
+    /// Creates the default keymap with `overrides`, as found in the config file, applied on top of it.
+    pub fn new(overrides: &HashMap<String, Action>) -> Result<Keymap, String> {
+        let mut bindings = HashMap::new();
+
+        for (key, action) in DEFAULT_BINDINGS {
+            bindings.insert(KeyBinding::parse(key).unwrap(), action);
+        }
+
+        for (key, &action) in overrides {
+            let binding = KeyBinding::parse(key).ok_or_else(|| format!("invalid key: {}", key))?;
+            bindings.insert(binding, action);
+        }
+
+        bindings.retain(|_, action| *action != Action::None);
+
+        Ok(Keymap { bindings })
+    }
+
+    pub fn get(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings.get(&KeyBinding::from_event(event)).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyBinding {
+        KeyBinding { code, modifiers }
+    }
+
+    #[test]
+    fn parses_keys_and_modifiers() {
+        let parse = KeyBinding::parse;
+
+        assert_eq!(
+            parse("a"),
+            Some(key(KeyCode::Char('a'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse("ctrl+s"),
+            Some(key(KeyCode::Char('s'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse("Ctrl+Alt+Left"),
+            Some(key(
+                KeyCode::Left,
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            ))
+        );
+        assert_eq!(
+            parse("space"),
+            Some(key(KeyCode::Char(' '), KeyModifiers::NONE))
+        );
+        assert_eq!(parse("f24"), Some(key(KeyCode::F(24), KeyModifiers::NONE)));
+
+        assert_eq!(parse("f25"), None);
+        assert_eq!(parse("f0"), None);
+        assert_eq!(parse("hyper+a"), None);
+        assert_eq!(parse("ctrl+"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn plus_is_a_key() {
+        assert_eq!(
+            KeyBinding::parse("+"),
+            Some(key(KeyCode::Char('+'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            KeyBinding::parse("ctrl++"),
+            Some(key(KeyCode::Char('+'), KeyModifiers::CONTROL))
+        );
+
+        // a plus after a plus needs a modifier before it
+        assert_eq!(KeyBinding::parse("++"), None);
+    }
+
+    #[test]
+    fn shift_is_folded_into_the_key() {
+        assert_eq!(KeyBinding::parse("shift+g"), KeyBinding::parse("G"));
+        assert_eq!(
+            KeyBinding::parse("shift+tab"),
+            Some(key(KeyCode::BackTab, KeyModifiers::NONE))
+        );
+        assert_eq!(KeyBinding::parse("shift+tab"), KeyBinding::parse("backtab"));
+        assert_eq!(
+            KeyBinding::parse("shift+f5"),
+            Some(key(KeyCode::F(5), KeyModifiers::SHIFT))
+        );
+    }
+
+    #[test]
+    fn overrides_replace_and_remove_bindings() {
+        let overrides = HashMap::from([
+            (String::from("x"), Action::Quit),
+            (String::from("q"), Action::None),
+        ]);
+        let keymap = Keymap::new(&overrides).unwrap();
+        let press = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+        assert_eq!(keymap.get(&press('x')), Some(Action::Quit));
+        assert_eq!(keymap.get(&press('q')), None);
+        assert_eq!(keymap.get(&press('u')), Some(Action::Undo));
+
+        let invalid = HashMap::from([(String::from("ctrl+nope"), Action::Quit)]);
+        assert!(Keymap::new(&invalid).is_err());
+    }
+}
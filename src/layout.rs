@@ -0,0 +1,132 @@
+/// Column of the first hex digit, after the offset column.
+const HEX_X: usize = 12;
+/// Gap between the hex pane and the text pane.
+const PANE_GAP: usize = 2;
+
+/// Horizontal placement of the editor panes, computed from the terminal width.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Layout {
+    pub bytes_per_row: usize,
+    /// Bytes are split into groups of this size by an extra space.
+    pub group_size: usize,
+    pub hex_x: usize,
+    pub text_x: Option<usize>,
+}
+
+impl Layout {
+    /// Picks the widest layout with at most `max_bytes_per_row` bytes per row that fits in `width` columns, preferring
+    /// to keep the text pane and halving the number of bytes per row before dropping it. Returns `None` if not even a
+    /// single byte fits.
+    pub fn new(width: usize, max_bytes_per_row: usize, group_size: usize) -> Option<Layout> {
+        let mut bytes_per_row = max_bytes_per_row;
+
+        while bytes_per_row > 0 {
+            let layout = Layout::with_text(bytes_per_row, group_size);
+
+            if layout.width() <= width {
+                return Some(layout);
+            }
+
+            bytes_per_row /= 2;
+        }
+
+        let mut bytes_per_row = max_bytes_per_row;
+
+        while bytes_per_row > 0 {
+            let layout = Layout::without_text(bytes_per_row, group_size);
+
+            if layout.width() <= width {
+                return Some(layout);
+            }
+
+            bytes_per_row /= 2;
+        }
+
+        None
+    }
+
+    /// The narrowest width [`Layout::new`] finds a layout for.
+    pub fn min_width(group_size: usize) -> usize {
+        Layout::without_text(1, group_size).width()
+    }
+
+    fn with_text(bytes_per_row: usize, group_size: usize) -> Layout {
+        Layout {
+            bytes_per_row,
+            group_size,
+            hex_x: HEX_X,
+            text_x: Some(HEX_X + hex_width(bytes_per_row, group_size) + PANE_GAP),
+        }
+    }
+
+    fn without_text(bytes_per_row: usize, group_size: usize) -> Layout {
+        Layout {
+            bytes_per_row,
+            group_size,
+            hex_x: HEX_X,
+            text_x: None,
+        }
+    }
+
+    /// The number of columns used by the layout.
+    pub fn width(&self) -> usize {
+        match self.text_x {
+            Some(text_x) => text_x + self.bytes_per_row + 1,
+            None => self.hex_x + hex_width(self.bytes_per_row, self.group_size),
+        }
+    }
+
+    /// The column of the first hex digit of the byte in column `col`.
+    pub fn hex_col_x(&self, col: usize) -> usize {
+        self.hex_x + col * 3 + col / self.group_size
+    }
+
+    /// Whether an extra space follows the byte in column `col`.
+    pub fn is_group_end(&self, col: usize) -> bool {
+        col % self.group_size == self.group_size - 1 && col != self.bytes_per_row - 1
+    }
+}
+
+/// Width of the hex pane, including the trailing space after the last byte.
+fn hex_width(bytes_per_row: usize, group_size: usize) -> usize {
+    bytes_per_row * 3 + (bytes_per_row - 1) / group_size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_the_widest_layout() {
+        let layout = Layout::new(80, 16, 8).unwrap();
+        assert_eq!(layout.bytes_per_row, 16);
+        assert_eq!(layout.text_x, Some(63));
+        assert_eq!(layout.width(), 80);
+
+        // halves the bytes per row before dropping the text pane
+        let layout = Layout::new(79, 16, 8).unwrap();
+        assert_eq!(layout.bytes_per_row, 8);
+        assert!(layout.text_x.is_some());
+    }
+
+    #[test]
+    fn drops_the_text_pane_last() {
+        let layout = Layout::new(Layout::min_width(8), 16, 8).unwrap();
+        assert_eq!(layout.bytes_per_row, 1);
+        assert_eq!(layout.text_x, None);
+
+        assert!(Layout::new(Layout::min_width(8) - 1, 16, 8).is_none());
+    }
+
+    #[test]
+    fn groups() {
+        let layout = Layout::new(82, 16, 4).unwrap();
+        assert_eq!(layout.width(), 82);
+
+        assert_eq!(layout.hex_col_x(3), 21);
+        assert_eq!(layout.hex_col_x(4), 25);
+        assert!(layout.is_group_end(3));
+        assert!(!layout.is_group_end(4));
+        assert!(!layout.is_group_end(15));
+    }
+}
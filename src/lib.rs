@@ -0,0 +1,67 @@
+//! The core of the hexzen hex editor, which works without a terminal: editing a file in memory with
+//! [`FileEditor`](editor::FileEditor), searching it with [`search`], printing it in the formats of [`dump`] and
+//! [`template`], and reading structures out of it with [`structure`].
+//!
+//! [`run`] starts the editor itself, which the `hexzen` binary does with the default [`Plugins`].
+
+pub use cli::run;
+pub use plugin::{CommandContext, Decoder, HighlightProvider, PluginCommand, Plugins};
+pub use value::Endianness;
+
+mod binary_template;
+mod bookmarks;
+mod cli;
+mod collapse;
+mod command;
+mod config_file;
+mod damage;
+mod diff;
+pub mod dump;
+pub mod editor;
+mod expr;
+mod filter;
+mod frame;
+mod hash;
+mod highlight;
+mod history;
+mod inflate;
+mod inspector;
+mod jumps;
+mod kaitai;
+mod keymap;
+mod layout;
+mod load;
+mod magic;
+mod panel;
+mod plugin;
+mod scan;
+mod screen;
+mod script;
+pub mod search;
+mod stats;
+pub mod structure;
+#[cfg(unix)]
+mod suspend;
+pub mod template;
+mod value;
+
+pub(crate) const BYTES_PER_ROW: usize = 16;
+
+/// Formats `value` as `digits` hex digits, padded with zeros.
+pub(crate) fn hex(value: usize, digits: usize, uppercase: bool) -> String {
+    match uppercase {
+        true => format!("{:0digits$X}", value),
+        false => format!("{:0digits$x}", value),
+    }
+}
+
+/// Parses a hexadecimal number with an optional `0x` prefix, or a decimal number with a `0d` prefix.
+pub(crate) fn parse_number(input: &str) -> Option<usize> {
+    if let Some(input) = input.strip_prefix("0d") {
+        input.parse().ok()
+    } else {
+        let input = input.strip_prefix("0x").unwrap_or(input);
+
+        usize::from_str_radix(input, 16).ok()
+    }
+}
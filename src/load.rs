@@ -0,0 +1,192 @@
+//! Reading the file to edit and watching it for changes on worker threads, so that the editor keeps responding while
+//! slow storage, like a network mount or a disk spinning up, is read from.
+
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::bookmarks::Bookmarks;
+
+/// The file is read in chunks of this size, so that its progress can be shown and the load cancelled while it runs.
+const CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// How often the file is checked for changes made to it by other programs.
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The contents of a file read to edit it, and the bookmarks and annotations saved next to it.
+pub struct LoadedFile {
+    pub buffer: Vec<u8>,
+    pub bookmarks: Bookmarks,
+}
+
+enum Message {
+    /// The size of the file, once it is known.
+    Size(u64),
+    /// The number of bytes read so far.
+    Read(usize),
+    Done(Result<LoadedFile, io::Error>),
+}
+
+/// A file being read on a worker thread, sending back how much of it has been read one chunk at a time. Dropping the
+/// job stops reading after the chunk being read.
+pub struct LoadJob {
+    receiver: Receiver<Message>,
+    size: Option<u64>,
+    read: usize,
+}
+
+impl LoadJob {
+    /// Starts reading `filename` and then its bookmarks.
+    pub fn start(filename: &str) -> LoadJob {
+        let (sender, receiver) = mpsc::channel();
+        let filename = filename.to_owned();
+
+        thread::spawn(move || {
+            let result = read(&filename, &sender);
+            let _ = sender.send(Message::Done(result));
+        });
+
+        LoadJob {
+            receiver,
+            size: None,
+            read: 0,
+        }
+    }
+
+    /// The number of bytes read so far, and the size of the file if it is known yet.
+    pub fn progress(&self) -> (usize, Option<u64>) {
+        (self.read, self.size)
+    }
+
+    /// Collects the progress made since the last call, waiting up to `timeout` for the file to be read, and returns
+    /// it once it has been, or the error that stopped reading it.
+    pub fn receive(&mut self, timeout: Duration) -> Option<Result<LoadedFile, io::Error>> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            match self
+                .receiver
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok(Message::Size(size)) => self.size = Some(size),
+                Ok(Message::Read(read)) => self.read = read,
+                Ok(Message::Done(result)) => return Some(result),
+                Err(RecvTimeoutError::Timeout) => return None,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Some(Err(io::Error::other("the file stopped loading")))
+                }
+            }
+        }
+    }
+}
+
+/// Reads `filename` and its bookmarks, sending the progress to `sender` until the job is dropped.
+fn read(filename: &str, sender: &Sender<Message>) -> Result<LoadedFile, io::Error> {
+    read_file(filename, sender)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", filename, e)))
+}
+
+fn read_file(filename: &str, sender: &Sender<Message>) -> Result<LoadedFile, io::Error> {
+    let mut file = File::open(filename)?;
+    let metadata = file.metadata()?;
+    let dropped = || io::Error::from(io::ErrorKind::Interrupted);
+
+    // pipes and devices don't know how much they will give
+    if metadata.is_file() {
+        sender
+            .send(Message::Size(metadata.len()))
+            .map_err(|_| dropped())?;
+    }
+
+    // the size is only a hint, since the file can change while it is read
+    let mut buffer = Vec::with_capacity(metadata.len() as usize);
+
+    while (&mut file).take(CHUNK_SIZE).read_to_end(&mut buffer)? > 0 {
+        sender
+            .send(Message::Read(buffer.len()))
+            .map_err(|_| dropped())?;
+    }
+
+    Ok(LoadedFile {
+        buffer,
+        bookmarks: Bookmarks::load(filename)?,
+    })
+}
+
+/// Checks on a worker thread whether the file has been changed by another program, so that it can be reloaded.
+/// Dropping the watch stops it.
+pub struct FileWatch {
+    /// Tells the worker which file to watch from now on.
+    control: Sender<String>,
+    receiver: Receiver<usize>,
+    /// The number of times the watch was restarted, which the worker sends along with every change it sees, so that
+    /// changes seen before a restart are told apart.
+    restarts: usize,
+}
+
+impl FileWatch {
+    /// Starts watching `filename` for changes made to it from now on.
+    pub fn start(filename: &str) -> FileWatch {
+        let (control, control_receiver) = mpsc::channel::<String>();
+        let (sender, receiver) = mpsc::channel();
+        let mut filename = filename.to_owned();
+
+        thread::spawn(move || {
+            let mut restarts = 0;
+            let mut last = modification(&filename);
+
+            loop {
+                match control_receiver.recv_timeout(WATCH_INTERVAL) {
+                    Ok(name) => {
+                        filename = name;
+                        restarts += 1;
+                        last = modification(&filename);
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        let current = modification(&filename);
+
+                        // the watch was dropped
+                        if current != last && sender.send(restarts).is_err() {
+                            break;
+                        }
+
+                        last = current;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+
+        FileWatch {
+            control,
+            receiver,
+            restarts: 0,
+        }
+    }
+
+    /// Watches `filename` from now on, ignoring the changes made to it so far, like the ones made by saving it.
+    pub fn restart(&mut self, filename: &str) {
+        self.restarts += 1;
+        let _ = self.control.send(filename.to_owned());
+    }
+
+    /// Returns whether the file has changed since the last call.
+    pub fn changed(&mut self) -> bool {
+        self.receiver
+            .try_iter()
+            .filter(|&restarts| restarts == self.restarts)
+            .count()
+            > 0
+    }
+}
+
+/// The size of `filename` and when it was last modified, which change together with its contents, if it can be read.
+fn modification(filename: &str) -> Option<(u64, SystemTime)> {
+    let metadata = fs::metadata(filename).ok()?;
+
+    Some((metadata.len(), metadata.modified().ok()?))
+}
@@ -0,0 +1,262 @@
+use std::io;
+
+use memchr::memmem;
+
+use crate::inflate::{self, Format};
+
+/// Reads how long data of a type is from the data, which starts at the start of the slice.
+type LengthFn = fn(&[u8]) -> Option<usize>;
+
+/// Bytes that identify a file type when found at `offset`.
+pub struct Signature {
+    pub name: &'static str,
+    pub offset: usize,
+    pub magic: &'static [u8],
+    /// The built-in structure definition applied to files of this type.
+    pub structure: Option<&'static str>,
+    length: Option<LengthFn>,
+}
+
+impl Signature {
+    const fn new(name: &'static str, offset: usize, magic: &'static [u8]) -> Signature {
+        Signature {
+            name,
+            offset,
+            magic,
+            structure: None,
+            length: None,
+        }
+    }
+
+    const fn with_structure(self, structure: &'static str) -> Signature {
+        Signature {
+            structure: Some(structure),
+            ..self
+        }
+    }
+
+    const fn with_length(self, length: LengthFn) -> Signature {
+        Signature {
+            length: Some(length),
+            ..self
+        }
+    }
+
+    fn matches(&self, buffer: &[u8], start: usize) -> bool {
+        buffer
+            .get(start + self.offset..)
+            .is_some_and(|bytes| bytes.starts_with(self.magic))
+    }
+}
+
+/// Signatures the file type is identified by, tried in order so that those that could be mistaken for others, like
+/// the two bytes of a boot sector, come last.
+pub const SIGNATURES: [Signature; 36] = [
+    Signature::new("ELF executable", 0, b"\x7fELF")
+        .with_structure("elf")
+        .with_length(elf_len),
+    Signature::new("Mach-O executable", 0, b"\xcf\xfa\xed\xfe"),
+    Signature::new("Mach-O executable", 0, b"\xce\xfa\xed\xfe"),
+    Signature::new(
+        "Java class or Mach-O universal binary",
+        0,
+        b"\xca\xfe\xba\xbe",
+    ),
+    Signature::new("WebAssembly module", 0, b"\0asm"),
+    Signature::new("PNG image", 0, b"\x89PNG\r\n\x1a\n")
+        .with_structure("png")
+        .with_length(png_len),
+    Signature::new("GIF image", 0, b"GIF87a"),
+    Signature::new("GIF image", 0, b"GIF89a"),
+    Signature::new("JPEG image", 0, b"\xff\xd8\xff").with_length(jpeg_len),
+    Signature::new("PDF document", 0, b"%PDF-"),
+    Signature::new("ZIP archive", 0, b"PK\x03\x04")
+        .with_structure("zip")
+        .with_length(zip_len),
+    Signature::new("ZIP archive", 0, b"PK\x05\x06")
+        .with_structure("zip")
+        .with_length(zip_len),
+    Signature::new("7-Zip archive", 0, b"7z\xbc\xaf\x27\x1c"),
+    Signature::new("RAR archive", 0, b"Rar!\x1a\x07"),
+    Signature::new("tar archive", 257, b"ustar"),
+    Signature::new("cpio archive", 0, b"070701"),
+    Signature::new("gzip data", 0, b"\x1f\x8b\x08").with_length(gzip_len),
+    Signature::new("bzip2 data", 0, b"BZh"),
+    Signature::new("xz data", 0, b"\xfd7zXZ\0"),
+    Signature::new("zstd data", 0, b"\x28\xb5\x2f\xfd"),
+    Signature::new("LZ4 data", 0, b"\x04\x22\x4d\x18"),
+    Signature::new("SQLite database", 0, b"SQLite format 3\0").with_length(sqlite_len),
+    Signature::new("squashfs filesystem", 0, b"hsqs"),
+    Signature::new("device tree blob", 0, b"\xd0\x0d\xfe\xed"),
+    Signature::new("ISO 9660 image", 0x8001, b"CD001"),
+    Signature::new("RIFF data (WAV, AVI or WebP)", 0, b"RIFF"),
+    Signature::new("Ogg data", 0, b"OggS"),
+    Signature::new("FLAC audio", 0, b"fLaC"),
+    Signature::new("MP3 audio", 0, b"ID3"),
+    Signature::new("ext2/3/4 filesystem", 0x438, b"\x53\xef"),
+    Signature::new("FAT32 filesystem", 0x52, b"FAT32   ").with_structure("fat"),
+    Signature::new("FAT16 filesystem", 0x36, b"FAT16   ").with_structure("fat"),
+    Signature::new("FAT12 filesystem", 0x36, b"FAT12   ").with_structure("fat"),
+    Signature::new("DOS or PE executable", 0, b"MZ").with_structure("pe"),
+    Signature::new("BMP image", 0, b"BM").with_length(bmp_len),
+    Signature::new("MBR boot sector", 510, b"\x55\xaa").with_structure("mbr"),
+];
+
+/// Shorter magic bytes than this turn up by chance too often to be looked for inside files.
+const MIN_EMBEDDED_LEN: usize = 3;
+
+/// Returns the signature of the type of the data in `buffer`, if any is known.
+pub fn identify(buffer: &[u8]) -> Option<&'static Signature> {
+    SIGNATURES
+        .iter()
+        .find(|signature| signature.matches(buffer, 0))
+}
+
+/// Returns the offsets where data of a known type starts anywhere in `buffer`, including at its start, sorted by
+/// offset. Only signatures at the start of the data and long enough not to match by chance are looked for.
+pub fn find_embedded(buffer: &[u8]) -> Vec<(usize, &'static Signature)> {
+    let mut found: Vec<(usize, &'static Signature)> = SIGNATURES
+        .iter()
+        .filter(|signature| signature.offset == 0 && signature.magic.len() >= MIN_EMBEDDED_LEN)
+        .flat_map(|signature| {
+            memmem::find_iter(buffer, signature.magic).map(move |offset| (offset, signature))
+        })
+        .collect();
+
+    found.sort_by_key(|&(offset, _)| offset);
+    found
+}
+
+/// Returns the offset of the first signature found after `offset` in `buffer`, or the end of `buffer` if there is
+/// none, where data whose length isn't known is taken to end.
+pub fn next_embedded(buffer: &[u8], offset: usize) -> usize {
+    let rest = &buffer[offset + 1..];
+
+    SIGNATURES
+        .iter()
+        .filter(|signature| signature.offset == 0 && signature.magic.len() >= MIN_EMBEDDED_LEN)
+        .filter_map(|signature| memmem::find(rest, signature.magic))
+        .min()
+        .map_or(buffer.len(), |i| offset + 1 + i)
+}
+
+/// Returns how long the data of the type of `signature` at `offset` in `buffer` is, if its format tells and it is
+/// valid enough to follow. Data cut off by the end of `buffer` ends there.
+pub fn data_len(buffer: &[u8], offset: usize, signature: &Signature) -> Option<usize> {
+    let bytes = &buffer[offset..];
+    let len = (signature.length?)(bytes)?;
+
+    (len > 0).then(|| len.min(bytes.len()))
+}
+
+/// Reads an unsigned integer of `size` bytes at `i` in `bytes`, in little endian if `little` is set.
+fn uint(bytes: &[u8], i: usize, size: usize, little: bool) -> Option<usize> {
+    let bytes = bytes.get(i..i.checked_add(size)?)?;
+    let fold = |value: u64, &byte: &u8| value << 8 | u64::from(byte);
+    let value = if little {
+        bytes.iter().rev().fold(0, fold)
+    } else {
+        bytes.iter().fold(0, fold)
+    };
+
+    usize::try_from(value).ok()
+}
+
+/// Up to the end of the IEND chunk.
+fn png_len(bytes: &[u8]) -> Option<usize> {
+    let mut offset = 8;
+
+    loop {
+        let len = uint(bytes, offset, 4, false)?;
+        let ty = bytes.get(offset + 4..offset + 8)?;
+        offset = offset.checked_add(len)?.checked_add(12)?;
+
+        if ty == b"IEND" {
+            return Some(offset);
+        }
+    }
+}
+
+/// Up to the end of image marker after the scan. The segments before the scan are skipped by their lengths, so that
+/// the end of a thumbnail inside one isn't taken for the end of the image.
+fn jpeg_len(bytes: &[u8]) -> Option<usize> {
+    let mut offset = 2;
+
+    // segments up to the start of scan, which is followed by entropy-coded data
+    loop {
+        if *bytes.get(offset)? != 0xff {
+            return None;
+        }
+
+        let marker = *bytes.get(offset + 1)?;
+        let len = uint(bytes, offset + 2, 2, false)?;
+        offset += 2 + len;
+
+        if marker == 0xda {
+            break;
+        }
+    }
+
+    // in the entropy-coded data 0xff is followed by 0 or a restart marker, while a later frame of a progressive image
+    // starts with a new segment that is skipped the same way
+    loop {
+        offset += memchr::memchr(0xff, bytes.get(offset..)?)?;
+
+        match *bytes.get(offset + 1)? {
+            0xd9 => return Some(offset + 2),
+            0x00 | 0xd0..=0xd7 | 0xff => offset += 1,
+            _ => offset += 2 + uint(bytes, offset + 2, 2, false)?,
+        }
+    }
+}
+
+/// Up to the end of the comment after the end of central directory record.
+fn zip_len(bytes: &[u8]) -> Option<usize> {
+    let end = memmem::find(bytes, b"PK\x05\x06")?;
+    let comment_len = uint(bytes, end + 20, 2, true)?;
+
+    Some(end + 22 + comment_len)
+}
+
+/// Up to where the stream and its trailer end.
+fn gzip_len(bytes: &[u8]) -> Option<usize> {
+    let stream = inflate::inflate(bytes, 0, Format::Gzip, &mut io::sink())?;
+
+    (!stream.cut_off).then_some(stream.compressed_len)
+}
+
+/// Up to the end of the section header table, which linkers put after the sections.
+fn elf_len(bytes: &[u8]) -> Option<usize> {
+    let little = match bytes.get(5)? {
+        1 => true,
+        2 => false,
+        _ => return None,
+    };
+
+    // where the offset of the table, its entry size and its entry count are for 32- and 64-bit files
+    let (table, table_size, entry_size, count) = match bytes.get(4)? {
+        1 => (0x20, 4, 0x2e, 0x30),
+        2 => (0x28, 8, 0x3a, 0x3c),
+        _ => return None,
+    };
+    let table = uint(bytes, table, table_size, little)?;
+    let entry_size = uint(bytes, entry_size, 2, little)?;
+    let count = uint(bytes, count, 2, little)?;
+
+    table.checked_add(entry_size.checked_mul(count)?)
+}
+
+/// The page size times the number of pages in the header.
+fn sqlite_len(bytes: &[u8]) -> Option<usize> {
+    let page_size = match uint(bytes, 16, 2, false)? {
+        1 => 0x10000,
+        page_size => page_size,
+    };
+
+    page_size.checked_mul(uint(bytes, 28, 4, false)?)
+}
+
+/// The size in the file header.
+fn bmp_len(bytes: &[u8]) -> Option<usize> {
+    uint(bytes, 2, 4, true)
+}
@@ -0,0 +1,82 @@
+use std::cmp;
+
+#[derive(PartialEq)]
+pub enum PanelKind {
+    Bookmarks,
+    Highlights,
+    SearchResults,
+    ReplacePreview,
+    Inspector,
+    Entropy,
+    Histogram,
+    Strings,
+    Signatures,
+    Structure,
+    Changes,
+    Streams,
+    Inflated,
+    Pointers,
+}
+
+pub struct PanelEntry {
+    /// The offset the editor jumps to when the entry is chosen.
+    pub offset: Option<usize>,
+    pub text: String,
+}
+
+/// A list shown in place of the hex view, with one entry selected at a time.
+pub struct ListPanel {
+    pub kind: PanelKind,
+    pub title: String,
+    pub entries: Vec<PanelEntry>,
+    selected: usize,
+    scroll: usize,
+}
+
+impl ListPanel {
+    pub fn new(kind: PanelKind, title: String, entries: Vec<PanelEntry>) -> ListPanel {
+        ListPanel {
+            kind,
+            title,
+            entries,
+            selected: 0,
+            scroll: 0,
+        }
+    }
+
+    pub fn selected(&self) -> Option<&PanelEntry> {
+        self.entries.get(self.selected)
+    }
+
+    pub fn selected_idx(&self) -> usize {
+        self.selected
+    }
+
+    /// Moves the selection by `delta` entries, stopping at the first and last entry.
+    pub fn move_selection(&mut self, delta: isize) {
+        self.selected = self
+            .selected
+            .saturating_add_signed(delta)
+            .min(self.entries.len().saturating_sub(1));
+    }
+
+    pub fn remove_selected(&mut self) {
+        if self.selected < self.entries.len() {
+            self.entries.remove(self.selected);
+            self.move_selection(0);
+        }
+    }
+
+    /// Scrolls so that the selected entry is one of the `rows` visible entries, and returns the index of the first
+    /// visible entry.
+    pub fn scroll(&mut self, rows: usize) -> usize {
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + rows {
+            self.scroll = self.selected + 1 - rows;
+        }
+
+        self.scroll = cmp::min(self.scroll, self.entries.len().saturating_sub(1));
+        self.scroll
+    }
+}
@@ -0,0 +1,121 @@
+use std::ops::Range;
+
+use crate::value::Endianness;
+
+/// Decodes the bytes at the cursor into a value shown by the inspector.
+pub trait Decoder {
+    /// Shown next to the value in the inspector.
+    fn name(&self) -> &str;
+
+    /// Decodes the value at the start of `bytes`, which run from the cursor to the end of the file. Returns `None` if
+    /// they don't hold a value of this kind, e.g. because there are too few of them.
+    fn decode(&self, bytes: &[u8], endianness: Endianness) -> Option<String>;
+}
+
+/// Finds ranges of a file to highlight when it is opened.
+pub trait HighlightProvider {
+    /// The name of the highlight layer the ranges are shown in.
+    fn name(&self) -> &str;
+
+    /// Returns the ranges of `buffer` to highlight, which must not overlap.
+    fn highlight(&self, buffer: &[u8]) -> Vec<Range<usize>>;
+}
+
+/// A command run from the command bar as `:<name> <args>`.
+pub trait PluginCommand {
+    fn name(&self) -> &str;
+
+    /// Runs the command, returning a message to show in the status line.
+    fn run(&self, args: &str, context: &mut CommandContext) -> Result<Option<String>, String>;
+}
+
+/// What a plugin command can see and change of the edited file. Writes are applied once the command returns, as a
+/// single change that is undone at once.
+pub struct CommandContext<'a> {
+    buffer: &'a [u8],
+    cursor: usize,
+    writes: Vec<(usize, Vec<u8>)>,
+}
+
+impl<'a> CommandContext<'a> {
+    pub(crate) fn new(buffer: &'a [u8], cursor: usize) -> CommandContext<'a> {
+        CommandContext {
+            buffer,
+            cursor,
+            writes: Vec::new(),
+        }
+    }
+
+    /// Returns the contents of the file, without the writes made by the command.
+    pub fn data(&self) -> &[u8] {
+        self.buffer
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Moves the cursor to `offset`, or to the last byte of the file if it is past the end.
+    pub fn set_cursor(&mut self, offset: usize) {
+        self.cursor = offset.min(self.buffer.len().saturating_sub(1));
+    }
+
+    /// Overwrites the bytes starting at `offset`. The file can't change size, so they must fit in it.
+    pub fn write(&mut self, offset: usize, bytes: &[u8]) -> Result<(), String> {
+        match offset.checked_add(bytes.len()) {
+            Some(end) if end <= self.buffer.len() => {
+                self.writes.push((offset, bytes.to_vec()));
+                Ok(())
+            }
+            _ => Err(format!(
+                "{:#x} bytes at {:#x} are outside the file",
+                bytes.len(),
+                offset
+            )),
+        }
+    }
+
+    pub(crate) fn into_changes(self) -> (usize, Vec<(usize, Vec<u8>)>) {
+        (self.cursor, self.writes)
+    }
+}
+
+/// Decoders, highlight providers and commands added to the editor, for building variants of hexzen suited to
+/// particular kinds of files.
+#[derive(Default)]
+pub struct Plugins {
+    decoders: Vec<Box<dyn Decoder>>,
+    highlighters: Vec<Box<dyn HighlightProvider>>,
+    commands: Vec<Box<dyn PluginCommand>>,
+}
+
+impl Plugins {
+    /// Adds a decoder, shown in the inspector after the built-in ones.
+    pub fn add_decoder(&mut self, decoder: impl Decoder + 'static) {
+        self.decoders.push(Box::new(decoder));
+    }
+
+    pub fn add_highlighter(&mut self, highlighter: impl HighlightProvider + 'static) {
+        self.highlighters.push(Box::new(highlighter));
+    }
+
+    /// Adds a command, which is used in place of a built-in command with the same name.
+    pub fn add_command(&mut self, command: impl PluginCommand + 'static) {
+        self.commands.push(Box::new(command));
+    }
+
+    pub(crate) fn decoders(&self) -> &[Box<dyn Decoder>] {
+        &self.decoders
+    }
+
+    pub(crate) fn highlighters(&self) -> &[Box<dyn HighlightProvider>] {
+        &self.highlighters
+    }
+
+    pub(crate) fn command(&self, name: &str) -> Option<&dyn PluginCommand> {
+        self.commands
+            .iter()
+            .find(|command| command.name() == name)
+            .map(|command| command.as_ref())
+    }
+}
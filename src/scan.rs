@@ -0,0 +1,92 @@
+use std::ops::Range;
+
+use crate::value::{self, Endianness};
+
+/// Returns the first offset after `position` where a region of bytes other than `fill` starts.
+pub fn next_data(buffer: &[u8], position: usize, fill: u8) -> Option<usize> {
+    (position + 1..buffer.len()).find(|&i| buffer[i] != fill && buffer[i - 1] == fill)
+}
+
+/// Returns the last offset before `position` where a region of bytes other than `fill` starts.
+pub fn prev_data(buffer: &[u8], position: usize, fill: u8) -> Option<usize> {
+    (0..position)
+        .rev()
+        .find(|&i| buffer[i] != fill && (i == 0 || buffer[i - 1] == fill))
+}
+
+pub fn is_printable(byte: u8) -> bool {
+    (32..=126).contains(&byte)
+}
+
+/// Returns the first offset after `position` where a run of at least `min_len` printable characters starts.
+pub fn next_string(buffer: &[u8], position: usize, min_len: usize) -> Option<usize> {
+    (position + 1..buffer.len()).find(|&i| is_string_start(buffer, i, min_len))
+}
+
+/// Returns the last offset before `position` where a run of at least `min_len` printable characters starts.
+pub fn prev_string(buffer: &[u8], position: usize, min_len: usize) -> Option<usize> {
+    (0..position)
+        .rev()
+        .find(|&i| is_string_start(buffer, i, min_len))
+}
+
+/// Returns the ranges of the runs of at least `min_len` printable characters in `buffer`.
+pub fn strings(buffer: &[u8], min_len: usize) -> Vec<Range<usize>> {
+    let mut strings = Vec::new();
+    let mut start = None;
+
+    for (i, &byte) in buffer.iter().enumerate() {
+        match (is_printable(byte), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                if i - s >= min_len {
+                    strings.push(s..i);
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(s) = start.filter(|&s| buffer.len() - s >= min_len) {
+        strings.push(s..buffer.len());
+    }
+
+    strings
+}
+
+fn is_string_start(buffer: &[u8], i: usize, min_len: usize) -> bool {
+    (i == 0 || !is_printable(buffer[i - 1]))
+        && buffer[i..]
+            .iter()
+            .take(min_len)
+            .filter(|&&b| is_printable(b))
+            .count()
+            == min_len
+}
+
+/// Returns the offsets in `range` of the values of `size` bytes, aligned to their size, that point into `buffer` once
+/// `base` is subtracted, along with the offset each points to. Null pointers are left out.
+pub fn pointers(
+    buffer: &[u8],
+    range: Range<usize>,
+    size: usize,
+    base: usize,
+    endianness: Endianness,
+) -> Vec<(usize, usize)> {
+    let start = range.start.next_multiple_of(size);
+
+    (start..range.end.saturating_sub(size - 1))
+        .step_by(size)
+        .filter_map(|offset| {
+            let pointer = value::read_uint(&buffer[offset..offset + size], endianness);
+            let target = usize::try_from(pointer)
+                .ok()
+                .filter(|&pointer| pointer != 0)?
+                .checked_sub(base)
+                .filter(|&target| target < buffer.len())?;
+
+            Some((offset, target))
+        })
+        .collect()
+}
@@ -1,240 +1,358 @@
 use std::{
-    io::{self, stdout, ErrorKind, Stdout, Write},
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt::Write as _,
+    fs,
+    io::{self, stdout, Stdout, Write},
     mem,
+    ops::Range,
+    panic,
+    sync::Arc,
+    thread,
+    time::Duration,
 };
 
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode},
-    execute, queue, style, terminal,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute,
+    style::{self, Color, Colors},
+    terminal,
     tty::IsTty,
 };
 use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use std::{char, cmp};
 
+use base64::{prelude::BASE64_STANDARD, Engine};
 use bytesize::ByteSize;
 
 use crate::{
-    search::{self, SearchResults},
-    Config, CursorMovementType, EditorMode, FileEditor, BYTES_PER_ROW,
+    bookmarks::{Bookmarks, DEFAULT_ANNOTATION_COLOR},
+    cli::Config,
+    collapse::{Run, RunMap},
+    command::{self, Command},
+    damage::{Cell, Damage},
+    editor::FileEditor,
+    expr, filter,
+    frame::Frame,
+    hash,
+    highlight::{self, Highlights},
+    history::{History, HistoryKind},
+    inflate, inspector,
+    jumps::JumpList,
+    keymap::Action,
+    layout::Layout,
+    load::{FileWatch, LoadJob, LoadedFile, WATCH_INTERVAL},
+    magic,
+    panel::{ListPanel, PanelEntry, PanelKind},
+    plugin::{CommandContext, Plugins},
+    scan, script,
+    search::{self, Direction, Pattern, SearchJob, SearchResults},
+    stats,
+    structure::{self, Structure},
+    value::{self, Endianness},
+    BYTES_PER_ROW,
 };
 
-const SCREEN_WIDTH: usize = 80;
+#[cfg(unix)]
+use crate::suspend;
+
+/// Number of bytes shown for each match in the search results panel.
+const RESULT_CONTEXT: usize = 8;
+
+/// Number of bytes shown before and after each change in the changes panel.
+const CHANGE_CONTEXT: usize = 8;
+
+/// Number of inflated bytes shown in the preview of a compressed stream, which `:inflate` can write to a file in full.
+const INFLATE_PREVIEW_LEN: usize = 0x10000;
+
+/// The name of the highlight layer of the fields of the structure applied last.
+const STRUCTURE_LAYER: &str = "structure";
+
+/// Columns taken by the bar of a block of random data in the entropy panel.
+const ENTROPY_BAR_WIDTH: usize = 64;
+
+/// Columns taken by the bar of the most common byte value in the histogram panel.
+const HISTOGRAM_BAR_WIDTH: usize = 48;
+
+/// How deep macros can play other macros, including themselves.
+const MAX_MACRO_DEPTH: usize = 16;
+
+/// The fewest rows the editor can be drawn in, with the header, a row of bytes and the status line.
+const MIN_HEIGHT: usize = 5;
+
+/// How often the progress of loading the file and the results of a running search are collected while waiting for
+/// input.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 type InputReadCallback = Box<dyn FnMut(&mut Screen, &str)>;
 
 pub struct Screen {
     editor: FileEditor,
+    /// Reads the file while the editor is open, which shows its progress until the file is first opened, and again
+    /// whenever the watch sees it change.
+    load_job: Option<LoadJob>,
+    opened: bool,
+    watch: Option<FileWatch>,
+    /// A position to go to once the file is loaded.
+    pending_goto: Option<String>,
     running: bool,
     stdout: Stdout,
     width: usize,
     height: usize,
+    layout: Option<Layout>,
+    /// The frame being drawn, and the one shown on the terminal if it is known.
+    frame: Frame,
+    presented: Option<Frame>,
+    /// What the rows of the grid in the presented frame were drawn from.
+    damage: Damage,
+    /// Reused while drawing, to build the lines that are cut to width and the output presented, without allocating
+    /// every frame.
+    line: String,
+    output: Vec<u8>,
+    /// The file size shown on the status line, formatted when it changes.
+    size_label: (usize, String),
+    collapse_runs: bool,
+    runs: RunMap,
     editor_mode: EditorMode,
     screen_mode: ScreenMode,
     input_buffer: String,
     input_callback: Option<InputReadCallback>,
     input_prefix: String,
     search_results: Option<SearchResults>,
+    search_job: Option<SearchJob>,
+    /// The input of the last search, repeated when a search prompt is left empty.
+    last_search: Option<String>,
+    search_history: History,
+    goto_history: History,
+    command_history: History,
+    /// The history that Up and Down go through in the current prompt, if it has one.
+    input_history: Option<HistoryKind>,
+    /// The history entry being shown in the prompt, or `None` if the user's own input is shown.
+    history_index: Option<usize>,
+    /// What the user had typed before going through the history.
+    history_draft: String,
+    /// The text searched for in the history of the prompt, while searching it with Ctrl+R.
+    history_search: Option<String>,
+    /// Completes the input of the current prompt when Tab is pressed.
+    input_completer: Option<fn(&str) -> Option<String>>,
+    jumps: JumpList,
+    marks: HashMap<char, usize>,
+    /// Where the selection was started, if there is one. It spans from there to the cursor.
+    selection_anchor: Option<usize>,
+    /// A key that needs another key to complete the command, like `m` followed by the name of a mark.
+    pending_key: Option<char>,
+    /// The keys recorded into each macro register.
+    macros: HashMap<char, Vec<KeyEvent>>,
+    /// The register being recorded into and the keys recorded so far.
+    recording: Option<(char, Vec<KeyEvent>)>,
+    last_macro: Option<char>,
+    /// How many macros are being played inside each other. Drawing is skipped until they are done.
+    macro_depth: usize,
+    bookmarks: Bookmarks,
+    /// Only the bookmarks and annotations containing this are listed in their panel.
+    marks_filter: String,
+    panel: Option<ListPanel>,
+    highlights: Highlights,
+    /// A replace-all waiting to be confirmed.
+    pending_replace: Option<Replacement>,
+    /// Shown in the status line until the next key press.
+    message: Option<String>,
+    /// The type of the file, identified by its magic bytes.
+    file_type: Option<&'static str>,
+    /// The structure applied last, whose fields are highlighted.
+    structure: Option<Structure>,
+    /// The indices of the nodes of the structure whose fields are hidden in its panel.
+    folded_nodes: HashSet<usize>,
     config: Config,
+    plugins: Plugins,
+}
+
+/// An entry of the bookmarks panel, with its index among the bookmarks or the annotations.
+#[derive(Clone, Copy)]
+enum Mark {
+    Bookmark(usize),
+    Annotation(usize),
+}
+
+/// Bytes to write at each match of a replace-all.
+struct Replacement {
+    positions: Vec<usize>,
+    bytes: Vec<u8>,
+}
+
+enum CursorMovementType {
+    Right,
+    Left,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    RowStart,
+    RowEnd,
+    ScrollUp,
+    ScrollDown,
+    GroupLeft,
+    GroupRight,
+    RecordUp,
+    RecordDown,
+    FileStart,
+    FileEnd,
+}
+
+#[derive(PartialEq)]
+pub(crate) enum EditorMode {
+    HexMode,
+    TextMode,
+}
+
+impl EditorMode {
+    pub fn next(&self) -> EditorMode {
+        match self {
+            EditorMode::HexMode => EditorMode::TextMode,
+            EditorMode::TextMode => EditorMode::HexMode,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EditorMode::HexMode => "normal",
+            EditorMode::TextMode => "text",
+        }
+    }
 }
 
+#[allow(clippy::enum_variant_names)]
 enum ScreenMode {
     EditMode,
     CommandMode,
+    PanelMode,
 }
 
 impl Screen {
-    pub fn new(filename: &str, config: Config) -> Result<Screen, io::Error> {
+    pub fn new(filename: &str, config: Config, plugins: Plugins) -> Result<Screen, io::Error> {
         let stdout = stdout();
 
         if !stdout.is_tty() {
-            return Err(io::Error::new(ErrorKind::Other, "not a terminal"));
+            return Err(io::Error::other("not a terminal"));
         }
 
-        let editor = FileEditor::new(filename)?;
         let (width, height) = terminal::size()?;
 
         Ok(Screen {
-            editor,
+            // the file is edited once the load job has read it
+            editor: FileEditor::from_bytes(filename, Vec::new()),
+            load_job: Some(LoadJob::start(filename)),
+            opened: false,
+            watch: None,
+            pending_goto: None,
             running: true,
             stdout,
             width: width.into(),
             height: height.into(),
+            layout: Layout::new(width.into(), config.bytes_per_row, config.group_size),
+            frame: Frame::new(width.into(), height.into()),
+            presented: None,
+            damage: Damage::default(),
+            line: String::new(),
+            output: Vec::new(),
+            size_label: (usize::MAX, String::new()),
+            collapse_runs: config.collapse_runs,
+            runs: RunMap::default(),
             editor_mode: EditorMode::HexMode,
             screen_mode: ScreenMode::EditMode,
             input_buffer: String::new(),
             input_callback: None,
             input_prefix: String::new(),
             search_results: None,
+            search_job: None,
+            last_search: None,
+            search_history: History::load(HistoryKind::Search),
+            goto_history: History::load(HistoryKind::Goto),
+            command_history: History::load(HistoryKind::Command),
+            input_history: None,
+            history_index: None,
+            history_draft: String::new(),
+            history_search: None,
+            input_completer: None,
+            jumps: JumpList::default(),
+            marks: HashMap::new(),
+            selection_anchor: None,
+            pending_key: None,
+            macros: HashMap::new(),
+            recording: None,
+            last_macro: None,
+            macro_depth: 0,
+            bookmarks: Bookmarks::new(filename),
+            marks_filter: String::new(),
+            panel: None,
+            highlights: Highlights::default(),
+            pending_replace: None,
+            message: None,
+            file_type: None,
+            structure: None,
+            folded_nodes: HashSet::new(),
             config,
+            plugins,
         })
     }
 
     pub fn screen_loop(&mut self) -> Result<(), io::Error> {
-        terminal::enable_raw_mode()?;
-        queue!(self.stdout, terminal::EnterAlternateScreen)?;
-        queue!(self.stdout, terminal::Clear(terminal::ClearType::All))?;
-        queue!(self.stdout, cursor::SetCursorStyle::SteadyBlock)?;
-        self.draw()?;
+        #[cfg(unix)]
+        suspend::handle_signals()?;
 
-        while self.running {
-            match event::read()? {
-                Event::Key(event) => match self.screen_mode {
-                    ScreenMode::EditMode => match event.code {
-                        KeyCode::Char(c) => match self.editor_mode {
-                            EditorMode::HexMode => match c {
-                                'a'..='f' | '0'..='9' => {
-                                    let nibble = hex_char_to_u8(c).unwrap();
-                                    self.editor.write_nibble(nibble)?;
-                                    self.move_cursor(CursorMovementType::Right)?;
-                                }
-                                'u' | 'z' => {
-                                    let undid = self.editor.undo();
+        // without this, a panic would leave the terminal in raw mode with its message in the alternate screen
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let _ = restore_terminal();
+            default_hook(info);
+        }));
 
-                                    if undid {
-                                        self.draw()?;
-                                    }
-                                }
-                                'r' => {
-                                    let redid = self.editor.redo();
+        setup_terminal()?;
 
-                                    if redid {
-                                        self.draw()?;
-                                    }
-                                }
-                                'w' => {
-                                    if let Err(e) = self.editor.save() {
-                                        eprintln!("unable to save file: {:?}", e);
-                                    }
+        // a file that loads quickly is opened before the first frame, without its progress flashing up
+        self.receive_file(POLL_INTERVAL)?;
+        self.draw()?;
 
-                                    self.draw()?;
-                                }
-                                'j' => {
-                                    self.read_user_input(
-                                        String::from("j "),
-                                        Box::new(|screen: &mut Screen, mut input: &str| {
-                                            input = input.strip_prefix("0x").unwrap_or(input);
-
-                                            if let Ok(address) = usize::from_str_radix(input, 16) {
-                                                screen.editor.cursor_nibble = 2 * address;
-                                            }
-                                        }),
-                                    )?;
-                                }
-                                '/' => {
-                                    self.search_results = None;
-
-                                    self.read_user_input(
-                                        String::from("/"),
-                                        Box::new(|screen: &mut Screen, input: &str| {
-                                            screen.search_results =
-                                                search::search(&screen.editor.buffer, input);
-
-                                            if let Some(results) = &screen.search_results {
-                                                screen.editor.cursor_nibble = 2 * results.result();
-                                            }
-                                        }),
-                                    )?;
-                                }
-                                'n' => {
-                                    if let Some(search_results) = &mut self.search_results {
-                                        self.editor.cursor_nibble = 2 * search_results.next();
-                                        self.draw()?;
-                                    }
-                                }
-                                'm' => {
-                                    if let Some(search_results) = &mut self.search_results {
-                                        self.editor.cursor_nibble = 2 * search_results.prev();
-                                        self.draw()?;
-                                    }
-                                }
-                                'q' => {
-                                    if self.editor.saved {
-                                        self.running = false;
-                                    } else {
-                                        self.read_user_input(
-                                            String::from("quit without saving? "),
-                                            Box::new(|screen: &mut Screen, input: &str| {
-                                                if input.eq_ignore_ascii_case("yes")
-                                                    || input.eq_ignore_ascii_case("y")
-                                                {
-                                                    screen.running = false;
-                                                }
-                                            }),
-                                        )?;
-                                    }
-                                }
-                                _ => {}
-                            },
-                            EditorMode::TextMode => {
-                                if let ' '..='~' = c {
-                                    self.editor.write_byte(c as u8)?;
-                                    self.move_cursor(CursorMovementType::Right)?;
-                                }
-                            }
-                        },
-                        KeyCode::Right => {
-                            self.move_cursor(CursorMovementType::Right)?;
-                        }
-                        KeyCode::Left | KeyCode::Backspace => {
-                            self.move_cursor(CursorMovementType::Left)?;
-                        }
-                        KeyCode::Down => {
-                            self.move_cursor(CursorMovementType::Down)?;
-                        }
-                        KeyCode::Up => {
-                            self.move_cursor(CursorMovementType::Up)?;
-                        }
-                        KeyCode::PageDown => {
-                            self.move_cursor(CursorMovementType::PageDown)?;
-                        }
-                        KeyCode::PageUp => {
-                            self.move_cursor(CursorMovementType::PageUp)?;
-                        }
-                        KeyCode::Tab => {
-                            self.cycle_editor_mode()?;
-                        }
-                        KeyCode::Esc => {
-                            self.set_editor_mode(EditorMode::HexMode)?;
-                        }
-                        _ => {}
-                    },
-                    ScreenMode::CommandMode => match event.code {
-                        KeyCode::Char(c) => {
-                            self.input_buffer.push(c);
-                            self.draw()?;
-                        }
-                        KeyCode::Esc => {
-                            self.screen_mode = ScreenMode::EditMode;
-                            self.input_buffer.clear();
-                            self.input_callback = None;
-                            self.draw()?;
-                        }
-                        KeyCode::Backspace => {
-                            self.input_buffer.pop();
+        while self.running {
+            self.receive_change()?;
 
-                            self.draw()?;
-                        }
-                        KeyCode::Enter => {
-                            let command = mem::take(&mut self.input_buffer);
+            let interval = if self.load_job.is_some() || self.search_job.is_some() {
+                if self.load_job.is_some() {
+                    self.receive_file(Duration::ZERO)?;
+                    self.draw()?;
+                }
 
-                            let callback = self.input_callback.take();
-                            if let Some(mut callback) = callback {
-                                callback(self, &command);
-                            }
+                self.receive_search_results()?;
+                POLL_INTERVAL
+            } else {
+                WATCH_INTERVAL
+            };
 
-                            self.screen_mode = ScreenMode::EditMode;
-                            self.draw()?;
-                        }
-                        _ => {}
-                    },
-                },
+            if !event::poll(interval)? {
+                continue;
+            }
+
+            match event::read()? {
+                // only quitting works while the file loads
+                Event::Key(event)
+                    if !self.opened && self.config.keymap.get(&event) == Some(Action::Quit) =>
+                {
+                    self.running = false;
+                }
+                // input is ignored until the terminal is large enough to show what it does, or the file is loaded
+                Event::Key(_) | Event::Paste(_) if self.too_small() || !self.opened => {}
+                Event::Key(event) => self.handle_key(event)?,
+                Event::Paste(text) => self.paste(&text)?,
                 Event::Resize(new_width, new_height) => {
                     self.width = new_width.into();
                     self.height = new_height.into();
+                    self.update_layout();
 
-                    queue!(self.stdout, terminal::Clear(terminal::ClearType::All))?;
+                    // what the terminal shows after a resize isn't known, so the next frame is drawn in full
+                    self.frame = Frame::new(self.width, self.height);
+                    self.presented = None;
                     self.draw()?;
                 }
                 _ => {}
@@ -244,339 +362,3381 @@ impl Screen {
         Ok(())
     }
 
-    fn read_user_input(
-        &mut self,
-        prefix: String,
-        callback: InputReadCallback,
-    ) -> Result<(), io::Error> {
-        self.screen_mode = ScreenMode::CommandMode;
-        self.input_callback = Some(callback);
-        self.input_prefix = prefix;
-        self.draw()
-    }
-
-    fn draw(&mut self) -> Result<(), io::Error> {
-        if self.height < 5 {
-            return Ok(());
+    fn handle_key(&mut self, event: KeyEvent) -> Result<(), io::Error> {
+        if let Some((_, keys)) = &mut self.recording {
+            if self.macro_depth == 0 {
+                keys.push(event);
+            }
         }
 
-        queue!(self.stdout, cursor::MoveTo(12, 1))?;
-        write!(
-            self.stdout,
-            "00 01 02 03 04 05 06 07  08 09 0a 0b 0c 0d 0e 0f"
-        )?;
+        self.message = None;
+        let pending_key = self.pending_key.take();
 
-        self.editor.cursor_nibble = self
-            .editor
-            .cursor_nibble
-            .clamp(0, 2 * self.editor.file_size() - 1);
-
-        let cursor_row = (self.editor.cursor_nibble / (2 * BYTES_PER_ROW)) * BYTES_PER_ROW;
-        if self.editor.cursor_nibble < 2 * self.editor.offset {
-            self.editor.offset = cursor_row;
-        } else if self.editor.cursor_nibble
-            >= 2 * (self.editor.offset + BYTES_PER_ROW * (self.height - 4))
-        {
-            self.editor.offset = cursor_row - BYTES_PER_ROW * (self.height - 4 - 1);
+        // keys used by a history search aren't typed into the prompt
+        if matches!(self.screen_mode, ScreenMode::CommandMode) && self.search_history_key(event)? {
+            return Ok(());
         }
 
-        self.editor.offset = self.editor.offset.clamp(
-            0,
-            self.editor.file_size() - self.editor.file_size() % BYTES_PER_ROW,
-        );
-
-        let buf = self.editor.read_bytes(BYTES_PER_ROW * (self.height - 4));
-
-        let data_rows = cmp::min(
-            self.height - 4,
-            (self.editor.file_size() - self.editor.offset + BYTES_PER_ROW - 1) / BYTES_PER_ROW,
-        );
-
-        for row in 0..self.height - 4 {
-            let y = (row + 3).try_into().unwrap();
-
-            queue!(self.stdout, cursor::MoveTo(1, y))?;
-            if row < data_rows {
-                write!(
-                    self.stdout,
-                    "{:08x}",
-                    (self.editor.offset + row * 0x10) & 0xfffffff0
-                )?;
-            } else {
-                write!(self.stdout, "        ")?;
-            }
-
-            queue!(self.stdout, cursor::MoveTo(63, y))?;
-            for col in 0..BYTES_PER_ROW {
-                let offset = self.editor.offset + row * BYTES_PER_ROW + col;
-
-                if offset >= self.editor.file_size() {
-                    write!(self.stdout, " ")?;
-                } else {
-                    let mut c = buf[row * BYTES_PER_ROW + col] as char;
-
-                    if !(32..=126).contains(&(c as u8)) {
-                        c = self.config.replacement_char;
+        match self.screen_mode {
+            ScreenMode::EditMode => match event.code {
+                KeyCode::Char(c) if pending_key.is_some() => {
+                    self.handle_pending_key(pending_key.unwrap(), c)?;
+                }
+                KeyCode::Char(c)
+                    if !event
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+                        && self.writes_byte(c) =>
+                {
+                    match self.editor_mode {
+                        EditorMode::HexMode => {
+                            let nibble = hex_char_to_u8(c).unwrap();
+                            match self.editor.write_nibble(nibble) {
+                                Ok(()) => self.move_cursor(CursorMovementType::Right)?,
+                                Err(e) => {
+                                    self.message = Some(e.to_string());
+                                    self.draw()?;
+                                }
+                            }
+                        }
+                        EditorMode::TextMode => self.write_char(c)?,
+                    }
+                }
+                _ => {
+                    if let Some(action) = self.config.keymap.get(&event) {
+                        self.run_action(action)?;
                     }
+                }
+            },
+            ScreenMode::CommandMode => match event.code {
+                KeyCode::Char('r')
+                    if event.modifiers.contains(KeyModifiers::CONTROL)
+                        && self.input_history.is_some() =>
+                {
+                    self.history_search = Some(String::new());
+                    self.draw()?;
+                }
+                KeyCode::Char(c) => {
+                    self.input_buffer.push(c);
+                    self.draw()?;
+                }
+                KeyCode::Esc => {
+                    self.screen_mode = self.mode_after_prompt();
+                    self.input_buffer.clear();
+                    self.input_callback = None;
+                    self.input_history = None;
+                    self.draw()?;
+                }
+                KeyCode::Backspace => {
+                    self.input_buffer.pop();
 
-                    let match_len = self
-                        .search_results
-                        .as_ref()
-                        .and_then(|res| res.match_len(offset));
-                    let highlight = self.config.highlight_colors.is_some() && match_len.is_some();
-
-                    if highlight {
-                        queue!(
-                            self.stdout,
-                            style::SetColors(self.config.highlight_colors.unwrap())
-                        )?;
-                        write!(self.stdout, "{}", c)?;
-                        queue!(self.stdout, style::ResetColor)?;
-                    } else {
-                        write!(self.stdout, "{}", c)?;
+                    self.draw()?;
+                }
+                KeyCode::Tab => {
+                    if let Some(completion) = self
+                        .input_completer
+                        .and_then(|complete| complete(&self.input_buffer))
+                    {
+                        self.input_buffer = completion;
+                        self.draw()?;
                     }
                 }
-            }
+                KeyCode::Up => {
+                    self.browse_history(-1)?;
+                }
+                KeyCode::Down => {
+                    self.browse_history(1)?;
+                }
+                KeyCode::Enter => {
+                    let command = mem::take(&mut self.input_buffer);
+
+                    if let Some(kind) = self.input_history.take() {
+                        let history = self.history_mut(kind);
+                        history.push(&command);
+                        // the history is a convenience, so failing to save it shouldn't interrupt anything
+                        let _ = history.save();
+                    }
 
-            queue!(self.stdout, cursor::MoveTo(12, y))?;
-            for col in 0..BYTES_PER_ROW {
-                let offset = self.editor.offset + row * BYTES_PER_ROW + col;
+                    // the callback may ask for more input or open a panel
+                    self.screen_mode = self.mode_after_prompt();
 
-                if offset >= self.editor.file_size() {
-                    write!(self.stdout, "   ")?;
-                } else {
-                    let c = buf[row * BYTES_PER_ROW + col];
+                    let callback = self.input_callback.take();
+                    if let Some(mut callback) = callback {
+                        callback(self, &command);
+                    }
 
-                    let match_len = self
-                        .search_results
+                    self.draw()?;
+                }
+                _ => {}
+            },
+            ScreenMode::PanelMode => match event.code {
+                KeyCode::Up => {
+                    self.move_panel_selection(-1)?;
+                }
+                KeyCode::Down => {
+                    self.move_panel_selection(1)?;
+                }
+                KeyCode::PageUp => {
+                    self.move_panel_selection(-(self.height as isize - 4))?;
+                }
+                KeyCode::PageDown => {
+                    self.move_panel_selection(self.height as isize - 4)?;
+                }
+                KeyCode::Home => {
+                    self.move_panel_selection(isize::MIN)?;
+                }
+                KeyCode::End => {
+                    self.move_panel_selection(isize::MAX)?;
+                }
+                KeyCode::Left => {
+                    self.fold_selected_node(true)?;
+                }
+                KeyCode::Right => {
+                    self.fold_selected_node(false)?;
+                }
+                KeyCode::Enter => {
+                    let offset = self
+                        .panel
                         .as_ref()
-                        .and_then(|res| res.match_len(offset));
-                    let highlight = self.config.highlight_colors.is_some() && match_len.is_some();
-
-                    if highlight {
-                        queue!(
-                            self.stdout,
-                            style::SetColors(self.config.highlight_colors.unwrap())
-                        )?;
-                        if col == BYTES_PER_ROW - 1 || match_len.unwrap() == 1 {
-                            write!(self.stdout, "{:02x}", c)?;
-                            queue!(self.stdout, style::ResetColor)?;
-
-                            if col == 7 {
-                                write!(self.stdout, "  ")?;
-                            } else {
-                                write!(self.stdout, " ")?;
-                            }
-                        } else {
-                            write!(self.stdout, "{:02x} ", c)?;
-
-                            if col == 7 {
-                                write!(self.stdout, " ")?;
-                            }
-
-                            queue!(self.stdout, style::ResetColor)?;
-                        }
-                    } else {
-                        write!(self.stdout, "{:02x} ", c)?;
+                        .and_then(|panel| panel.selected().and_then(|entry| entry.offset));
+                    let extent = self.selected_extent();
 
-                        if col == 7 {
-                            write!(self.stdout, " ")?;
+                    // continue with n and N from the chosen match
+                    if let (Some(panel), Some(results)) = (&self.panel, &mut self.search_results) {
+                        if panel.kind == PanelKind::SearchResults {
+                            results.select(panel.selected_idx());
                         }
                     }
-                };
-            }
-        }
-
-        queue!(self.stdout, cursor::MoveTo(1, 0))?;
-        queue!(
-            self.stdout,
-            terminal::Clear(terminal::ClearType::CurrentLine)
-        )?;
 
-        let mode = self.editor_mode.name();
-        queue!(
-            self.stdout,
-            cursor::MoveTo((SCREEN_WIDTH - mode.len()).try_into().unwrap(), 0)
-        )?;
-        write!(self.stdout, "{}", mode)?;
-
-        queue!(self.stdout, cursor::MoveTo(0, self.height as u16 - 1))?;
-        queue!(
-            self.stdout,
-            terminal::Clear(terminal::ClearType::CurrentLine)
-        )?;
-
-        match self.screen_mode {
-            ScreenMode::EditMode => {
-                write!(
-                    self.stdout,
-                    "[{}] {}",
-                    self.editor.filename,
-                    ByteSize::b(self.editor.file_size() as u64)
-                )?;
+                    if self.pending_replace.is_some() {
+                        self.close_panel()?;
+                        self.replace_all();
+                        self.draw()?;
+                    } else if let Some(offset) = offset {
+                        self.close_panel()?;
+                        self.jump_to(offset);
+
+                        // select the whole field or annotation, with the cursor at its start
+                        if let Some(extent) = extent {
+                            self.selection_anchor = Some(extent.end - 1);
+                        }
 
-                if !self.editor.saved {
-                    write!(self.stdout, " [+]")?;
+                        self.draw()?;
+                    }
                 }
-
-                if let Some(search_results) = &self.search_results {
-                    write!(
-                        self.stdout,
-                        " [{}/{}]",
-                        search_results.idx() + 1,
-                        search_results.len()
-                    )?;
+                KeyCode::Char('d') => {
+                    self.delete_panel_entry()?;
                 }
-            }
-            ScreenMode::CommandMode => {
-                let prefix_width = self.input_prefix.width() + 1;
-                let mut i = 0;
-                let chars = self.input_buffer.chars();
-
-                // find the character index where the width of the end of the text is small enough to be shown on screen
-                while chars
-                    .clone()
-                    .skip(i)
-                    .map(|c| c.width().unwrap())
-                    .sum::<usize>()
-                    + prefix_width
-                    > self.width
+                KeyCode::Esc | KeyCode::Char('q') => {
+                    self.pending_replace = None;
+                    self.close_panel()?;
+                }
+                KeyCode::Char('S')
+                    if self
+                        .panel
+                        .as_ref()
+                        .is_some_and(|panel| panel.kind == PanelKind::SearchResults) =>
                 {
-                    i += 1;
+                    self.close_panel()?;
+                }
+                KeyCode::Char('T')
+                    if self
+                        .panel
+                        .as_ref()
+                        .is_some_and(|panel| panel.kind == PanelKind::Strings) =>
+                {
+                    self.close_panel()?;
+                }
+                KeyCode::Char('t')
+                    if self
+                        .panel
+                        .as_ref()
+                        .is_some_and(|panel| panel.kind == PanelKind::Structure) =>
+                {
+                    self.close_panel()?;
+                }
+                KeyCode::Char('/')
+                    if self
+                        .panel
+                        .as_ref()
+                        .is_some_and(|panel| panel.kind == PanelKind::Bookmarks) =>
+                {
+                    self.read_user_input(
+                        String::from("filter "),
+                        None,
+                        Box::new(|screen: &mut Screen, input: &str| {
+                            screen.marks_filter = input.trim().to_owned();
+                            let panel = screen.bookmarks_panel();
+                            screen.show_panel(panel);
+                        }),
+                    )?;
                 }
+                _ => {}
+            },
+        }
 
-                let start = self
-                    .input_buffer
-                    .char_indices()
-                    .nth(i)
-                    .unwrap_or((0, '\x00'))
-                    .0;
+        Ok(())
+    }
 
-                write!(
-                    self.stdout,
-                    "{}{}",
-                    self.input_prefix,
-                    &self.input_buffer[start..]
-                )?;
+    /// Returns whether typing `c` writes to the file in the current editor mode instead of running the action bound
+    /// to it.
+    fn writes_byte(&self, c: char) -> bool {
+        match self.editor_mode {
+            // uppercase digits only write if the keymap doesn't bind them to anything
+            EditorMode::HexMode if c.is_ascii_uppercase() => {
+                hex_char_to_u8(c).is_some()
+                    && self
+                        .config
+                        .keymap
+                        .get(&KeyEvent::from(KeyCode::Char(c)))
+                        .is_none()
             }
+            EditorMode::HexMode => hex_char_to_u8(c).is_some(),
+            EditorMode::TextMode => !c.is_control(),
         }
+    }
 
-        self.draw_cursor()?;
+    /// Overwrites the bytes at the cursor with the UTF-8 encoding of `c` as a single change.
+    fn write_char(&mut self, c: char) -> Result<(), io::Error> {
+        let position = self.editor.cursor_nibble / 2;
+        let mut bytes = [0; 4];
+        let bytes = c.encode_utf8(&mut bytes).as_bytes();
 
-        self.stdout.flush()?;
+        if self
+            .editor
+            .apply_writes(&[(position, bytes.to_vec())])
+            .is_err()
+        {
+            self.message = Some(format!(
+                "{} needs {:#x} bytes, which don't fit before the end of the file",
+                c,
+                bytes.len()
+            ));
+            return self.draw();
+        }
 
-        Ok(())
-    }
+        self.editor.cursor_nibble += 2 * bytes.len();
 
-    pub fn cycle_editor_mode(&mut self) -> Result<(), io::Error> {
-        self.set_editor_mode(self.editor_mode.next())
+        self.draw()
     }
 
-    pub fn set_editor_mode(&mut self, editor_mode: EditorMode) -> Result<(), io::Error> {
-        if self.editor_mode != editor_mode {
-            self.editor_mode = editor_mode;
-            self.editor.cursor_nibble -= self.editor.cursor_nibble % 2;
-            self.draw()
-        } else {
-            Ok(())
+    /// Handles text pasted into the terminal. In text mode it is written to the file as is, and in hex mode it is read
+    /// as hex digits, in both cases as a single change that stops at the end of the file.
+    fn paste(&mut self, text: &str) -> Result<(), io::Error> {
+        self.message = None;
+
+        if matches!(self.screen_mode, ScreenMode::CommandMode) {
+            self.input_buffer
+                .extend(text.chars().filter(|c| !c.is_control()));
+            return self.draw();
         }
-    }
 
-    fn move_cursor(&mut self, movement: CursorMovementType) -> Result<(), io::Error> {
-        let xmov = match self.editor_mode {
-            EditorMode::HexMode => 1,
-            EditorMode::TextMode => 2,
+        let size = self.editor.file_size();
+        let start = self.editor.cursor_nibble;
+
+        // the nibbles written from the cursor, which is on a nibble in hex mode and on a byte in text mode
+        let nibbles: Vec<u8> = match self.editor_mode {
+            EditorMode::HexMode => {
+                let Some(nibbles) = text
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .map(hex_char_to_u8)
+                    .collect()
+                else {
+                    self.message = Some(String::from("pasted text isn't hex"));
+                    return self.draw();
+                };
+                nibbles
+            }
+            EditorMode::TextMode => text
+                .bytes()
+                .flat_map(|byte| [byte >> 4, byte & 0x0f])
+                .collect(),
         };
+        let end = cmp::min(start + nibbles.len(), 2 * size);
 
-        let ymov = 2 * BYTES_PER_ROW;
+        let mut bytes = self.editor.buffer[start / 2..end.div_ceil(2)].to_vec();
+        for (nibble, &value) in (start..end).zip(&nibbles) {
+            let byte = &mut bytes[nibble / 2 - start / 2];
 
-        match movement {
-            CursorMovementType::Right => {
-                self.editor.cursor_nibble += xmov;
-            }
-            CursorMovementType::Left => {
-                self.editor.cursor_nibble = self.editor.cursor_nibble.saturating_sub(xmov);
-            }
-            CursorMovementType::Up => {
-                self.editor.cursor_nibble = self.editor.cursor_nibble.saturating_sub(ymov);
-            }
-            CursorMovementType::Down => {
-                self.editor.cursor_nibble += ymov;
-            }
-            CursorMovementType::PageUp => {
-                self.editor.cursor_nibble = self
-                    .editor
-                    .cursor_nibble
-                    .saturating_sub(ymov * (self.height - 4));
-                self.editor.offset = self
-                    .editor
-                    .offset
-                    .saturating_sub((self.height - 4) * BYTES_PER_ROW);
-            }
-            CursorMovementType::PageDown => {
-                self.editor.cursor_nibble += ymov * (self.height - 4);
-                self.editor.offset += (self.height - 4) * BYTES_PER_ROW;
-            }
+            *byte = match nibble % 2 {
+                0 => (*byte & 0x0f) | (value << 4),
+                _ => (*byte & 0xf0) | value,
+            };
         }
 
-        self.draw()?;
+        if let Err(e) = self.editor.apply_writes(&[(start / 2, bytes)]) {
+            self.message = Some(e.to_string());
+            return self.draw();
+        }
 
-        Ok(())
-    }
+        self.editor.cursor_nibble = cmp::min(end, (2 * size).saturating_sub(1));
+        if end - start < nibbles.len() {
+            self.message = Some(String::from("paste stopped at the end of the file"));
+        }
 
-    fn draw_cursor(&mut self) -> Result<(), io::Error> {
-        let (x, y) = match self.screen_mode {
-            ScreenMode::EditMode => self.coords_for_cursor(),
-            ScreenMode::CommandMode => (
-                self.input_prefix.width() + self.input_buffer.width(),
-                self.height - 1,
-            ),
-        };
-        queue!(
-            self.stdout,
-            cursor::MoveTo(x.try_into().unwrap(), y.try_into().unwrap())
-        )
+        self.draw()
     }
 
-    fn coords_for_cursor(&self) -> (usize, usize) {
-        let nibble_wo_offset = self.editor.cursor_nibble - 2 * self.editor.offset;
-
-        match self.editor_mode {
-            EditorMode::HexMode => {
-                let mut x =
-                    12 + (nibble_wo_offset % (2 * BYTES_PER_ROW)) / 2 * 3 + nibble_wo_offset % 2;
-                let y = 3 + nibble_wo_offset / (2 * BYTES_PER_ROW);
+    fn run_action(&mut self, action: Action) -> Result<(), io::Error> {
+        match action {
+            Action::MoveLeft => self.move_cursor(CursorMovementType::Left)?,
+            Action::MoveRight => self.move_cursor(CursorMovementType::Right)?,
+            Action::MoveUp => self.move_cursor(CursorMovementType::Up)?,
+            Action::MoveDown => self.move_cursor(CursorMovementType::Down)?,
+            Action::PageUp => self.move_cursor(CursorMovementType::PageUp)?,
+            Action::PageDown => self.move_cursor(CursorMovementType::PageDown)?,
+            Action::RowStart => self.move_cursor(CursorMovementType::RowStart)?,
+            Action::RowEnd => self.move_cursor(CursorMovementType::RowEnd)?,
+            Action::ScrollUp => self.move_cursor(CursorMovementType::ScrollUp)?,
+            Action::ScrollDown => self.move_cursor(CursorMovementType::ScrollDown)?,
+            Action::GroupLeft => self.move_cursor(CursorMovementType::GroupLeft)?,
+            Action::GroupRight => self.move_cursor(CursorMovementType::GroupRight)?,
+            Action::RecordUp => self.move_cursor(CursorMovementType::RecordUp)?,
+            Action::RecordDown => self.move_cursor(CursorMovementType::RecordDown)?,
+            Action::FileStart => self.move_cursor(CursorMovementType::FileStart)?,
+            Action::FileEnd => self.move_cursor(CursorMovementType::FileEnd)?,
+            Action::JumpBack => {
+                let cursor = self.editor.cursor_nibble / 2;
+
+                if let Some(position) = self.jumps.back(cursor) {
+                    self.editor.cursor_nibble = 2 * position;
+                    self.draw()?;
+                }
+            }
+            Action::JumpForward => {
+                if let Some(position) = self.jumps.forward() {
+                    self.editor.cursor_nibble = 2 * position;
+                    self.draw()?;
+                }
+            }
+            Action::ToggleMode => self.cycle_editor_mode()?,
+            Action::NormalMode if self.search_job.is_some() => {
+                self.search_job = None;
+                self.message = Some(String::from("search cancelled"));
+                self.draw()?;
+            }
+            Action::NormalMode if self.selection_anchor.is_some() => {
+                self.selection_anchor = None;
+                self.draw()?;
+            }
+            Action::NormalMode => self.set_editor_mode(EditorMode::HexMode)?,
+            Action::Undo => {
+                if self.editor.undo() {
+                    self.draw()?;
+                }
+            }
+            Action::Redo => {
+                if self.editor.redo() {
+                    self.draw()?;
+                }
+            }
+            Action::Save => {
+                self.save(false);
+                self.draw()?;
+            }
+            Action::Quit => {
+                if self.editor.saved {
+                    self.running = false;
+                } else {
+                    self.read_user_input(
+                        String::from("quit without saving? "),
+                        None,
+                        Box::new(|screen: &mut Screen, input: &str| {
+                            if input.eq_ignore_ascii_case("yes") || input.eq_ignore_ascii_case("y")
+                            {
+                                screen.running = false;
+                            }
+                        }),
+                    )?;
+                }
+            }
+            Action::Command => {
+                self.read_user_input(
+                    String::from(":"),
+                    Some(HistoryKind::Command),
+                    Box::new(|screen: &mut Screen, input: &str| {
+                        screen.run_command(input);
+                    }),
+                )?;
+                self.input_completer = Some(command::complete);
+            }
+            Action::Goto => {
+                self.read_user_input(
+                    String::from("j "),
+                    Some(HistoryKind::Goto),
+                    Box::new(|screen: &mut Screen, input: &str| {
+                        screen.goto(input);
+                    }),
+                )?;
+            }
+            Action::Search | Action::SearchBackward => {
+                let (prefix, direction) = if action == Action::Search {
+                    ("/", Direction::Forward)
+                } else {
+                    ("?", Direction::Backward)
+                };
+                self.search_results = None;
+
+                self.read_user_input(
+                    String::from(prefix),
+                    Some(HistoryKind::Search),
+                    Box::new(move |screen: &mut Screen, input: &str| {
+                        screen.search(input, direction);
+                    }),
+                )?;
+            }
+            Action::NextMatch => {
+                self.next_match(true);
+                self.draw()?;
+            }
+            Action::PrevMatch => {
+                self.next_match(false);
+                self.draw()?;
+            }
+            Action::CountMatches => {
+                self.read_user_input(
+                    String::from("count "),
+                    Some(HistoryKind::Search),
+                    Box::new(|screen: &mut Screen, input: &str| {
+                        screen.count_matches(input);
+                    }),
+                )?;
+            }
+            Action::SearchMany => {
+                self.read_user_input(
+                    String::from("patterns "),
+                    Some(HistoryKind::Search),
+                    Box::new(|screen: &mut Screen, input: &str| {
+                        screen.search_many(input);
+                    }),
+                )?;
+            }
+            Action::Replace => {
+                self.read_user_input(
+                    String::from("replace "),
+                    Some(HistoryKind::Search),
+                    Box::new(|screen: &mut Screen, input: &str| {
+                        screen.read_replacement(input);
+                    }),
+                )?;
+            }
+            Action::SearchResults => self.open_search_results_panel()?,
+            Action::HighlightResults => {
+                self.highlight_search_results();
+                self.draw()?;
+            }
+            Action::ListHighlights => self.open_highlights_panel()?,
+            Action::ExportResults => {
+                if self.search_results.is_some() {
+                    self.read_user_input(
+                        String::from("export results to "),
+                        None,
+                        Box::new(|screen: &mut Screen, input: &str| {
+                            screen.export_search_results(input);
+                        }),
+                    )?;
+                } else {
+                    self.message = Some(String::from("no search results"));
+                    self.draw()?;
+                }
+            }
+            Action::Bookmark => {
+                self.read_user_input(
+                    String::from("bookmark "),
+                    None,
+                    Box::new(|screen: &mut Screen, input: &str| {
+                        let name = input.trim();
+
+                        if !name.is_empty() {
+                            let cursor = screen.editor.cursor_nibble / 2;
+                            screen.bookmarks.set(name, cursor);
+                            screen.save_bookmarks();
+                        }
+                    }),
+                )?;
+            }
+            Action::ListBookmarks => self.open_bookmarks_panel()?,
+            Action::Annotate => {
+                self.read_user_input(
+                    String::from("annotation "),
+                    None,
+                    Box::new(|screen: &mut Screen, input: &str| screen.annotate(input)),
+                )?;
+            }
+            Action::Inspect => self.open_inspector_panel()?,
+            Action::ListStrings => self.open_strings_panel()?,
+            Action::ListFields => match self.structure_panel() {
+                Some(panel) => self.open_panel(panel)?,
+                None => {
+                    self.message = Some(String::from("no structure applied"));
+                    self.draw()?;
+                }
+            },
+            Action::FollowPointer => {
+                self.follow_pointer();
+                self.draw()?;
+            }
+            Action::ToggleSelection => {
+                self.selection_anchor = match self.selection_anchor {
+                    Some(_) => None,
+                    None => Some(self.editor.cursor_nibble / 2),
+                };
+                self.draw()?;
+            }
+            Action::ZeroBytes => {
+                match self.selection() {
+                    Some(selection) => {
+                        if let Err(e) = self
+                            .editor
+                            .replace_all(&[selection.start], &vec![0; selection.len()])
+                        {
+                            self.message = Some(e.to_string());
+                        }
+                        self.selection_anchor = None;
+                    }
+                    None => {
+                        // moving on to the next byte lets holding the key zero a run of bytes
+                        let position = self.editor.cursor_nibble / 2;
+
+                        match self.editor.apply_writes(&[(position, vec![0])]) {
+                            Ok(()) => self.editor.cursor_nibble = 2 * (position + 1),
+                            Err(e) => self.message = Some(e.to_string()),
+                        }
+                    }
+                }
+
+                self.draw()?;
+            }
+            Action::Suspend => {
+                #[cfg(unix)]
+                suspend::suspend()?;
+
+                #[cfg(not(unix))]
+                {
+                    self.message =
+                        Some(String::from("suspending isn't supported on this platform"));
+                    self.draw()?;
+                }
+            }
+            Action::ToggleCollapse => {
+                self.collapse_runs = !self.collapse_runs;
+                self.draw()?;
+            }
+            Action::SetMark => self.pending_key = Some('m'),
+            Action::JumpToMark => self.pending_key = Some('\''),
+            Action::NextPrefix => self.pending_key = Some(']'),
+            Action::PrevPrefix => self.pending_key = Some('['),
+            Action::ScrollPrefix => self.pending_key = Some('z'),
+            Action::RecordMacro => match self.recording.take() {
+                Some((register, mut keys)) => {
+                    // the key that stopped the recording
+                    keys.pop();
+                    self.macros.insert(register, keys);
+                    self.draw()?;
+                }
+                None => self.pending_key = Some('Q'),
+            },
+            Action::PlayMacro => self.pending_key = Some('@'),
+            Action::None => {}
+        }
+
+        Ok(())
+    }
+
+    fn handle_pending_key(&mut self, pending_key: char, c: char) -> Result<(), io::Error> {
+        match pending_key {
+            'Q' if c.is_ascii_alphabetic() => {
+                self.recording = Some((c, Vec::new()));
+            }
+            '@' if c == '@' => match self.last_macro {
+                Some(register) => self.play_macro(register, 1)?,
+                None => self.message = Some(String::from("no macro played yet")),
+            },
+            '@' if c.is_ascii_alphabetic() => self.play_macro(c, 1)?,
+            'm' if c.is_ascii_alphabetic() => {
+                self.marks.insert(c, self.editor.cursor_nibble / 2);
+            }
+            '\'' if c.is_ascii_alphabetic() => match self.marks.get(&c) {
+                Some(&position) => self.jump_to(position),
+                None => self.message = Some(format!("mark {} not set", c)),
+            },
+            'z' => {
+                let visible_rows = self.height - 4;
+                let rows_above = match c {
+                    'z' => (visible_rows - 1) / 2,
+                    't' => self.scroll_margin(visible_rows),
+                    'b' => visible_rows - 1 - self.scroll_margin(visible_rows),
+                    _ => return self.draw(),
+                };
+
+                self.scroll_cursor_row(rows_above);
+            }
+            ']' | '[' => {
+                let cursor = self.editor.cursor_nibble / 2;
+                let forward = pending_key == ']';
+
+                let (position, what) = match c {
+                    'c' if forward => (self.editor.next_modified(cursor), "modified bytes"),
+                    'c' => (self.editor.prev_modified(cursor), "modified bytes"),
+                    '0' | 'f' => {
+                        let fill = if c == '0' { 0x00 } else { 0xff };
+                        let position = if forward {
+                            scan::next_data(&self.editor.buffer, cursor, fill)
+                        } else {
+                            scan::prev_data(&self.editor.buffer, cursor, fill)
+                        };
+
+                        (position, "data")
+                    }
+                    'a' => {
+                        let align = self.config.alignment;
+                        let position = if forward {
+                            Some((cursor / align + 1) * align)
+                                .filter(|&position| position < self.editor.file_size())
+                        } else if cursor.is_multiple_of(align) {
+                            cursor.checked_sub(align)
+                        } else {
+                            Some(cursor - cursor % align)
+                        };
+
+                        (position, "aligned offsets")
+                    }
+                    's' if forward => (
+                        scan::next_string(&self.editor.buffer, cursor, self.config.string_length),
+                        "strings",
+                    ),
+                    's' => (
+                        scan::prev_string(&self.editor.buffer, cursor, self.config.string_length),
+                        "strings",
+                    ),
+                    _ => return self.draw(),
+                };
+
+                match position {
+                    Some(position) => self.editor.cursor_nibble = 2 * position,
+                    None => self.message = Some(format!("no more {}", what)),
+                }
+            }
+            _ => {}
+        }
+
+        self.draw()
+    }
+
+    /// Plays the keys recorded into `register` `count` times, without drawing the result.
+    fn play_macro(&mut self, register: char, count: usize) -> Result<(), io::Error> {
+        let Some(keys) = self.macros.get(&register).cloned() else {
+            self.message = Some(format!("macro {} not recorded", register));
+            return Ok(());
+        };
+
+        // a macro that plays itself would never end
+        if self.macro_depth >= MAX_MACRO_DEPTH {
+            return Ok(());
+        }
+
+        self.last_macro = Some(register);
+        self.macro_depth += 1;
+
+        'repeat: for _ in 0..count {
+            for &key in &keys {
+                self.handle_key(key)?;
+
+                // the next keys may depend on where the search moves the cursor
+                while self.search_job.is_some() {
+                    thread::sleep(Duration::from_millis(1));
+                    self.receive_search_results()?;
+                }
+
+                if !self.running {
+                    break 'repeat;
+                }
+            }
+        }
+
+        self.macro_depth -= 1;
+
+        Ok(())
+    }
+
+    fn read_user_input(
+        &mut self,
+        prefix: String,
+        history: Option<HistoryKind>,
+        callback: InputReadCallback,
+    ) -> Result<(), io::Error> {
+        self.prompt(prefix, history, callback);
+        self.draw()
+    }
+
+    /// Returns the mode to go back to when a prompt is closed, which is the panel mode if it was opened from a panel.
+    fn mode_after_prompt(&self) -> ScreenMode {
+        match self.panel {
+            Some(_) => ScreenMode::PanelMode,
+            None => ScreenMode::EditMode,
+        }
+    }
+
+    /// Like `read_user_input`, but without drawing, for input callbacks that ask for more input.
+    fn prompt(
+        &mut self,
+        prefix: String,
+        history: Option<HistoryKind>,
+        callback: InputReadCallback,
+    ) {
+        self.screen_mode = ScreenMode::CommandMode;
+        self.input_callback = Some(callback);
+        self.input_prefix = prefix;
+        self.input_history = history;
+        self.history_index = None;
+        self.history_search = None;
+        self.input_completer = None;
+    }
+
+    fn history_mut(&mut self, kind: HistoryKind) -> &mut History {
+        match kind {
+            HistoryKind::Search => &mut self.search_history,
+            HistoryKind::Goto => &mut self.goto_history,
+            HistoryKind::Command => &mut self.command_history,
+        }
+    }
+
+    /// Replaces the prompt input with the history entry `delta` entries newer than the one shown, going back to what
+    /// the user typed after the newest entry.
+    fn browse_history(&mut self, delta: isize) -> Result<(), io::Error> {
+        let Some(kind) = self.input_history else {
+            return Ok(());
+        };
+
+        let len = self.history_mut(kind).len();
+        let current = self.history_index.unwrap_or(len);
+        let index = current.saturating_add_signed(delta).min(len);
+
+        if index == current {
+            return Ok(());
+        }
+
+        if self.history_index.is_none() {
+            self.history_draft = mem::take(&mut self.input_buffer);
+        }
+
+        if index == len {
+            self.history_index = None;
+            self.input_buffer = mem::take(&mut self.history_draft);
+        } else {
+            self.history_index = Some(index);
+            self.input_buffer = self
+                .history_mut(kind)
+                .get(index)
+                .unwrap_or_default()
+                .to_owned();
+        }
+
+        self.draw()
+    }
+
+    /// Handles a key pressed while searching the history of the prompt, returning whether it was used by the search.
+    /// Any other key ends the search, leaving the entry that was found in the prompt.
+    fn search_history_key(&mut self, event: KeyEvent) -> Result<bool, io::Error> {
+        let Some(query) = &mut self.history_search else {
+            return Ok(false);
+        };
+        // keep showing the current entry as long as it matches, unless the next one is asked for
+        let mut before = self.history_index.map_or(usize::MAX, |index| index + 1);
+
+        match event.code {
+            KeyCode::Char('r') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                before = self.history_index.unwrap_or(usize::MAX);
+            }
+            KeyCode::Char(c)
+                if !event
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                query.push(c);
+            }
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Esc => {
+                self.history_search = None;
+                self.draw()?;
+                return Ok(true);
+            }
+            _ => {
+                self.history_search = None;
+                return Ok(false);
+            }
+        }
+
+        let query = query.clone();
+        let Some(kind) = self.input_history else {
+            return Ok(true);
+        };
+
+        if let Some(index) = self.history_mut(kind).find(&query, before) {
+            if self.history_index.is_none() {
+                self.history_draft = mem::take(&mut self.input_buffer);
+            }
+
+            self.history_index = Some(index);
+            self.input_buffer = self
+                .history_mut(kind)
+                .get(index)
+                .unwrap_or_default()
+                .to_owned();
+        }
+
+        self.draw()?;
+
+        Ok(true)
+    }
+
+    fn draw(&mut self) -> Result<(), io::Error> {
+        if self.macro_depth > 0 {
+            return Ok(());
+        }
+
+        self.frame.clear();
+
+        if let Some(job) = self.load_job.as_ref().filter(|_| !self.opened) {
+            let (read, size) = job.progress();
+            let mut message = format!(
+                "loading {}... {}",
+                self.editor.filename,
+                ByteSize::b(read as u64)
+            );
+
+            if let Some(size) = size {
+                message += &format!(" of {}", ByteSize::b(size));
+            }
+
+            return self.draw_centered(message);
+        }
+
+        let layout = match self.layout {
+            Some(layout) if self.height >= MIN_HEIGHT => layout,
+            _ => {
+                let message = format!(
+                    "terminal too small (need ≥ {}x{})",
+                    Layout::min_width(self.config.group_size),
+                    MIN_HEIGHT
+                );
+
+                return self.draw_centered(message);
+            }
+        };
+        let cursor_y = match self.screen_mode {
+            ScreenMode::PanelMode => self.draw_panel()?,
+            // a prompt opened from a panel, like the filter of the bookmarks
+            ScreenMode::CommandMode if self.panel.is_some() => self.draw_panel()?,
+            _ => self.draw_grid(layout)?,
+        };
+
+        self.line.clear();
+        if let Some((register, _)) = self.recording {
+            let _ = write!(self.line, "recording @{} ", register);
+        }
+        self.line.push_str(self.editor_mode.name());
+
+        self.frame
+            .move_to(layout.width().saturating_sub(self.line.len()), 0);
+        self.frame.print(&self.line);
+
+        self.frame.move_to(0, self.height - 1);
+
+        match self.screen_mode {
+            ScreenMode::EditMode | ScreenMode::PanelMode if self.message.is_some() => {
+                self.line.clear();
+                self.line.push_str(self.message.as_ref().unwrap());
+                truncate_to_width(&mut self.line, self.width - 1);
+                self.frame.print(&self.line);
+            }
+            ScreenMode::EditMode | ScreenMode::PanelMode => {
+                let size = self.editor.file_size();
+                if self.size_label.0 != size {
+                    self.size_label = (size, ByteSize::b(size as u64).to_string());
+                }
+
+                let selection = self.selection();
+                let status = &mut self.line;
+                status.clear();
+                let _ = write!(status, "[{}] {}", self.editor.filename, self.size_label.1);
+
+                if let Some(file_type) = self.file_type {
+                    let _ = write!(status, " {}", file_type);
+                }
+
+                if !self.editor.saved {
+                    status.push_str(" [+]");
+                }
+
+                if let Some(search_results) = &self.search_results {
+                    let _ = write!(
+                        status,
+                        " [{}/{}]",
+                        search_results.idx() + 1,
+                        search_results.len()
+                    );
+                }
+
+                if let Some(selection) = selection {
+                    let _ = write!(status, " [{:#x} bytes selected]", selection.len());
+                }
+
+                if self.search_job.is_some() {
+                    status.push_str(" searching...");
+                }
+
+                if let Some(i) = self.bookmarks.annotation_at(self.editor.cursor_nibble / 2) {
+                    let _ = write!(status, " \"{}\"", self.bookmarks.annotations()[i].comment);
+                }
+
+                if let Some(field) = self
+                    .structure
+                    .as_ref()
+                    .and_then(|structure| structure.field_at(self.editor.cursor_nibble / 2))
+                {
+                    let _ = write!(
+                        status,
+                        " {} = {}",
+                        field.path,
+                        field.value.as_ref().unwrap()
+                    );
+                }
+
+                // the field value can be long, and the terminal would scroll if the last line wrapped
+                truncate_to_width(status, self.width - 1);
+                self.frame.print(status);
+            }
+            ScreenMode::CommandMode => {
+                // the prefix borrows the screen, so the line is taken out of it while it is built
+                let mut line = mem::take(&mut self.line);
+                let prefix = self.prompt_prefix();
+                let prefix_width = prefix.width() + 1;
+                let mut i = 0;
+                let chars = self.input_buffer.chars();
+
+                // find the character index where the width of the end of the text is small enough to be shown on screen
+                while chars
+                    .clone()
+                    .skip(i)
+                    .map(|c| c.width().unwrap())
+                    .sum::<usize>()
+                    + prefix_width
+                    > self.width
+                {
+                    i += 1;
+                }
+
+                let start = self
+                    .input_buffer
+                    .char_indices()
+                    .nth(i)
+                    .unwrap_or((0, '\x00'))
+                    .0;
+
+                line.clear();
+                line.push_str(&prefix);
+                line.push_str(&self.input_buffer[start..]);
+                self.frame.print(&line);
+                self.line = line;
+            }
+        }
+
+        let cursor = self.cursor_position(layout, cursor_y);
+        self.present(cursor)
+    }
+
+    /// Shows the frame drawn since the last one on the terminal, with the terminal cursor at `cursor`.
+    fn present(&mut self, cursor: (usize, usize)) -> Result<(), io::Error> {
+        self.output.clear();
+        self.frame
+            .present(&mut self.output, self.presented.as_ref(), cursor)?;
+        self.stdout.write_all(&self.output)?;
+        self.stdout.flush()?;
+
+        // the frame presented before is drawn over next
+        match &mut self.presented {
+            Some(presented) => mem::swap(presented, &mut self.frame),
+            None => {
+                let frame = Frame::new(self.width, self.height);
+                self.presented = Some(mem::replace(&mut self.frame, frame));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn too_small(&self) -> bool {
+        self.layout.is_none() || self.height < MIN_HEIGHT
+    }
+
+    /// Replaces the editor with `message`, like one saying how large the terminal needs to be.
+    fn draw_centered(&mut self, mut message: String) -> Result<(), io::Error> {
+        self.damage.invalidate();
+        truncate_to_width(&mut message, self.width);
+        let x = self.width.saturating_sub(message.width()) / 2;
+
+        self.frame.move_to(x, self.height / 2);
+        write!(self.frame, "{}", message)?;
+
+        self.present((x + message.width(), self.height / 2))
+    }
+
+    /// Returns what is shown before the input of the prompt.
+    fn prompt_prefix(&self) -> Cow<'_, str> {
+        match &self.history_search {
+            Some(query) if self.input_buffer.contains(query.as_str()) => {
+                format!("(history `{}') ", query).into()
+            }
+            Some(query) => format!("(failed history `{}') ", query).into(),
+            None => self.input_prefix.as_str().into(),
+        }
+    }
+
+    /// Draws the hex and text panes, returning the screen row of the cursor.
+    fn draw_grid(&mut self, layout: Layout) -> Result<usize, io::Error> {
+        let bytes_per_row = layout.bytes_per_row;
+
+        self.frame.move_to(layout.hex_x, 1);
+        for col in 0..bytes_per_row {
+            self.frame.print_hex(col, 2);
+            self.frame.put(' ');
+
+            if layout.is_group_end(col) {
+                self.frame.put(' ');
+            }
+        }
+
+        // an empty file only has the cursor at 0, with nothing under it
+        self.editor.cursor_nibble = cmp::min(
+            self.editor.cursor_nibble,
+            (2 * self.editor.file_size()).saturating_sub(1),
+        );
+
+        self.update_runs();
+        self.scroll_to_cursor(self.height - 4);
+
+        let cursor_row = self.row_start(self.editor.cursor_nibble / 2);
+        let rows = self.height - 4;
+        let mut cursor_y = 3;
+        let mut row_offset = self.editor.offset;
+
+        // rows can only be copied from a frame the terminal is known to show
+        if self.presented.is_none() {
+            self.damage.invalidate();
+        }
+        self.damage.start(layout, rows);
+
+        for row in 0..rows {
+            let y = row + 3;
+
+            if row_offset == cursor_row {
+                cursor_y = y;
+            }
+
+            let run = self.collapsed_run(row_offset);
+            self.damage.clear_cells();
+
+            if run.is_none() {
+                for col in 0..cmp::min(
+                    bytes_per_row,
+                    self.editor.file_size().saturating_sub(row_offset),
+                ) {
+                    let offset = row_offset + col;
+                    let highlight = self.highlight_at(offset);
+
+                    self.damage.push_cell(Cell {
+                        byte: self.editor.buffer[offset],
+                        colors: highlight.map(|(colors, _)| colors),
+                        // the space after the last byte of a highlight isn't colored, nor the one at the end of the row
+                        joined: highlight.is_some_and(|(_, len)| len > 1)
+                            && col != bytes_per_row - 1,
+                    });
+                }
+            }
+
+            match (self.damage.update(row, row_offset, run), &self.presented) {
+                (false, Some(presented)) => self.frame.copy_row(presented, y),
+                _ => match run {
+                    Some(run) => self.draw_collapsed_row(layout, y, run)?,
+                    None => self.draw_row(layout, y, row_offset, row)?,
+                },
+            }
+
+            row_offset = self.next_row(row_offset);
+        }
+
+        Ok(cursor_y)
+    }
+
+    /// Draws the open panel in place of the hex view, returning the screen row of the selected entry.
+    fn draw_panel(&mut self) -> Result<usize, io::Error> {
+        self.damage.invalidate();
+
+        let rows = self.height - 4;
+        let width = self.width;
+        let panel = self.panel.as_mut().unwrap();
+        let scroll = panel.scroll(rows);
+        let selected = panel.selected_idx();
+
+        self.frame.move_to(1, 1);
+        self.frame.print(&panel.title);
+
+        for row in 0..rows {
+            self.frame.move_to(0, row + 3);
+
+            if let Some(entry) = panel.entries.get(scroll + row) {
+                let line = &mut self.line;
+                line.clear();
+                let _ = match entry.offset {
+                    Some(offset) => write!(line, " {:08x}   {}", offset, entry.text),
+                    None => write!(line, " {}", entry.text),
+                };
+                truncate_to_width(line, width);
+
+                self.frame.set_reverse(scroll + row == selected);
+                self.frame.print(line);
+                self.frame.set_reverse(false);
+            }
+        }
+
+        if panel.entries.is_empty() {
+            self.frame.move_to(1, 3);
+            self.frame.print("(empty)");
+        }
+
+        Ok(3 + selected - scroll)
+    }
+
+    fn open_panel(&mut self, panel: ListPanel) -> Result<(), io::Error> {
+        self.show_panel(panel);
+        self.draw()
+    }
+
+    /// Like `open_panel`, but without drawing, for input callbacks.
+    fn show_panel(&mut self, panel: ListPanel) {
+        self.panel = Some(panel);
+        self.screen_mode = ScreenMode::PanelMode;
+    }
+
+    fn close_panel(&mut self) -> Result<(), io::Error> {
+        self.panel = None;
+        self.screen_mode = ScreenMode::EditMode;
+        self.draw()
+    }
+
+    fn move_panel_selection(&mut self, delta: isize) -> Result<(), io::Error> {
+        if let Some(panel) = &mut self.panel {
+            panel.move_selection(delta);
+        }
+
+        self.draw()
+    }
+
+    fn delete_panel_entry(&mut self) -> Result<(), io::Error> {
+        let marks = self.marks();
+        let Some(panel) = &mut self.panel else {
+            return Ok(());
+        };
+
+        if panel.selected().is_some() {
+            match panel.kind {
+                PanelKind::Bookmarks => {
+                    match marks[panel.selected_idx()] {
+                        Mark::Bookmark(i) => self.bookmarks.remove(i),
+                        Mark::Annotation(i) => self.bookmarks.remove_annotation(i),
+                    }
+                    panel.remove_selected();
+                    self.save_bookmarks();
+                }
+                PanelKind::Highlights => {
+                    self.highlights.remove(panel.selected_idx());
+                    panel.remove_selected();
+                }
+                // leaves the occurrence out of the replacement
+                PanelKind::ReplacePreview => {
+                    if let Some(replacement) = &mut self.pending_replace {
+                        replacement.positions.remove(panel.selected_idx());
+                    }
+                    panel.remove_selected();
+                }
+                PanelKind::SearchResults
+                | PanelKind::Inspector
+                | PanelKind::Entropy
+                | PanelKind::Histogram
+                | PanelKind::Strings
+                | PanelKind::Signatures
+                | PanelKind::Structure
+                | PanelKind::Changes
+                | PanelKind::Streams
+                | PanelKind::Inflated
+                | PanelKind::Pointers => {}
+            }
+        }
+
+        self.draw()
+    }
+
+    fn open_bookmarks_panel(&mut self) -> Result<(), io::Error> {
+        self.marks_filter.clear();
+
+        let panel = self.bookmarks_panel();
+        self.open_panel(panel)
+    }
+
+    /// Returns the bookmarks and annotations whose name, comment or color contains the filter, ignoring case, sorted
+    /// by offset.
+    fn marks(&self) -> Vec<Mark> {
+        let filter = self.marks_filter.to_lowercase();
+        let matches = |text: &str| text.to_lowercase().contains(&filter);
+
+        let mut marks: Vec<(usize, Mark)> = self
+            .bookmarks
+            .list()
+            .iter()
+            .enumerate()
+            .filter(|(_, bookmark)| matches(&bookmark.name))
+            .map(|(i, bookmark)| (bookmark.offset, Mark::Bookmark(i)))
+            .chain(
+                self.bookmarks
+                    .annotations()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, annotation)| {
+                        matches(&annotation.comment) || matches(&annotation.color_name)
+                    })
+                    .map(|(i, annotation)| (annotation.range.start, Mark::Annotation(i))),
+            )
+            .collect();
+        marks.sort_by_key(|&(offset, _)| offset);
+
+        marks.into_iter().map(|(_, mark)| mark).collect()
+    }
+
+    /// Lists the bookmarks and the annotations, with the length and color of each annotation.
+    fn bookmarks_panel(&self) -> ListPanel {
+        let entries = self
+            .marks()
+            .into_iter()
+            .map(|mark| match mark {
+                Mark::Bookmark(i) => {
+                    let bookmark = &self.bookmarks.list()[i];
+
+                    PanelEntry {
+                        offset: Some(bookmark.offset),
+                        text: format!("{:<10} {:>10} {:<12} {}", "bookmark", "", "", bookmark.name),
+                    }
+                }
+                Mark::Annotation(i) => {
+                    let annotation = &self.bookmarks.annotations()[i];
+
+                    PanelEntry {
+                        offset: Some(annotation.range.start),
+                        text: format!(
+                            "{:<10} {:>10} {:<12} {}",
+                            "annotation",
+                            format!("{:#x}", annotation.range.len()),
+                            annotation.color_name,
+                            annotation.comment
+                        ),
+                    }
+                }
+            })
+            .collect();
+
+        let title = match self.marks_filter.as_str() {
+            "" => String::from("bookmarks and annotations"),
+            filter => format!("bookmarks and annotations matching `{}'", filter),
+        };
+
+        ListPanel::new(
+            PanelKind::Bookmarks,
+            format!("{} (enter: jump, /: filter, d: delete, esc: close)", title),
+            entries,
+        )
+    }
+
+    /// Returns the range of the structure field or annotation selected in a panel, which choosing it selects.
+    fn selected_extent(&self) -> Option<Range<usize>> {
+        let panel = self.panel.as_ref()?;
+
+        let range = match panel.kind {
+            PanelKind::Structure => {
+                let node = self.selected_node()?;
+                self.structure.as_ref()?.nodes[node].range.clone()
+            }
+            PanelKind::Bookmarks => match self.marks().get(panel.selected_idx())? {
+                Mark::Annotation(i) => self.bookmarks.annotations()[*i].range.clone(),
+                Mark::Bookmark(_) => return None,
+            },
+            _ => return None,
+        };
+
+        Some(range).filter(|range| !range.is_empty())
+    }
+
+    /// Reads what to replace the matches of `input` with, then asks for confirmation.
+    fn read_replacement(&mut self, input: &str) {
+        let Some(pattern) = Pattern::parse(input, self.config.endianness) else {
+            self.message = Some(String::from("invalid pattern"));
+            return;
+        };
+
+        self.prompt(
+            String::from("with "),
+            None,
+            Box::new(move |screen: &mut Screen, input: &str| {
+                screen.confirm_replace(&pattern, input);
+            }),
+        );
+    }
+
+    fn confirm_replace(&mut self, pattern: &Pattern, input: &str) {
+        let bytes = Pattern::parse(input, self.config.endianness)
+            .and_then(|replacement| replacement.exact_bytes().map(<[u8]>::to_vec))
+            .filter(|bytes| bytes.len() == pattern.len());
+
+        let Some(bytes) = bytes else {
+            self.message = Some(format!(
+                "the replacement must be {} bytes without wildcards",
+                pattern.len()
+            ));
+            return;
+        };
+
+        let positions = pattern.find_all(&self.editor.buffer);
+
+        if positions.is_empty() {
+            self.message = Some(String::from("pattern not found"));
+            return;
+        }
+
+        let count = positions.len();
+        self.pending_replace = Some(Replacement { positions, bytes });
+
+        self.prompt(
+            format!("replace {} occurrences? (y/n/p: preview) ", count),
+            None,
+            Box::new(|screen: &mut Screen, input: &str| match input {
+                "y" | "yes" => screen.replace_all(),
+                "p" => screen.show_replace_preview(),
+                _ => screen.pending_replace = None,
+            }),
+        );
+    }
+
+    fn show_replace_preview(&mut self) {
+        let Some(replacement) = &self.pending_replace else {
+            return;
+        };
+
+        let to_hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let new = to_hex(&replacement.bytes);
+
+        let entries = replacement
+            .positions
+            .iter()
+            .map(|&position| PanelEntry {
+                offset: Some(position),
+                text: format!(
+                    "{}  ->  {}",
+                    to_hex(&self.editor.buffer[position..position + replacement.bytes.len()]),
+                    new
+                ),
+            })
+            .collect();
+
+        self.show_panel(ListPanel::new(
+            PanelKind::ReplacePreview,
+            String::from("replace (enter: replace all, d: skip occurrence, esc: cancel)"),
+            entries,
+        ));
+    }
+
+    fn replace_all(&mut self) {
+        if let Some(replacement) = self.pending_replace.take() {
+            self.message = Some(
+                match self
+                    .editor
+                    .replace_all(&replacement.positions, &replacement.bytes)
+                {
+                    Ok(changed) => format!("replaced {} occurrences", changed),
+                    Err(e) => e.to_string(),
+                },
+            );
+        }
+    }
+
+    /// Keeps the current search results highlighted as a layer, so that they stay after the next search.
+    fn highlight_search_results(&mut self) {
+        let (Some(results), Some(name)) = (&self.search_results, &self.last_search) else {
+            self.message = Some(String::from("no search results"));
+            return;
+        };
+
+        let ranges = results
+            .results()
+            .iter()
+            .map(|&offset| offset..offset + results.match_size())
+            .collect();
+
+        self.highlights.add(name, ranges);
+        self.message = Some(format!("highlighted {} matches of {}", results.len(), name));
+    }
+
+    /// Lists the search results, with the bytes at the start of each match.
+    fn open_search_results_panel(&mut self) -> Result<(), io::Error> {
+        let Some(results) = &self.search_results else {
+            self.message = Some(String::from("no search results"));
+            return self.draw();
+        };
+
+        let buffer = &self.editor.buffer;
+        let entries = results
+            .results()
+            .iter()
+            .map(|&offset| {
+                let bytes = &buffer[offset..cmp::min(offset + RESULT_CONTEXT, buffer.len())];
+                let hex = bytes
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let text = bytes
+                    .iter()
+                    .map(|&b| match b {
+                        32..=126 => b as char,
+                        _ => self.config.replacement_char,
+                    })
+                    .collect::<String>();
+
+                PanelEntry {
+                    offset: Some(offset),
+                    text: format!("{:<w$}  {}", hex, text, w = RESULT_CONTEXT * 3 - 1),
+                }
+            })
+            .collect();
+
+        let mut panel = ListPanel::new(
+            PanelKind::SearchResults,
+            format!("{} search results (enter: jump, esc: close)", results.len()),
+            entries,
+        );
+        panel.move_selection(results.idx() as isize);
+
+        self.open_panel(panel)
+    }
+
+    fn open_highlights_panel(&mut self) -> Result<(), io::Error> {
+        let entries = self
+            .highlights
+            .list()
+            .iter()
+            .map(|layer| PanelEntry {
+                offset: layer.ranges().first().map(|range| range.start),
+                text: format!("{} ({} ranges)", layer.name, layer.ranges().len()),
+            })
+            .collect();
+
+        self.open_panel(ListPanel::new(
+            PanelKind::Highlights,
+            String::from("highlights (enter: jump, d: delete, esc: close)"),
+            entries,
+        ))
+    }
+
+    /// Lists the values that the bytes at the cursor decode to.
+    fn open_inspector_panel(&mut self) -> Result<(), io::Error> {
+        let cursor = self.editor.cursor_nibble / 2;
+        let bytes = &self.editor.buffer[cursor..];
+        let endianness = self.config.endianness;
+
+        let entries = inspector::DECODERS
+            .into_iter()
+            .chain(
+                self.plugins
+                    .decoders()
+                    .iter()
+                    .map(|decoder| decoder.as_ref()),
+            )
+            .filter_map(|decoder| {
+                decoder.decode(bytes, endianness).map(|value| PanelEntry {
+                    offset: None,
+                    text: format!("{:<8} {}", decoder.name(), value),
+                })
+            })
+            .collect();
+
+        let endianness = match endianness {
+            Endianness::Little => "little",
+            Endianness::Big => "big",
+        };
+
+        self.open_panel(ListPanel::new(
+            PanelKind::Inspector,
+            format!(
+                "values at {:#x}, {} endian (esc: close)",
+                cursor, endianness
+            ),
+            entries,
+        ))
+    }
+
+    /// Reads the structure definition `name` and applies it at `position`, or at the cursor, highlighting its fields.
+    fn apply_structure(&mut self, name: &str, position: Option<&str>) -> Result<(), String> {
+        let definition = structure::load(name)?;
+        let start = match position {
+            Some(position) => self.position(position, true)?,
+            None => self.editor.cursor_nibble / 2,
+        };
+
+        let structure = definition.apply(name, &self.editor.buffer, start, self.config.endianness);
+        self.highlights
+            .add_striped(STRUCTURE_LAYER, structure.field_ranges());
+
+        if let Some(e) = &structure.error {
+            self.message = Some(e.clone());
+        }
+
+        self.structure = Some(structure);
+        self.folded_nodes.clear();
+        Ok(())
+    }
+
+    /// Returns the indices of the nodes of the structure that aren't inside a folded one.
+    fn shown_nodes(&self) -> Vec<usize> {
+        let Some(structure) = &self.structure else {
+            return Vec::new();
+        };
+
+        let mut shown = Vec::new();
+        let mut folded_depth = None;
+
+        for (i, node) in structure.nodes.iter().enumerate() {
+            if folded_depth.is_some_and(|depth| node.depth > depth) {
+                continue;
+            }
+
+            shown.push(i);
+            folded_depth = self.folded_nodes.contains(&i).then_some(node.depth);
+        }
+
+        shown
+    }
+
+    /// Lists the fields of the structure applied last as a tree, indented by how deep they are nested, with their
+    /// values. Structures and arrays are marked with whether their fields are shown.
+    fn structure_panel(&self) -> Option<ListPanel> {
+        let structure = self.structure.as_ref()?;
+        let entries = self
+            .shown_nodes()
+            .into_iter()
+            .map(|i| {
+                let node = &structure.nodes[i];
+                let marker = match structure.has_children(i) {
+                    true if self.folded_nodes.contains(&i) => "▸ ",
+                    true => "▾ ",
+                    false => "  ",
+                };
+
+                PanelEntry {
+                    offset: Some(node.range.start),
+                    text: match &node.value {
+                        Some(value) => format!(
+                            "{:w$}{}{}: {}",
+                            "",
+                            marker,
+                            node.name,
+                            value,
+                            w = node.depth * 2
+                        ),
+                        None => format!("{:w$}{}{}", "", marker, node.name, w = node.depth * 2),
+                    },
+                }
+            })
+            .collect();
+
+        Some(ListPanel::new(
+            PanelKind::Structure,
+            format!(
+                "{} (enter: select, left/right: fold/unfold, esc: close)",
+                structure.name
+            ),
+            entries,
+        ))
+    }
+
+    /// Returns the index of the node selected in the structure panel.
+    fn selected_node(&self) -> Option<usize> {
+        let panel = self
+            .panel
+            .as_ref()
+            .filter(|panel| panel.kind == PanelKind::Structure)?;
+
+        self.shown_nodes().get(panel.selected_idx()).copied()
+    }
+
+    /// Hides the fields of the structure or array selected in the structure panel if `fold` is set, or shows them
+    /// otherwise. Folding a field that isn't an unfolded structure or array selects the one it is inside instead.
+    fn fold_selected_node(&mut self, fold: bool) -> Result<(), io::Error> {
+        let (Some(structure), Some(node)) = (&self.structure, self.selected_node()) else {
+            return Ok(());
+        };
+
+        let mut select = node;
+        if !structure.has_children(node) {
+            if fold {
+                select = structure.parent(node).unwrap_or(node);
+            }
+        } else if !fold {
+            self.folded_nodes.remove(&node);
+        } else if !self.folded_nodes.insert(node) {
+            select = structure.parent(node).unwrap_or(node);
+        }
+
+        let shown = self.shown_nodes();
+        let entries = self.structure_panel().map(|panel| panel.entries);
+
+        if let (Some(panel), Some(entries)) = (&mut self.panel, entries) {
+            panel.entries = entries;
+
+            let selected = shown.iter().position(|&i| i == select).unwrap_or(0);
+            panel.move_selection(selected as isize - panel.selected_idx() as isize);
+        }
+
+        self.draw()
+    }
+
+    /// Lists each run of bytes that differs from the file as it was loaded, with its bytes then and now. Unlike the
+    /// modified bytes, which are compared to the last save, this shows everything changed since the editor opened.
+    fn changes_panel(&self) -> Option<ListPanel> {
+        let changes = self.editor.changes_since_load();
+        if changes.is_empty() {
+            return None;
+        }
+
+        let hex = |bytes: &[u8]| {
+            let mut hex = bytes
+                .iter()
+                .take(CHANGE_CONTEXT)
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            if bytes.len() > CHANGE_CONTEXT {
+                hex += " ...";
+            }
+
+            hex
+        };
+
+        let total: usize = changes.iter().map(|(_, bytes)| bytes.len()).sum();
+        let entries = changes
+            .iter()
+            .map(|(offset, before)| {
+                let after = &self.editor.buffer[*offset..offset + before.len()];
+
+                PanelEntry {
+                    offset: Some(*offset),
+                    text: format!(
+                        "{:>8}  {:<w$}  ->  {}",
+                        format!("{:#x}", before.len()),
+                        hex(before),
+                        hex(after),
+                        w = CHANGE_CONTEXT * 3 + 3
+                    ),
+                }
+            })
+            .collect();
+
+        Some(ListPanel::new(
+            PanelKind::Changes,
+            format!(
+                "{:#x} bytes changed in {} places since loading (enter: jump, esc: close)",
+                total,
+                changes.len()
+            ),
+            entries,
+        ))
+    }
+
+    /// Lists the values in the selection, or in the whole file if nothing is selected, that could be pointers into the
+    /// file, which helps to map out an unknown format. Choosing one jumps to where it points.
+    fn pointers_panel(&self, size: usize, base: usize) -> ListPanel {
+        let range = self.selection().unwrap_or(0..self.editor.file_size());
+        let pointers = scan::pointers(
+            &self.editor.buffer,
+            range,
+            size,
+            base,
+            self.config.endianness,
+        );
+
+        let entries = pointers
+            .iter()
+            .map(|&(offset, target)| PanelEntry {
+                offset: Some(target),
+                text: format!("from {:08x}", offset),
+            })
+            .collect();
+
+        ListPanel::new(
+            PanelKind::Pointers,
+            format!(
+                "{} pointers of {} bytes with base {:#x} (enter: jump to target, esc: close)",
+                pointers.len(),
+                size,
+                base
+            ),
+            entries,
+        )
+    }
+
+    /// Lists the zlib and gzip streams and the deflate data of ZIP entries in the file, with their sizes compressed and
+    /// inflated.
+    fn streams_panel(&self) -> ListPanel {
+        let streams = inflate::find_streams(&self.editor.buffer);
+        let entries = streams
+            .iter()
+            .map(|stream| PanelEntry {
+                offset: Some(stream.offset),
+                text: format!(
+                    "{:<8} {:#x} -> {}{:#x} bytes",
+                    stream.format.name(),
+                    stream.compressed_len,
+                    if stream.cut_off { "over " } else { "" },
+                    stream.inflated_len
+                ),
+            })
+            .collect();
+
+        ListPanel::new(
+            PanelKind::Streams,
+            format!(
+                "{} compressed streams (enter: jump, esc: close)",
+                streams.len()
+            ),
+            entries,
+        )
+    }
+
+    /// Inflates the stream at `position`, whose format is told by its header, returning it along with the inflated
+    /// data.
+    fn inflate_at(&self, position: &str) -> Result<(inflate::Stream, Vec<u8>), String> {
+        let position = self.position(position, true)?;
+        let format = inflate::Format::detect(&self.editor.buffer[position..]);
+        let mut data = Vec::new();
+
+        match inflate::inflate(&self.editor.buffer, position, format, &mut data) {
+            Some(stream) => Ok((stream, data)),
+            None => Err(format!(
+                "no valid {} stream at {:#x}",
+                format.name(),
+                position
+            )),
+        }
+    }
+
+    /// Shows a hex dump of the start of the data the stream at `position` inflates to.
+    fn inflate_preview(&self, position: &str) -> Result<ListPanel, String> {
+        let (stream, data) = self.inflate_at(position)?;
+        let entries = data[..cmp::min(data.len(), INFLATE_PREVIEW_LEN)]
+            .chunks(BYTES_PER_ROW)
+            .enumerate()
+            .map(|(row, bytes)| {
+                let hex = bytes
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let text = bytes
+                    .iter()
+                    .map(|&b| match b {
+                        32..=126 => b as char,
+                        _ => self.config.replacement_char,
+                    })
+                    .collect::<String>();
+
+                PanelEntry {
+                    offset: None,
+                    text: format!(
+                        "{:08x}   {:<w$}  {}",
+                        row * BYTES_PER_ROW,
+                        hex,
+                        text,
+                        w = BYTES_PER_ROW * 3 - 1
+                    ),
+                }
+            })
+            .collect();
+
+        let shown = match data.len() > INFLATE_PREVIEW_LEN {
+            true => format!(", showing the first {:#x}", INFLATE_PREVIEW_LEN),
+            false => String::new(),
+        };
+
+        Ok(ListPanel::new(
+            PanelKind::Inflated,
+            format!(
+                "{} stream at {:#x} inflated to {}{:#x} bytes{} (esc: close)",
+                stream.format.name(),
+                stream.offset,
+                if stream.cut_off { "over " } else { "" },
+                stream.inflated_len,
+                shown
+            ),
+            entries,
+        ))
+    }
+
+    /// Writes the data the stream at `position` inflates to into `file`.
+    fn inflate_to_file(&mut self, position: &str, file: &str) -> Result<(), String> {
+        let (stream, data) = self.inflate_at(position)?;
+        fs::write(file, &data).map_err(|e| format!("{}: {}", file, e))?;
+
+        self.message = Some(format!(
+            "wrote the {:#x} bytes of the {} stream at {:#x} to {}",
+            data.len(),
+            stream.format.name(),
+            stream.offset,
+            file
+        ));
+        Ok(())
+    }
+
+    /// Identifies the file type again, since editing may have changed it, and lists the places where data of a known
+    /// type starts, like an archive appended to an executable or an image inside a firmware blob, with how long the
+    /// data is. Lengths the format doesn't tell are marked with `~` and run up to the next place.
+    fn signatures_panel(&mut self) -> ListPanel {
+        let buffer = &self.editor.buffer;
+        self.file_type = magic::identify(buffer).map(|signature| signature.name);
+
+        let found = magic::find_embedded(buffer);
+        let entries = found
+            .iter()
+            .enumerate()
+            .map(|(i, &(offset, signature))| {
+                let len = match magic::data_len(buffer, offset, signature) {
+                    Some(len) => format!("{:#x}", len),
+                    None => {
+                        let next = found[i + 1..]
+                            .iter()
+                            .map(|&(next, _)| next)
+                            .find(|&next| next > offset)
+                            .unwrap_or(buffer.len());
+                        format!("~{:#x}", next - offset)
+                    }
+                };
+
+                PanelEntry {
+                    offset: Some(offset),
+                    text: format!("{:>10}  {}", len, signature.name),
+                }
+            })
+            .collect();
+
+        ListPanel::new(
+            PanelKind::Signatures,
+            format!(
+                "file type: {} (enter: jump, esc: close, :carve to extract)",
+                self.file_type.unwrap_or("unknown")
+            ),
+            entries,
+        )
+    }
+
+    /// Writes the data of a known type at `position` to `file`, as long as its format tells, or up to the next place
+    /// where data of a known type starts.
+    fn carve_to_file(&mut self, position: &str, file: &str) -> Result<(), String> {
+        let position = self.position(position, true)?;
+        let buffer = &self.editor.buffer;
+        let signature = magic::identify(&buffer[position..])
+            .ok_or_else(|| format!("no known signature at {:#x}", position))?;
+        let end = match magic::data_len(buffer, position, signature) {
+            Some(len) => position + len,
+            None => magic::next_embedded(buffer, position),
+        };
+
+        fs::write(file, &buffer[position..end]).map_err(|e| format!("{}: {}", file, e))?;
+
+        self.message = Some(format!(
+            "wrote the {:#x} bytes of the {} at {:#x} to {}",
+            end - position,
+            signature.name,
+            position,
+            file
+        ));
+        Ok(())
+    }
+
+    /// Lists the entropy of each block of `block_size` bytes with a bar, so that compressed or encrypted regions, which
+    /// are close to 8 bits per byte, stand out from code, text and padding. The block at the cursor is selected.
+    fn entropy_panel(&self, block_size: usize) -> ListPanel {
+        let entries = self
+            .editor
+            .buffer
+            .chunks(block_size)
+            .enumerate()
+            .map(|(i, block)| {
+                let entropy = stats::entropy(block);
+
+                PanelEntry {
+                    offset: Some(i * block_size),
+                    text: format!(
+                        "{:.2}  {}",
+                        entropy,
+                        stats::bar(entropy, 8.0, ENTROPY_BAR_WIDTH)
+                    ),
+                }
+            })
+            .collect();
+
+        let mut panel = ListPanel::new(
+            PanelKind::Entropy,
+            format!(
+                "entropy of {:#x}-byte blocks in bits per byte (enter: jump, esc: close)",
+                block_size
+            ),
+            entries,
+        );
+        panel.move_selection((self.editor.cursor_nibble / 2 / block_size) as isize);
+
+        panel
+    }
+
+    /// Lists the strings in the selection, or in the whole file if nothing is selected, starting from the first one at
+    /// or after the cursor.
+    fn open_strings_panel(&mut self) -> Result<(), io::Error> {
+        let range = self.selection().unwrap_or(0..self.editor.file_size());
+        let cursor = self.editor.cursor_nibble / 2;
+        let min_len = self.config.string_length;
+
+        let strings = scan::strings(&self.editor.buffer[range.clone()], min_len);
+        let first = strings.partition_point(|string| range.start + string.end <= cursor);
+        let entries = strings
+            .into_iter()
+            .map(|string| PanelEntry {
+                offset: Some(range.start + string.start),
+                text: String::from_utf8_lossy(&self.editor.buffer[range.start..][string])
+                    .into_owned(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut panel = ListPanel::new(
+            PanelKind::Strings,
+            format!(
+                "{} strings of at least {} characters (enter: jump, esc: close)",
+                entries.len(),
+                min_len
+            ),
+            entries,
+        );
+        panel.move_selection(first as isize);
+
+        self.open_panel(panel)
+    }
+
+    /// Lists how often each byte value occurs in the selection, or in the whole file if nothing is selected, which
+    /// shows padding values, XOR keys and how much of the data is text. The `top` most common values are named in the
+    /// title.
+    fn histogram_panel(&self, top: usize) -> ListPanel {
+        let range = self.selection().unwrap_or(0..self.editor.file_size());
+        let counts = stats::byte_counts(&self.editor.buffer[range.clone()]);
+        let max = counts.iter().copied().max().unwrap_or(0).max(1);
+
+        let entries = counts
+            .iter()
+            .enumerate()
+            .map(|(value, &count)| PanelEntry {
+                offset: None,
+                text: format!(
+                    "{:02x}  {:>10}  {:>6.2}%  {}",
+                    value,
+                    count,
+                    count as f64 * 100.0 / range.len().max(1) as f64,
+                    stats::bar(count as f64, max as f64, HISTOGRAM_BAR_WIDTH)
+                ),
+            })
+            .collect();
+
+        let most_common = stats::most_common(&counts, top)
+            .iter()
+            .map(|value| format!("{:02x}", value))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        ListPanel::new(
+            PanelKind::Histogram,
+            format!(
+                "byte values in {:#x} bytes, most common: {} (esc: close)",
+                range.len(),
+                most_common
+            ),
+            entries,
+        )
+    }
+
+    /// Annotates the selection, or the byte at the cursor, with `input`, which can start with a color and a colon, like
+    /// `red: length`. Removes the innermost annotation at the cursor if `input` is empty.
+    fn annotate(&mut self, input: &str) {
+        let cursor = self.editor.cursor_nibble / 2;
+
+        if input.trim().is_empty() {
+            match self.bookmarks.annotation_at(cursor) {
+                Some(i) => {
+                    self.bookmarks.remove_annotation(i);
+                    self.save_bookmarks();
+                }
+                None => self.message = Some(String::from("no annotation at the cursor")),
+            }
+
+            return;
+        }
+
+        let (color, comment) = match input.split_once(':') {
+            Some((color, comment)) if highlight::parse_color(color.trim()).is_some() => {
+                (color.trim(), comment.trim())
+            }
+            _ => (DEFAULT_ANNOTATION_COLOR, input.trim()),
+        };
+        let range = self.selection().unwrap_or(cursor..cursor + 1);
+
+        if range.end > self.editor.file_size() {
+            self.message = Some(String::from("nothing to annotate in an empty file"));
+            return;
+        }
+
+        match self.bookmarks.annotate(range, color, comment) {
+            Ok(()) => {
+                self.selection_anchor = None;
+                self.save_bookmarks();
+            }
+            Err(e) => self.message = Some(e),
+        }
+    }
+
+    /// Writes the bookmarks and annotations to `path` as JSON.
+    fn export_bookmarks(&mut self, path: &str) {
+        self.message = Some(match fs::write(path, self.bookmarks.to_json()) {
+            Ok(()) => format!(
+                "exported {} bookmarks and {} annotations to {}",
+                self.bookmarks.list().len(),
+                self.bookmarks.annotations().len(),
+                path
+            ),
+            Err(e) => format!("{}: {}", path, e),
+        });
+    }
+
+    /// Adds the bookmarks and annotations exported to `path`.
+    fn import_bookmarks(&mut self, path: &str) {
+        let result = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| self.bookmarks.import_json(&json));
+
+        match result {
+            Ok((bookmarks, annotations)) => {
+                self.message = Some(format!(
+                    "imported {} bookmarks and {} annotations",
+                    bookmarks, annotations
+                ));
+                self.save_bookmarks();
+            }
+            Err(e) => self.message = Some(format!("{}: {}", path, e)),
+        }
+    }
+
+    fn save_bookmarks(&mut self) {
+        if let Err(e) = self.bookmarks.save() {
+            self.message = Some(format!("unable to save bookmarks and annotations: {}", e));
+        }
+    }
+
+    /// Draws the row at `row_offset` on screen row `y` from the cells recorded for row `row` of the grid.
+    fn draw_row(
+        &mut self,
+        layout: Layout,
+        y: usize,
+        row_offset: usize,
+        row: usize,
+    ) -> Result<(), io::Error> {
+        let cells = self.damage.cells(row);
+
+        self.frame.move_to(1, y);
+        if row_offset < self.editor.file_size() {
+            self.frame.print_hex(row_offset, 8);
+        }
+
+        if let Some(text_x) = layout.text_x {
+            self.frame.move_to(text_x, y);
+            for cell in cells {
+                let c = match cell.byte {
+                    32..=126 => cell.byte as char,
+                    _ => self.config.replacement_char,
+                };
+
+                if let Some(colors) = cell.colors {
+                    self.frame.set_colors(colors);
+                    self.frame.put(c);
+                    self.frame.reset_colors();
+                } else {
+                    self.frame.put(c);
+                }
+            }
+        }
+
+        self.frame.move_to(layout.hex_x, y);
+        for (col, cell) in cells.iter().enumerate() {
+            let gap = if layout.is_group_end(col) { "  " } else { " " };
+
+            match cell.colors {
+                Some(colors) if cell.joined => {
+                    self.frame.set_colors(colors);
+                    self.frame.print_byte(cell.byte);
+                    self.frame.print(gap);
+                    self.frame.reset_colors();
+                }
+                Some(colors) => {
+                    self.frame.set_colors(colors);
+                    self.frame.print_byte(cell.byte);
+                    self.frame.reset_colors();
+                    self.frame.print(gap);
+                }
+                None => {
+                    self.frame.print_byte(cell.byte);
+                    self.frame.print(gap);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the colors of the byte at `offset` if it is highlighted, and the number of bytes highlighted from there.
+    /// The selection is drawn over search results, which are drawn over annotations and then highlight layers.
+    fn highlight_at(&self, offset: usize) -> Option<(Colors, usize)> {
+        let colors = self.config.highlight_colors?;
+
+        if let Some(selection) = self
+            .selection()
+            .filter(|selection| selection.contains(&offset))
+        {
+            return Some((
+                Colors::new(Color::Black, Color::Grey),
+                selection.end - offset,
+            ));
+        }
+
+        if let Some(len) = self
+            .search_results
+            .as_ref()
+            .and_then(|res| res.match_len(offset))
+        {
+            return Some((colors, len));
+        }
+
+        if let Some(i) = self.bookmarks.annotation_at(offset) {
+            let annotation = &self.bookmarks.annotations()[i];
+
+            // an annotation inside this one is drawn over it
+            let end = match self.bookmarks.next_annotation_start(offset) {
+                Some(start) => cmp::min(start, annotation.range.end),
+                None => annotation.range.end,
+            };
+
+            return Some((Colors::new(Color::White, annotation.color), end - offset));
+        }
+
+        self.highlights
+            .color_at(offset)
+            .map(|(color, len)| (Colors::new(Color::White, color), len))
+    }
+
+    fn draw_collapsed_row(&mut self, layout: Layout, y: usize, run: Run) -> Result<(), io::Error> {
+        self.frame.move_to(1, y);
+        self.frame.print_hex(run.start, 8);
+
+        let summary = &mut self.line;
+        summary.clear();
+        let _ = write!(
+            summary,
+            "* {:02x} ({:#x} bytes)",
+            run.byte,
+            run.end - run.start
+        );
+        summary.truncate(layout.width() - layout.hex_x);
+
+        self.frame.move_to(layout.hex_x, y);
+        self.frame.print(summary);
+
+        Ok(())
+    }
+
+    fn update_runs(&mut self) {
+        if self.collapse_runs {
+            self.runs.update(
+                &self.editor.buffer,
+                self.bytes_per_row(),
+                self.editor.generation,
+            );
+        }
+    }
+
+    /// Returns the collapsed run hiding the byte at `offset`. A run is shown expanded while the cursor is inside of
+    /// it, except when the cursor is on the summary row.
+    fn collapsed_run(&self, offset: usize) -> Option<Run> {
+        if !self.collapse_runs {
+            return None;
+        }
+
+        let cursor = self.editor.cursor_nibble / 2;
+
+        self.runs
+            .run_at(offset)
+            .filter(|run| cursor <= run.start || cursor >= run.end)
+    }
+
+    /// Returns the offset of the first byte of the screen row containing `offset`.
+    fn row_start(&self, offset: usize) -> usize {
+        match self.collapsed_run(offset) {
+            Some(run) => run.start,
+            None => offset - offset % self.bytes_per_row(),
+        }
+    }
+
+    fn next_row(&self, row_offset: usize) -> usize {
+        match self.collapsed_run(row_offset) {
+            Some(run) => run.end,
+            None => row_offset + self.bytes_per_row(),
+        }
+    }
+
+    fn prev_row(&self, row_offset: usize) -> usize {
+        if row_offset == 0 {
+            0
+        } else {
+            self.row_start(row_offset - 1)
+        }
+    }
+
+    /// Returns the row `n` rows above the row starting at `row_offset`, stopping at the first row.
+    fn rows_before(&self, mut row_offset: usize, n: usize) -> usize {
+        for _ in 0..n {
+            row_offset = self.prev_row(row_offset);
+        }
+
+        row_offset
+    }
+
+    /// Scrolls so that the row of the cursor has `rows_above` rows above it on the screen, as far as the file allows.
+    fn scroll_cursor_row(&mut self, rows_above: usize) {
+        let cursor_row = self.row_start(self.editor.cursor_nibble / 2);
+        self.editor.offset = self.rows_before(cursor_row, rows_above);
+    }
+
+    /// Returns the row `n` rows below the row starting at `row_offset`, stopping at the last row.
+    fn rows_after(&self, mut row_offset: usize, n: usize) -> usize {
+        for _ in 0..n {
+            let next = self.next_row(row_offset);
+
+            if next >= self.editor.file_size() {
+                break;
+            }
+
+            row_offset = next;
+        }
+
+        row_offset
+    }
+
+    /// The number of rows kept between the cursor and the top and bottom of the screen.
+    fn scroll_margin(&self, visible_rows: usize) -> usize {
+        cmp::min(self.config.scroll_off, (visible_rows - 1) / 2)
+    }
+
+    /// Moves the viewport so that the cursor row is one of the `visible_rows` rows on screen, at least the scroll
+    /// margin away from the edges.
+    fn scroll_to_cursor(&mut self, visible_rows: usize) {
+        // the offset may have been set with a different layout or before runs were collapsed
+        let last_byte = self.editor.file_size().saturating_sub(1);
+        self.editor.offset = self.row_start(cmp::min(self.editor.offset, last_byte));
+
+        let margin = self.scroll_margin(visible_rows);
+        let cursor_row = self.row_start(self.editor.cursor_nibble / 2);
+        let top = self.rows_before(cursor_row, margin);
+        let bottom = self.rows_after(cursor_row, margin);
+
+        if top < self.editor.offset {
+            self.editor.offset = top;
+        } else {
+            let mut row_offset = self.editor.offset;
+
+            for _ in 0..visible_rows {
+                if row_offset == bottom {
+                    return;
+                }
+
+                row_offset = self.next_row(row_offset);
+            }
+
+            self.editor.offset = self.rows_before(bottom, visible_rows - 1);
+        }
+    }
+
+    pub fn cycle_editor_mode(&mut self) -> Result<(), io::Error> {
+        self.set_editor_mode(self.editor_mode.next())
+    }
+
+    pub fn set_editor_mode(&mut self, editor_mode: EditorMode) -> Result<(), io::Error> {
+        if self.editor_mode != editor_mode {
+            self.editor_mode = editor_mode;
+            self.editor.cursor_nibble -= self.editor.cursor_nibble % 2;
+            self.draw()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn move_cursor(&mut self, movement: CursorMovementType) -> Result<(), io::Error> {
+        let xmov = match self.editor_mode {
+            EditorMode::HexMode => 1,
+            EditorMode::TextMode => 2,
+        };
+
+        self.update_runs();
+
+        let page = self.height - 4;
+        let cursor_row = self.row_start(self.editor.cursor_nibble / 2);
+        let col_nibble = self.editor.cursor_nibble - 2 * cursor_row;
+
+        match movement {
+            CursorMovementType::Right => {
+                self.editor.cursor_nibble += xmov;
+            }
+            CursorMovementType::Left => {
+                self.editor.cursor_nibble = self.editor.cursor_nibble.saturating_sub(xmov);
+            }
+            CursorMovementType::Up => {
+                if cursor_row == 0 {
+                    self.editor.cursor_nibble = 0;
+                } else {
+                    self.set_cursor_row(self.prev_row(cursor_row), col_nibble);
+                }
+            }
+            CursorMovementType::Down => {
+                self.set_cursor_row(self.next_row(cursor_row), col_nibble);
+            }
+            CursorMovementType::PageUp => {
+                let mut row_offset = cursor_row;
+                for _ in 0..page {
+                    row_offset = self.prev_row(row_offset);
+                }
+                self.set_cursor_row(row_offset, col_nibble);
+
+                for _ in 0..page {
+                    self.editor.offset = self.prev_row(self.editor.offset);
+                }
+            }
+            CursorMovementType::PageDown => {
+                let mut row_offset = cursor_row;
+                for _ in 0..page {
+                    row_offset = self.next_row(row_offset);
+                }
+                self.set_cursor_row(row_offset, col_nibble);
+
+                for _ in 0..page {
+                    self.editor.offset = self.next_row(self.editor.offset);
+                }
+            }
+            CursorMovementType::RowStart => {
+                self.editor.cursor_nibble = 2 * cursor_row;
+            }
+            CursorMovementType::RowEnd => {
+                self.editor.cursor_nibble = 2 * (self.next_row(cursor_row) - 1);
+            }
+            CursorMovementType::GroupLeft => {
+                let cursor = self.editor.cursor_nibble / 2;
+                let group_start = cursor - cursor % self.config.group_size;
+
+                self.editor.cursor_nibble = if group_start == cursor && col_nibble.is_multiple_of(2)
+                {
+                    2 * cursor.saturating_sub(self.config.group_size)
+                } else {
+                    2 * group_start
+                };
+            }
+            CursorMovementType::GroupRight => {
+                let cursor = self.editor.cursor_nibble / 2;
+                let group_start = cursor - cursor % self.config.group_size;
+
+                self.editor.cursor_nibble = 2 * (group_start + self.config.group_size);
+            }
+            CursorMovementType::ScrollUp => {
+                self.editor.offset = self.prev_row(self.editor.offset);
+
+                let bottom =
+                    self.rows_after(self.editor.offset, page - 1 - self.scroll_margin(page));
+                if cursor_row > bottom {
+                    self.set_cursor_row(bottom, col_nibble);
+                }
+            }
+            CursorMovementType::ScrollDown => {
+                if self.editor.offset < self.row_start(self.editor.file_size().saturating_sub(1)) {
+                    self.editor.offset = self.next_row(self.editor.offset);
+                }
+
+                let top = self.rows_after(self.editor.offset, self.scroll_margin(page));
+                if cursor_row < top {
+                    self.set_cursor_row(top, col_nibble);
+                }
+            }
+            CursorMovementType::RecordUp => {
+                self.editor.cursor_nibble = self
+                    .editor
+                    .cursor_nibble
+                    .saturating_sub(2 * self.config.record_size);
+            }
+            CursorMovementType::RecordDown => {
+                self.editor.cursor_nibble += 2 * self.config.record_size;
+            }
+            CursorMovementType::FileStart => {
+                self.jump_to(0);
+                self.scroll_cursor_row((page - 1) / 2);
+            }
+            CursorMovementType::FileEnd => {
+                self.jump_to(self.editor.file_size().saturating_sub(1));
+                self.scroll_cursor_row((page - 1) / 2);
+            }
+        }
+
+        self.draw()?;
+
+        Ok(())
+    }
+
+    fn run_command(&mut self, input: &str) {
+        let input = input.trim();
+        let (name, args) = input.split_once(' ').unwrap_or((input, ""));
+
+        if let Some(command) = self.plugins.command(name) {
+            let mut context =
+                CommandContext::new(&self.editor.buffer, self.editor.cursor_nibble / 2);
+            let result = command.run(args.trim(), &mut context);
+            let (position, writes) = context.into_changes();
+
+            match result {
+                Ok(message) => {
+                    self.message = match self.editor.apply_writes(&writes) {
+                        Ok(()) => message,
+                        Err(e) => Some(format!("{}: {}", name, e)),
+                    };
+
+                    if position != self.editor.cursor_nibble / 2 {
+                        self.jump_to(position);
+                    }
+                }
+                Err(e) => self.message = Some(e),
+            }
+
+            return;
+        }
+
+        let command = match command::parse(input) {
+            Ok(command) => command,
+            Err(e) => {
+                self.message = Some(e);
+                return;
+            }
+        };
+
+        match command {
+            Command::Write => {
+                self.save(false);
+            }
+            Command::Quit { force } => {
+                if force || self.editor.saved {
+                    self.running = false;
+                } else {
+                    self.message = Some(String::from("unsaved changes, use :q! to quit anyway"));
+                }
+            }
+            Command::WriteQuit => {
+                self.save(true);
+            }
+            Command::SaveAs(path) => {
+                self.save_as(&path, false);
+            }
+            Command::Set {
+                option,
+                value: None,
+            } => {
+                self.message = Some(match command::get_option(&self.config, &option) {
+                    Ok(value) => format!("{}={}", option, value),
+                    Err(e) => e,
+                });
+            }
+            Command::Set {
+                option,
+                value: Some(value),
+            } => match command::set_option(&mut self.config, &option, &value) {
+                Ok(()) => self.update_layout(),
+                Err(e) => self.message = Some(e),
+            },
+            Command::Fill { byte, count } => {
+                let position = self.editor.cursor_nibble / 2;
+                let end = cmp::min(position.saturating_add(count), self.editor.file_size());
+                let bytes = vec![byte; end - position];
+
+                self.message = Some(match self.editor.replace_all(&[position], &bytes) {
+                    Ok(_) => format!("filled {:#x} bytes", bytes.len()),
+                    Err(e) => e.to_string(),
+                });
+            }
+            Command::Goto(position) => self.goto(&position),
+            Command::Calc(expression) => match self.evaluate(&expression) {
+                Ok(value) => self.message = Some(value::format_bases(value)),
+                Err(e) => self.message = Some(e),
+            },
+            Command::Put { ty, expression } => {
+                if let Err(e) = self.put(&ty, &expression) {
+                    self.message = Some(e);
+                }
+            }
+            Command::Script(path) => self.run_script(&path),
+            Command::Filter(command) => self.filter_selection(&command),
+            Command::Hash(algorithm) => self.hash(&algorithm),
+            Command::Struct { name, position } => {
+                let result = match name {
+                    Some(name) => self.apply_structure(&name, position.as_deref()),
+                    None => Ok(()),
+                };
+
+                match (result, self.structure_panel()) {
+                    (Err(e), _) => self.message = Some(e),
+                    (Ok(()), Some(panel)) => self.show_panel(panel),
+                    (Ok(()), None) => self.message = Some(String::from("no structure applied")),
+                }
+            }
+            Command::Magic => {
+                let panel = self.signatures_panel();
+                self.show_panel(panel);
+            }
+            Command::Inflate { position, file } => match (position, file) {
+                (None, _) => {
+                    let panel = self.streams_panel();
+                    self.show_panel(panel);
+                }
+                (Some(position), None) => match self.inflate_preview(&position) {
+                    Ok(panel) => self.show_panel(panel),
+                    Err(e) => self.message = Some(e),
+                },
+                (Some(position), Some(file)) => {
+                    if let Err(e) = self.inflate_to_file(&position, &file) {
+                        self.message = Some(e);
+                    }
+                }
+            },
+            Command::Carve { position, file } => {
+                if let Err(e) = self.carve_to_file(&position, &file) {
+                    self.message = Some(e);
+                }
+            }
+            Command::Export(path) => self.export_bookmarks(&path),
+            Command::Import(path) => self.import_bookmarks(&path),
+            Command::Pointers { size, base } => {
+                let size = size.unwrap_or(self.config.pointer_size);
+                let base = base.unwrap_or(self.config.pointer_base);
+                let panel = self.pointers_panel(size, base);
+                self.show_panel(panel);
+            }
+            Command::Changes => match self.changes_panel() {
+                Some(panel) => self.show_panel(panel),
+                None => self.message = Some(String::from("no changes since the file was loaded")),
+            },
+            Command::Entropy { block_size } => {
+                let panel = self.entropy_panel(block_size);
+                self.show_panel(panel);
+            }
+            Command::Histogram { top } => {
+                let panel = self.histogram_panel(top);
+                self.show_panel(panel);
+            }
+            Command::Checksum {
+                algorithm,
+                start,
+                end,
+                target,
+                width,
+                endianness,
+            } => {
+                let endianness = endianness.unwrap_or(self.config.endianness);
+
+                if let Err(e) =
+                    self.write_checksum(&algorithm, [&start, &end, &target], width, endianness)
+                {
+                    self.message = Some(e);
+                }
+            }
+            Command::Play { register, count } => {
+                if let Err(e) = self.play_macro(register, count) {
+                    self.message = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    /// Returns the selected range of bytes.
+    fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.selection_anchor?;
+        let cursor = self.editor.cursor_nibble / 2;
+
+        let end = cmp::min(cmp::max(anchor, cursor) + 1, self.editor.file_size());
+
+        Some(cmp::min(anchor, cursor)..end).filter(|selection| !selection.is_empty())
+    }
+
+    /// Replaces the selection with the output of the shell command `command`, which is given the selected bytes as
+    /// input. Since the buffer is edited in place and can't grow or shrink, the output must be as long as the
+    /// selection.
+    fn filter_selection(&mut self, command: &str) {
+        let Some(selection) = self.selection() else {
+            self.message = Some(String::from("no selection"));
+            return;
+        };
+
+        let output = match filter::run(command, &self.editor.buffer[selection.clone()]) {
+            Ok(output) => output,
+            Err(e) => {
+                self.message = Some(e);
+                return;
+            }
+        };
+
+        if output.len() != selection.len() {
+            self.message = Some(format!(
+                "command output is {:#x} bytes, but the selection is {:#x} bytes",
+                output.len(),
+                selection.len()
+            ));
+            return;
+        }
+
+        self.selection_anchor = None;
+        self.message = Some(match self.editor.replace_all(&[selection.start], &output) {
+            Ok(_) => format!("filtered {:#x} bytes", output.len()),
+            Err(e) => e.to_string(),
+        });
+    }
+
+    /// Shows the hash of the selection, or of the whole file if nothing is selected, and copies it to the clipboard.
+    fn hash(&mut self, algorithm: &str) {
+        let range = self.selection().unwrap_or(0..self.editor.file_size());
+        let Some(hash) = hash::hash(algorithm, &self.editor.buffer[range.clone()]) else {
+            self.message = Some(format!("unknown hash: {}", algorithm));
+            return;
+        };
+
+        let copied = match self.copy_to_clipboard(&hash) {
+            Ok(()) => " (copied)",
+            Err(_) => "",
+        };
+        self.message = Some(format!(
+            "{} of {:#x} bytes: {}{}",
+            algorithm,
+            range.len(),
+            hash,
+            copied
+        ));
+    }
+
+    /// Writes the value of `expression` at the cursor as an integer of type `ty`, as a single change.
+    fn put(&mut self, ty: &str, expression: &str) -> Result<(), String> {
+        let value = self.evaluate(expression)?;
+        let bytes = value::encode(&format!("{}:{}", ty, value), self.config.endianness)
+            .ok_or_else(|| format!("{} doesn't fit in {}", value, ty))?;
+        let cursor = self.editor.cursor_nibble / 2;
+
+        if self.editor.replace_all(&[cursor], &bytes).is_err() {
+            return Err(format!("{} bytes don't fit at {:#x}", bytes.len(), cursor));
+        }
+
+        self.message = Some(format!("wrote {} {} at {:#x}", ty, value, cursor));
+
+        Ok(())
+    }
+
+    /// Computes a checksum of the bytes between the positions `start` and `end` and writes it at `target`, as a single
+    /// change that can be undone, so that a header stays valid after patching the bytes it covers.
+    fn write_checksum(
+        &mut self,
+        algorithm: &str,
+        [start, end, target]: [&str; 3],
+        width: Option<usize>,
+        endianness: Endianness,
+    ) -> Result<(), String> {
+        let start = self.position(start, true)?;
+        let end = self.position(end, false)?;
+        let target = self.position(target, true)?;
+
+        if end < start {
+            return Err(format!("the range ends before it starts at {:#x}", start));
+        }
+
+        let (checksum, size) = hash::checksum(algorithm, &self.editor.buffer[start..end])
+            .ok_or_else(|| format!("unknown checksum: {}", algorithm))?;
+        let width = width.unwrap_or(size);
+
+        if target + width > self.editor.file_size() {
+            return Err(format!("{} bytes don't fit at {:#x}", width, target));
+        }
+
+        let bytes = match endianness {
+            Endianness::Little => checksum.to_le_bytes()[..width].to_vec(),
+            Endianness::Big => checksum.to_be_bytes()[8 - width..].to_vec(),
+        };
+
+        self.editor
+            .replace_all(&[target], &bytes)
+            .map_err(|e| e.to_string())?;
+        self.message = Some(format!(
+            "wrote {} {:#x} of {:#x} bytes at {:#x}",
+            algorithm,
+            checksum,
+            end - start,
+            target
+        ));
+
+        Ok(())
+    }
+
+    /// Copies `text` to the clipboard with the OSC 52 escape sequence, which most terminals support, also over ssh.
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<(), io::Error> {
+        let encoded = BASE64_STANDARD.encode(text);
+        execute!(
+            self.stdout,
+            style::Print(format!("\x1b]52;c;{}\x07", encoded))
+        )
+    }
+
+    /// Runs the script at `path`, showing the last line it printed or its error in the status line.
+    fn run_script(&mut self, path: &str) {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                self.message = Some(format!("unable to read script: {}", e));
+                return;
+            }
+        };
+        let cursor = self.editor.cursor_nibble / 2;
+
+        match script::run(
+            &mut self.editor,
+            &mut self.selection_anchor,
+            &source,
+            self.config.endianness,
+        ) {
+            Ok(mut output) => self.message = output.pop(),
+            Err(e) => self.message = Some(format!("error in script: {}", e)),
+        }
+
+        // a script moving the cursor counts as a jump
+        let position = self.editor.cursor_nibble / 2;
+        if position != cursor {
+            self.editor.cursor_nibble = 2 * cursor;
+            self.jump_to(position);
+        }
+    }
+
+    /// Saves the file and quits if `quit` is set. If saving fails, asks whether to try again, save to another path or
+    /// discard the changes.
+    fn save(&mut self, quit: bool) {
+        match self.editor.save() {
+            Ok(()) => {
+                self.watch_saved_file();
+                self.running = !quit;
+            }
+            Err(e) => self.ask_save_failed(e, quit),
+        }
+    }
+
+    /// Like `save`, but saves to `path` and keeps editing that file.
+    fn save_as(&mut self, path: &str, quit: bool) {
+        match self.editor.save_as(path) {
+            Ok(()) => {
+                self.watch_saved_file();
+                self.message = Some(format!("saved as {}", path));
+                self.running = !quit;
+            }
+            Err(e) => self.ask_save_failed(e, quit),
+        }
+    }
+
+    /// Keeps the watch from reloading the file that was just saved, and has it watch the file saved to.
+    fn watch_saved_file(&mut self) {
+        if let Some(watch) = &mut self.watch {
+            watch.restart(&self.editor.filename);
+        }
+    }
+
+    fn ask_save_failed(&mut self, error: io::Error, quit: bool) {
+        self.prompt(
+            format!(
+                "unable to save file: {}. (r: retry/s: save as/d: discard changes) ",
+                error
+            ),
+            None,
+            Box::new(move |screen: &mut Screen, input: &str| match input {
+                "r" => screen.save(quit),
+                "s" => screen.prompt(
+                    String::from("save as: "),
+                    None,
+                    Box::new(move |screen: &mut Screen, path: &str| screen.save_as(path, quit)),
+                ),
+                "d" => {
+                    screen.editor.discard_changes();
+                    screen.running = !quit;
+                }
+                _ => {}
+            }),
+        );
+    }
+
+    /// Recomputes the layout after the terminal or the options changed.
+    fn update_layout(&mut self) {
+        self.layout = Layout::new(
+            self.width,
+            self.config.bytes_per_row,
+            self.config.group_size,
+        );
+    }
+
+    /// Jumps to the position given by the expression `input`, which is relative to the cursor if it starts with `+`
+    /// or `-`.
+    pub fn goto(&mut self, input: &str) {
+        if !self.opened {
+            self.pending_goto = Some(input.to_owned());
+            return;
+        }
+
+        let relative = input.starts_with(['+', '-']);
+        let input = if relative {
+            format!(".{}", input)
+        } else {
+            input.to_owned()
+        };
+
+        let position = match self.evaluate(&input) {
+            // a relative jump back past the start of the file stops there
+            Ok(position) if relative => Ok(position.max(0)),
+            Ok(position) if position >= 0 => Ok(position),
+            Ok(position) => Err(format!("invalid position {}", position)),
+            // bookmark names may contain characters that aren't allowed in expressions
+            Err(e) => self
+                .bookmarks
+                .get(input.trim())
+                .map(|position| position as i128)
+                .ok_or(e),
+        };
+
+        let position = position.and_then(|position| {
+            usize::try_from(position)
+                .ok()
+                .filter(|&position| position < self.editor.file_size())
+                .ok_or_else(|| String::from("past end of file"))
+        });
+
+        match position {
+            Ok(position) => self.jump_to(position),
+            Err(e) => self.message = Some(e),
+        }
+    }
+
+    /// Evaluates an expression where `.` is the cursor, `end` the file size and other names are bookmarks.
+    fn evaluate(&self, input: &str) -> Result<i128, String> {
+        let cursor = self.editor.cursor_nibble / 2;
+        let lookup = |name: &str| match name {
+            "." => Some(cursor),
+            "end" => Some(self.editor.file_size()),
+            _ => self.bookmarks.get(name),
+        };
+
+        expr::evaluate(input, &lookup)
+    }
+
+    /// Evaluates `input` like `:goto` does, failing if the position is outside the file or, if `inclusive`, past its
+    /// end.
+    fn position(&self, input: &str, inclusive: bool) -> Result<usize, String> {
+        let position = match self.evaluate(input) {
+            Ok(position) => usize::try_from(position).ok(),
+            Err(e) => Some(self.bookmarks.get(input).ok_or(e)?),
+        };
+
+        match position {
+            Some(position) if position < self.editor.file_size() + usize::from(!inclusive) => {
+                Ok(position)
+            }
+            _ => Err(format!("invalid position {}", input)),
+        }
+    }
+
+    /// Searches for `input` starting at the cursor, or for the last search again if `input` is empty, so that `?`
+    /// after `/` searches for the same thing backward.
+    fn search(&mut self, input: &str, direction: Direction) {
+        let input = match (input, &self.last_search) {
+            ("", Some(last)) => last.clone(),
+            _ => input.to_owned(),
+        };
+
+        self.search_results = None;
+        self.search_job = match Pattern::parse(&input, self.config.endianness) {
+            Some(pattern) if !pattern.is_empty() => Some(SearchJob::start(
+                Arc::clone(&self.editor.buffer),
+                pattern,
+                self.editor.cursor_nibble / 2,
+                direction,
+            )),
+            _ => {
+                self.message = Some(String::from("invalid pattern"));
+                None
+            }
+        };
+        self.last_search = Some(input);
+    }
+
+    /// Reports how many times `input`, or the last search if it is empty, occurs in the file, without moving the
+    /// cursor.
+    fn count_matches(&mut self, input: &str) {
+        let input = match (input, &self.last_search) {
+            ("", Some(last)) => last.as_str(),
+            _ => input,
+        };
+
+        self.message = Some(match Pattern::parse(input, self.config.endianness) {
+            Some(pattern) if !pattern.is_empty() => {
+                match pattern.find_all(&self.editor.buffer).len() {
+                    0 => String::from("no matches"),
+                    1 => String::from("1 match"),
+                    count => format!("{} matches", count),
+                }
+            }
+            _ => String::from("invalid pattern"),
+        });
+    }
+
+    /// Writes the offsets of the current search results to the file `path`.
+    fn export_search_results(&mut self, path: &str) {
+        let Some(results) = &self.search_results else {
+            return;
+        };
+
+        let output = search::export(
+            &self.editor.buffer,
+            results.results(),
+            results.match_size(),
+            self.config.export_context,
+        );
+
+        self.message = Some(match fs::write(path, output) {
+            Ok(()) => format!("exported {} offsets to {}", results.len(), path),
+            Err(e) => format!("unable to export results: {}", e),
+        });
+    }
+
+    /// Searches for several patterns at once, separated by `|` or read one per line from a file given as `@path`, and
+    /// highlights the matches of each pattern in its own color.
+    fn search_many(&mut self, input: &str) {
+        let inputs: Vec<String> = match input.strip_prefix('@') {
+            Some(path) => match fs::read_to_string(path.trim()) {
+                Ok(contents) => contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(String::from)
+                    .collect(),
+                Err(e) => {
+                    self.message = Some(format!("unable to read patterns: {}", e));
+                    return;
+                }
+            },
+            None => input
+                .split('|')
+                .map(str::trim)
+                .filter(|pattern| !pattern.is_empty())
+                .map(String::from)
+                .collect(),
+        };
+
+        if inputs.is_empty() {
+            self.message = Some(String::from("no patterns"));
+            return;
+        }
 
-                if x >= 36 {
-                    x += 1;
+        let mut patterns = Vec::new();
+
+        for input in &inputs {
+            match Pattern::parse(input, self.config.endianness) {
+                Some(pattern) if !pattern.is_empty() && pattern.exact_bytes().is_some() => {
+                    patterns.push(pattern)
+                }
+                _ => {
+                    self.message = Some(format!(
+                        "invalid pattern {} (wildcards aren't allowed)",
+                        input
+                    ));
+                    return;
                 }
+            }
+        }
+
+        let bytes: Vec<&[u8]> = patterns
+            .iter()
+            .filter_map(|pattern| pattern.exact_bytes())
+            .collect();
+        let mut results = search::find_many(&self.editor.buffer, &bytes);
+
+        for (offsets, pattern) in results.iter_mut().zip(&patterns) {
+            offsets.retain(|offset| offset.is_multiple_of(pattern.align()));
+        }
+
+        for ((input, offsets), pattern) in inputs.iter().zip(&results).zip(&bytes) {
+            if !offsets.is_empty() {
+                let ranges = offsets
+                    .iter()
+                    .map(|&offset| offset..offset + pattern.len())
+                    .collect();
+
+                self.highlights.add(input, ranges);
+            }
+        }
+
+        let cursor = self.editor.cursor_nibble / 2;
+        let next = results
+            .iter()
+            .flatten()
+            .filter(|&&offset| offset > cursor)
+            .min();
+
+        if let Some(&position) = next.or_else(|| results.iter().flatten().min()) {
+            self.jump_to(position);
+        }
+
+        let found = results.iter().filter(|offsets| !offsets.is_empty()).count();
+        self.message = Some(format!(
+            "{} matches of {} out of {} patterns",
+            results.iter().map(Vec::len).sum::<usize>(),
+            found,
+            inputs.len()
+        ));
+    }
+
+    /// Collects how much of the file has been read, waiting up to `timeout` for it, and starts editing it once it is
+    /// loaded.
+    fn receive_file(&mut self, timeout: Duration) -> Result<(), io::Error> {
+        let Some(job) = &mut self.load_job else {
+            return Ok(());
+        };
+
+        let result = job.receive(timeout);
+
+        if result.is_some() {
+            self.load_job = None;
+        }
+
+        match result {
+            Some(Ok(file)) if self.opened => self.reload_file(file),
+            Some(Ok(file)) => self.open_file(file),
+            Some(Err(e)) if self.opened => {
+                self.message = Some(format!("unable to reload: {}", e));
+            }
+            Some(Err(e)) => return Err(e),
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// Edits the file read by the load job, identifying its type and highlighting it.
+    fn open_file(&mut self, file: LoadedFile) {
+        self.editor = FileEditor::from_bytes(&self.editor.filename, file.buffer);
+        self.bookmarks = file.bookmarks;
+        self.opened = true;
+        self.watch = Some(FileWatch::start(&self.editor.filename));
+
+        let signature = magic::identify(&self.editor.buffer);
+        self.file_type = signature.map(|signature| signature.name);
+
+        // unlike with :struct, fields that don't fit the file are left out without a message
+        self.structure = signature
+            .and_then(|signature| signature.structure)
+            .filter(|_| self.config.auto_structure)
+            .and_then(|name| {
+                let definition = structure::load(name).ok()?;
+                Some(definition.apply(name, &self.editor.buffer, 0, self.config.endianness))
+            });
+
+        self.highlight_file();
+
+        if let Some(position) = self.pending_goto.take() {
+            self.goto(&position);
+        }
+    }
+
+    /// Reloads the file if another program changed it, unless it has changes that reloading would lose.
+    fn receive_change(&mut self) -> Result<(), io::Error> {
+        if !self.watch.as_mut().is_some_and(|watch| watch.changed()) {
+            return Ok(());
+        }
+
+        if self.editor.saved {
+            self.load_job = Some(LoadJob::start(&self.editor.filename));
+        } else {
+            self.message = Some(format!("{} changed on disk", self.editor.filename));
+        }
+
+        self.draw()
+    }
+
+    /// Replaces the contents of the file with the ones read again by the load job, keeping the view and reapplying
+    /// the structure.
+    fn reload_file(&mut self, file: LoadedFile) {
+        // changes made while the file was read again would be lost
+        if !self.editor.saved {
+            self.message = Some(format!("{} changed on disk", self.editor.filename));
+            return;
+        }
+
+        let mut editor = FileEditor::from_bytes(&self.editor.filename, file.buffer);
+        // views derived from the contents of the file are recomputed
+        editor.generation = self.editor.generation + 1;
+        editor.offset = self.editor.offset;
+        editor.cursor_nibble = cmp::min(
+            self.editor.cursor_nibble,
+            (2 * editor.file_size()).saturating_sub(1),
+        );
+
+        self.editor = editor;
+        self.bookmarks = file.bookmarks;
+        self.search_job = None;
+        self.search_results = None;
+
+        if let Some(structure) = &self.structure {
+            let name = structure.name.clone();
+            let start = structure.start;
+
+            self.structure = structure::load(&name).ok().map(|definition| {
+                definition.apply(&name, &self.editor.buffer, start, self.config.endianness)
+            });
+        }
+
+        self.highlight_file();
+        self.message = Some(format!("reloaded {}", self.editor.filename));
+    }
+
+    /// Highlights the fields of the structure and what the highlight rules and the highlighters of plugins find in
+    /// the file.
+    fn highlight_file(&mut self) {
+        if let Some(structure) = &self.structure {
+            self.highlights
+                .add_striped(STRUCTURE_LAYER, structure.field_ranges());
+        }
 
-                (x, y)
+        self.highlights
+            .add_rules(&self.config.highlight_rules, &self.editor.buffer);
+
+        for highlighter in self.plugins.highlighters() {
+            let ranges = highlighter.highlight(&self.editor.buffer);
+
+            if !ranges.is_empty() {
+                self.highlights.add(highlighter.name(), ranges);
             }
-            EditorMode::TextMode => {
-                let x = 63 + (nibble_wo_offset % (2 * BYTES_PER_ROW)) / 2;
-                let y = 3 + nibble_wo_offset / (2 * BYTES_PER_ROW);
+        }
+    }
+
+    /// Collects the results of the running search, jumping to the first match once it is known.
+    fn receive_search_results(&mut self) -> Result<(), io::Error> {
+        let Some(job) = &mut self.search_job else {
+            return Ok(());
+        };
+
+        let selected = job.receive(&mut self.search_results);
+        let finished = job.is_finished();
 
-                (x, y)
+        if let Some((position, wrapped)) = selected {
+            self.jump_to_match(position, wrapped);
+        }
+
+        if finished {
+            self.search_job = None;
+
+            if self.search_results.is_none() {
+                self.message = Some(String::from("pattern not found"));
             }
         }
+
+        self.draw()
+    }
+
+    /// Jumps to the next match in the direction of the search, or in the opposite direction if `forward` isn't set.
+    fn next_match(&mut self, forward: bool) {
+        // more matches may still be found past the last one while the search runs
+        let wrap = self.config.wrap_search && self.search_job.is_none();
+
+        let Some(results) = &mut self.search_results else {
+            return;
+        };
+
+        let next = if forward {
+            results.advance(wrap)
+        } else {
+            results.retreat(wrap)
+        };
+
+        match next {
+            Some((position, wrapped)) => self.jump_to_match(position, wrapped),
+            None => self.message = Some(String::from("no more matches")),
+        }
+    }
+
+    fn jump_to_match(&mut self, position: usize, wrapped: bool) {
+        if wrapped && !self.config.wrap_search {
+            self.message = Some(String::from("no more matches"));
+            return;
+        }
+
+        self.jump_to(position);
+
+        if wrapped {
+            self.message = Some(String::from("search wrapped"));
+        }
+    }
+
+    /// Reads a pointer at the cursor and jumps to the offset it points to.
+    fn follow_pointer(&mut self) {
+        let cursor = self.editor.cursor_nibble / 2;
+        let size = self.config.pointer_size;
+
+        let Some(bytes) = self.editor.buffer.get(cursor..cursor + size) else {
+            self.message = Some(String::from("not enough bytes for a pointer"));
+            return;
+        };
+
+        let pointer = value::read_uint(bytes, self.config.endianness);
+        let offset = usize::try_from(pointer)
+            .ok()
+            .and_then(|pointer| pointer.checked_sub(self.config.pointer_base))
+            .filter(|&offset| offset < self.editor.file_size());
+
+        match offset {
+            Some(offset) => self.jump_to(offset),
+            None => self.message = Some(format!("pointer {:#x} is outside of the file", pointer)),
+        }
+    }
+
+    /// Moves the cursor to `position`, remembering the previous position in the jump list.
+    fn jump_to(&mut self, position: usize) {
+        self.jumps.push(self.editor.cursor_nibble / 2);
+        self.editor.cursor_nibble = 2 * position;
+    }
+
+    /// Puts the cursor on the row starting at `row_offset`, keeping its column unless the row is collapsed.
+    fn set_cursor_row(&mut self, row_offset: usize, col_nibble: usize) {
+        self.editor.cursor_nibble = match self.collapsed_run(row_offset) {
+            Some(run) => 2 * run.start,
+            None => 2 * row_offset + col_nibble,
+        };
+    }
+
+    fn bytes_per_row(&self) -> usize {
+        self.layout
+            .map_or(self.config.bytes_per_row, |layout| layout.bytes_per_row)
+    }
+
+    /// Returns where the terminal cursor is shown, given the screen row of the cursor or of the selected entry.
+    fn cursor_position(&self, layout: Layout, cursor_y: usize) -> (usize, usize) {
+        match self.screen_mode {
+            ScreenMode::EditMode => self.coords_for_cursor(layout, cursor_y),
+            ScreenMode::PanelMode => (0, cursor_y),
+            ScreenMode::CommandMode => (
+                self.prompt_prefix().width() + self.input_buffer.width(),
+                self.height - 1,
+            ),
+        }
+    }
+
+    fn coords_for_cursor(&self, layout: Layout, y: usize) -> (usize, usize) {
+        let cursor = self.editor.cursor_nibble / 2;
+        let col = cursor - self.row_start(cursor);
+
+        match (&self.editor_mode, layout.text_x) {
+            (EditorMode::TextMode, Some(text_x)) => (text_x + col, y),
+            // the text pane is hidden on narrow terminals, so text mode edits happen in the hex pane
+            _ => (layout.hex_col_x(col) + self.editor.cursor_nibble % 2, y),
+        }
     }
 }
 
 impl Drop for Screen {
     fn drop(&mut self) {
-        terminal::disable_raw_mode().unwrap();
-        execute!(self.stdout, cursor::SetCursorStyle::DefaultUserShape).unwrap();
-        execute!(self.stdout, terminal::LeaveAlternateScreen).unwrap();
+        // the panic hook has already restored the terminal, and doing it twice can hide the panic message
+        if !thread::panicking() {
+            restore_terminal().unwrap();
+        }
     }
 }
 
+/// Puts the terminal into the state the editor is drawn in.
+pub fn setup_terminal() -> Result<(), io::Error> {
+    terminal::enable_raw_mode()?;
+    execute!(
+        stdout(),
+        terminal::EnterAlternateScreen,
+        event::EnableBracketedPaste,
+        terminal::Clear(terminal::ClearType::All),
+        cursor::SetCursorStyle::SteadyBlock
+    )
+}
+
+/// Gives the terminal back to the shell, undoing [`setup_terminal`].
+pub fn restore_terminal() -> Result<(), io::Error> {
+    terminal::disable_raw_mode()?;
+    execute!(
+        stdout(),
+        event::DisableBracketedPaste,
+        cursor::SetCursorStyle::DefaultUserShape,
+        terminal::LeaveAlternateScreen
+    )
+}
+
 fn hex_char_to_u8(c: char) -> Option<u8> {
     let i = c as u8;
 
     match c {
         '0'..='9' => Some(i - b'0'),
         'a'..='f' => Some(i - b'a' + 10),
+        'A'..='F' => Some(i - b'A' + 10),
         _ => None,
     }
 }
+
+/// Cuts `s` so that it is at most `width` columns wide.
+fn truncate_to_width(s: &mut String, width: usize) {
+    let mut total = 0;
+
+    for (i, c) in s.char_indices() {
+        total += c.width().unwrap_or(0);
+
+        if total > width {
+            s.truncate(i);
+            return;
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::{cell::RefCell, cmp, ops::Range, rc::Rc, sync::Arc};
+
+use rhai::{Array, Blob, Dynamic, Engine, EvalAltResult};
+
+use crate::{editor::FileEditor, search::Pattern, value, value::Endianness};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// The file as seen by a running script, with the script's own writes applied.
+struct State {
+    buffer: Arc<Vec<u8>>,
+    cursor: usize,
+    /// The end of the selection the cursor isn't at, if there is a selection.
+    selection_anchor: Option<usize>,
+    endianness: Endianness,
+    /// Applied to the editor once the script finishes.
+    writes: Vec<(usize, Vec<u8>)>,
+}
+
+impl State {
+    fn range(&self, offset: i64, len: i64) -> ScriptResult<Range<usize>> {
+        let start = usize::try_from(offset).ok();
+        let end = start
+            .zip(usize::try_from(len).ok())
+            .and_then(|(start, len)| start.checked_add(len));
+
+        match (start, end) {
+            (Some(start), Some(end)) if end <= self.buffer.len() => Ok(start..end),
+            _ => Err(format!(
+                "{:#x}..{:#x} is outside the file",
+                offset,
+                offset.saturating_add(len)
+            )
+            .into()),
+        }
+    }
+
+    /// Returns the selected range of bytes, which runs from the anchor to the cursor like in the editor.
+    fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.selection_anchor?;
+        let end = cmp::min(cmp::max(anchor, self.cursor) + 1, self.buffer.len());
+
+        Some(cmp::min(anchor, self.cursor)..end).filter(|selection| !selection.is_empty())
+    }
+
+    fn write(&mut self, offset: i64, bytes: Vec<u8>) -> ScriptResult<()> {
+        let range = self.range(offset, bytes.len() as i64)?;
+
+        Arc::make_mut(&mut self.buffer)[range.clone()].copy_from_slice(&bytes);
+        self.writes.push((range.start, bytes));
+
+        Ok(())
+    }
+}
+
+fn parse_pattern(input: &str, endianness: Endianness) -> ScriptResult<Pattern> {
+    Pattern::parse(input, endianness)
+        .filter(|pattern| !pattern.is_empty())
+        .ok_or_else(|| format!("invalid pattern: {}", input).into())
+}
+
+/// Runs the rhai script `source` on the file open in `editor`, returning the lines it printed. The writes of the
+/// script are applied as a single change, and only if it finishes without an error. The selection runs from
+/// `selection_anchor` to the cursor, and is updated along with the cursor if the script selects something else.
+pub fn run(
+    editor: &mut FileEditor,
+    selection_anchor: &mut Option<usize>,
+    source: &str,
+    endianness: Endianness,
+) -> Result<Vec<String>, String> {
+    let state = Rc::new(RefCell::new(State {
+        buffer: editor.buffer.clone(),
+        cursor: editor.cursor_nibble / 2,
+        selection_anchor: *selection_anchor,
+        endianness,
+        writes: Vec::new(),
+    }));
+    let output = Rc::new(RefCell::new(Vec::new()));
+
+    let mut engine = Engine::new();
+    register_api(&mut engine, &state);
+
+    let printed = output.clone();
+    engine.on_print(move |s| printed.borrow_mut().push(s.to_owned()));
+
+    engine.run(source).map_err(|e| e.to_string())?;
+
+    let state = state.borrow();
+    editor
+        .apply_writes(&state.writes)
+        .map_err(|e| e.to_string())?;
+
+    if state.cursor != editor.cursor_nibble / 2 {
+        editor.cursor_nibble = 2 * state.cursor;
+    }
+
+    *selection_anchor = state.selection_anchor;
+
+    Ok(output.take())
+}
+
+fn register_api(engine: &mut Engine, state: &Rc<RefCell<State>>) {
+    let s = state.clone();
+    engine.register_fn("size", move || s.borrow().buffer.len() as i64);
+
+    let s = state.clone();
+    engine.register_fn("cursor", move || s.borrow().cursor as i64);
+
+    let s = state.clone();
+    engine.register_fn("set_cursor", move |offset: i64| -> ScriptResult<()> {
+        let mut state = s.borrow_mut();
+        state.cursor = state.range(offset, 1)?.start;
+        Ok(())
+    });
+
+    let s = state.clone();
+    engine.register_fn("selection", move || -> Dynamic {
+        match s.borrow().selection() {
+            Some(selection) => vec![
+                Dynamic::from(selection.start as i64),
+                Dynamic::from(selection.end as i64),
+            ]
+            .into(),
+            None => Dynamic::UNIT,
+        }
+    });
+
+    let s = state.clone();
+    engine.register_fn(
+        "set_selection",
+        move |offset: i64, len: i64| -> ScriptResult<()> {
+            let mut state = s.borrow_mut();
+
+            if len == 0 {
+                state.selection_anchor = None;
+                return Ok(());
+            }
+
+            let range = state.range(offset, len)?;
+            state.selection_anchor = Some(range.start);
+            state.cursor = range.end - 1;
+
+            Ok(())
+        },
+    );
+
+    let s = state.clone();
+    engine.register_fn("read", move |offset: i64, len: i64| -> ScriptResult<Blob> {
+        let state = s.borrow();
+        Ok(state.buffer[state.range(offset, len)?].to_vec())
+    });
+
+    let s = state.clone();
+    engine.register_fn(
+        "read_uint",
+        move |offset: i64, size: i64| -> ScriptResult<i64> {
+            let state = s.borrow();
+
+            if !matches!(size, 1 | 2 | 4 | 8) {
+                return Err(format!("invalid size: {}", size).into());
+            }
+
+            let bytes = &state.buffer[state.range(offset, size)?];
+            Ok(value::read_uint(bytes, state.endianness) as i64)
+        },
+    );
+
+    let s = state.clone();
+    engine.register_fn("write", move |offset: i64, bytes: Blob| {
+        s.borrow_mut().write(offset, bytes)
+    });
+
+    let s = state.clone();
+    engine.register_fn("write", move |offset: i64, byte: i64| -> ScriptResult<()> {
+        let byte = u8::try_from(byte).map_err(|_| format!("not a byte: {}", byte))?;
+        s.borrow_mut().write(offset, vec![byte])
+    });
+
+    let s = state.clone();
+    engine.register_fn(
+        "write_uint",
+        move |offset: i64, size: i64, value: i64| -> ScriptResult<()> {
+            let mut state = s.borrow_mut();
+            let bytes = match (size, state.endianness) {
+                (1 | 2 | 4 | 8, Endianness::Little) => {
+                    value.to_le_bytes()[..size as usize].to_vec()
+                }
+                (1 | 2 | 4 | 8, Endianness::Big) => {
+                    value.to_be_bytes()[8 - size as usize..].to_vec()
+                }
+                _ => return Err(format!("invalid size: {}", size).into()),
+            };
+
+            state.write(offset, bytes)
+        },
+    );
+
+    let s = state.clone();
+    engine.register_fn("find", move |pattern: &str| -> ScriptResult<Array> {
+        let state = s.borrow();
+        let pattern = parse_pattern(pattern, state.endianness)?;
+
+        Ok(pattern
+            .find_all(&state.buffer)
+            .into_iter()
+            .map(|offset| Dynamic::from(offset as i64))
+            .collect())
+    });
+
+    let s = state.clone();
+    engine.register_fn(
+        "find",
+        move |pattern: &str, from: i64| -> ScriptResult<i64> {
+            let state = s.borrow();
+            let pattern = parse_pattern(pattern, state.endianness)?;
+            let from = from.clamp(0, state.buffer.len() as i64) as usize;
+
+            Ok(pattern
+                .find_first(&state.buffer, from)
+                .map_or(-1, |offset| offset as i64))
+        },
+    );
+}
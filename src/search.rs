@@ -1,29 +1,400 @@
-use std::cmp::{self, Ordering};
+//! Searching a buffer for byte patterns, given as hex with wildcards, text or numbers, on a worker thread or at once.
 
+use aho_corasick::{AhoCorasick, MatchKind};
+use std::{
+    cmp::{self, Ordering},
+    sync::{
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+use crate::{
+    parse_number,
+    value::{self, Endianness},
+};
+
+/// The direction a search was started in. `n` continues in this direction and `N` goes the opposite way.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+/// The matches of a search and which of them is selected, which moves through them in the direction of the search.
 pub struct SearchResults {
     results: Vec<usize>,
     match_size: usize,
     i: usize,
+    direction: Direction,
 }
 
-pub fn search(buffer: &[u8], input: &str) -> Option<SearchResults> {
-    let bytes = if input.starts_with('/') {
-        Ok(input.strip_prefix('/').unwrap().as_bytes().to_vec())
-    } else {
-        hex::decode(input)
+/// Bytes to search for, where only the bits set in `mask` have to match, at offsets that are a multiple of `align`.
+pub struct Pattern {
+    bytes: Vec<u8>,
+    mask: Vec<u8>,
+    align: usize,
+}
+
+/// The buffer is searched in chunks of this size, so that results can be shown and the search cancelled while it runs.
+const CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// A search running on a worker thread, sending matches back one chunk at a time. Dropping the job stops the search
+/// after the chunk being searched.
+pub struct SearchJob {
+    receiver: Receiver<(Vec<usize>, usize)>,
+    match_size: usize,
+    direction: Direction,
+    /// The cursor position when the search started.
+    position: usize,
+    /// Everything before this offset has been searched.
+    scanned: usize,
+    selected: bool,
+    finished: bool,
+}
+
+impl SearchJob {
+    /// Starts searching `buffer` for `pattern`, which must not be empty.
+    pub fn start(
+        buffer: Arc<Vec<u8>>,
+        pattern: Pattern,
+        position: usize,
+        direction: Direction,
+    ) -> SearchJob {
+        let (sender, receiver) = mpsc::channel();
+        let match_size = pattern.len();
+
+        thread::spawn(move || {
+            let mut from = 0;
+
+            while from < buffer.len() {
+                let to = cmp::min(from + CHUNK_SIZE, buffer.len());
+                let results = pattern.find_in(&buffer, from, to);
+
+                from = results
+                    .last()
+                    .map_or(to, |&last| cmp::max(to, last + match_size));
+
+                // the job was dropped
+                if sender.send((results, from)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        SearchJob {
+            receiver,
+            match_size,
+            direction,
+            position,
+            scanned: 0,
+            selected: false,
+            finished: false,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Adds the matches found since the last call to `results`. Once enough of the buffer has been searched to know
+    /// which match comes first in the direction of the search, selects it and returns its offset, a single time,
+    /// along with whether the search wrapped around the file to find it.
+    pub fn receive(&mut self, results: &mut Option<SearchResults>) -> Option<(usize, bool)> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok((found, scanned)) => {
+                    self.scanned = scanned;
+
+                    match results {
+                        Some(results) => results.results.extend(found),
+                        None => {
+                            *results = SearchResults::new(found, self.match_size, self.direction)
+                        }
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    break;
+                }
+            }
+        }
+
+        if self.selected {
+            return None;
+        }
+
+        let results = results.as_mut()?;
+        let i = match self.direction {
+            Direction::Forward => {
+                let i = results.results.partition_point(|&x| x <= self.position);
+
+                if i < results.len() {
+                    i
+                } else if self.finished {
+                    0
+                } else {
+                    return None;
+                }
+            }
+            Direction::Backward => {
+                if self.scanned < self.position && !self.finished {
+                    return None;
+                }
+
+                match results.results.partition_point(|&x| x < self.position) {
+                    0 if self.finished => results.len() - 1,
+                    0 => return None,
+                    i => i - 1,
+                }
+            }
+        };
+
+        let wrapped = match self.direction {
+            Direction::Forward => results.results[i] <= self.position,
+            Direction::Backward => results.results[i] >= self.position,
+        };
+
+        self.selected = true;
+        results.i = i;
+
+        Some((results.result(), wrapped))
+    }
+}
+
+/// Finds all of `patterns` in a single pass, returning the offsets of the matches of each pattern. Where matches of
+/// different patterns overlap, the longest one is kept.
+pub fn find_many(buffer: &[u8], patterns: &[&[u8]]) -> Vec<Vec<usize>> {
+    let mut results = vec![Vec::new(); patterns.len()];
+
+    let Ok(automaton) = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostLongest)
+        .build(patterns)
+    else {
+        return results;
+    };
+
+    for m in automaton.find_iter(buffer) {
+        results[m.pattern().as_usize()].push(m.start());
+    }
+
+    results
+}
+
+/// Formats `offsets` in hex, one per line. If `context` is given, each offset is followed by the `match_len` bytes of
+/// the match in brackets, with up to `context` bytes before and after it.
+pub fn export(
+    buffer: &[u8],
+    offsets: &[usize],
+    match_len: usize,
+    context: Option<usize>,
+) -> String {
+    let to_hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(" ")
     };
 
-    if let Ok(bytes) = bytes {
-        let search_results = memchr::memmem::find_iter(buffer, &bytes).collect::<Vec<usize>>();
+    let mut output = String::new();
+
+    for &offset in offsets {
+        output += &format!("{:#010x}", offset);
+
+        if let Some(context) = context {
+            let end = cmp::min(offset + match_len, buffer.len());
+            let before = &buffer[offset.saturating_sub(context)..offset];
+            let after = &buffer[end..cmp::min(end + context, buffer.len())];
+
+            let parts = [
+                to_hex(before),
+                format!("[{}]", to_hex(&buffer[offset..end])),
+                to_hex(after),
+            ];
+
+            output += "  ";
+            output += &parts
+                .iter()
+                .filter(|part| !part.is_empty())
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" ");
+        }
+
+        output.push('\n');
+    }
+
+    output
+}
+
+impl Pattern {
+    /// Parses `/text` as text, `type:value` as a number encoded with `value::encode`, and anything else as hex digits
+    /// where `?` matches any nibble. Whitespace between hex digits is ignored. A `%n ` prefix only matches at offsets
+    /// that are a multiple of `n`.
+    pub fn parse(input: &str, endianness: Endianness) -> Option<Pattern> {
+        if let Some(input) = input.strip_prefix('%') {
+            let (align, input) = input.split_once(' ')?;
+            let mut pattern = Pattern::parse(input.trim_start(), endianness)?;
+            pattern.align = parse_number(align).filter(|&align| align > 0)?;
+
+            return Some(pattern);
+        }
+
+        if let Some(text) = input.strip_prefix('/') {
+            return Some(Pattern::exact(text.as_bytes().to_vec()));
+        }
+
+        if input.contains(':') {
+            return value::encode(input, endianness).map(Pattern::exact);
+        }
+
+        let digits = input
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match c {
+                '?' => Some((0, 0)),
+                _ => c.to_digit(16).map(|d| (d as u8, 0xf)),
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        if digits.is_empty() || digits.len() % 2 != 0 {
+            return None;
+        }
+
+        let (bytes, mask) = digits
+            .chunks(2)
+            .map(|pair| (pair[0].0 << 4 | pair[1].0, pair[0].1 << 4 | pair[1].1))
+            .unzip();
+
+        Some(Pattern {
+            bytes,
+            mask,
+            align: 1,
+        })
+    }
+
+    /// Returns a pattern matching exactly `bytes` at any offset.
+    pub fn exact(bytes: Vec<u8>) -> Pattern {
+        Pattern {
+            mask: vec![0xff; bytes.len()],
+            bytes,
+            align: 1,
+        }
+    }
+
+    /// The number of bytes a match takes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Whether the pattern matches nothing, which searching for isn't possible.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
 
-        SearchResults::new(search_results, bytes.len())
-    } else {
-        None
+    /// The alignment the offset of a match must have.
+    pub fn align(&self) -> usize {
+        self.align
+    }
+
+    /// The bytes of the pattern, if it has no wildcards.
+    pub fn exact_bytes(&self) -> Option<&[u8]> {
+        self.mask
+            .iter()
+            .all(|&m| m == 0xff)
+            .then_some(&self.bytes[..])
+    }
+
+    fn matches_at(&self, buffer: &[u8], offset: usize) -> bool {
+        buffer[offset..offset + self.len()]
+            .iter()
+            .zip(self.bytes.iter().zip(&self.mask))
+            .all(|(&b, (&p, &m))| b & m == p & m)
+    }
+
+    /// Returns the offsets of all non-overlapping matches in `buffer`.
+    pub fn find_all(&self, buffer: &[u8]) -> Vec<usize> {
+        self.find_in(buffer, 0, buffer.len())
+    }
+
+    /// Returns the offset of the first match in `buffer` that starts at or after `start`.
+    pub fn find_first(&self, buffer: &[u8], start: usize) -> Option<usize> {
+        (start..buffer.len()).step_by(CHUNK_SIZE).find_map(|from| {
+            let to = cmp::min(from + CHUNK_SIZE, buffer.len());
+            self.find_in(buffer, from, to).first().copied()
+        })
+    }
+
+    /// Returns the offsets of the non-overlapping matches in `buffer` that start between `start` and `end`.
+    pub fn find_in(&self, buffer: &[u8], start: usize, end: usize) -> Vec<usize> {
+        let len = self.len();
+
+        if len == 0 || len > buffer.len() {
+            return Vec::new();
+        }
+
+        // one past the last offset where a match can start
+        let stop = cmp::min(end, buffer.len() - len + 1);
+
+        if start >= stop {
+            return Vec::new();
+        }
+
+        let haystack = &buffer[start..stop + len - 1];
+        // alignment is relative to the start of the buffer, not the haystack
+        let aligned = |i: usize| (start + i).is_multiple_of(self.align);
+        let mut results = Vec::new();
+
+        // anchor the search on a byte without wildcards so that memchr can skip most of the buffer
+        match self.mask.iter().position(|&m| m == 0xff) {
+            Some(_) if self.mask.iter().all(|&m| m == 0xff) => {
+                let finder = memchr::memmem::Finder::new(&self.bytes);
+                let mut next = 0;
+
+                while let Some(i) = finder.find(&haystack[next..]).map(|i| next + i) {
+                    if aligned(i) {
+                        results.push(start + i);
+                        next = i + len;
+                    } else {
+                        next = i + 1;
+                    }
+                }
+            }
+            Some(anchor) => {
+                let last = haystack.len() - len;
+                let mut next = 0;
+
+                for i in memchr::memchr_iter(self.bytes[anchor], &haystack[anchor..=last + anchor])
+                {
+                    if i >= next && aligned(i) && self.matches_at(haystack, i) {
+                        results.push(start + i);
+                        next = i + len;
+                    }
+                }
+            }
+            None => {
+                let mut i = 0;
+
+                while i + len <= haystack.len() {
+                    if aligned(i) && self.matches_at(haystack, i) {
+                        results.push(start + i);
+                        i += len;
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+        }
+
+        results
     }
 }
 
 impl SearchResults {
-    fn new(results: Vec<usize>, match_size: usize) -> Option<SearchResults> {
+    fn new(results: Vec<usize>, match_size: usize, direction: Direction) -> Option<SearchResults> {
         if results.is_empty() {
             return None;
         }
@@ -32,6 +403,7 @@ impl SearchResults {
             results,
             match_size,
             i: 0,
+            direction,
         })
     }
 
@@ -52,24 +424,77 @@ impl SearchResults {
             .ok()
     }
 
+    /// The number of matches found.
     pub fn len(&self) -> usize {
         self.results.len()
     }
 
-    pub fn next(&mut self) -> usize {
-        self.i = cmp::min(self.len() - 1, self.i + 1);
-        self.results[self.i]
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
     }
 
-    pub fn prev(&mut self) -> usize {
-        self.i = self.i.saturating_sub(1);
-        self.results[self.i]
+    /// Moves to the next match, going back to the first one after the last if `wrap` is set. Returns the offset of
+    /// the match and whether it wrapped, or `None` if there are no more matches.
+    pub fn next(&mut self, wrap: bool) -> Option<(usize, bool)> {
+        if self.i + 1 < self.len() {
+            self.i += 1;
+        } else if wrap {
+            self.i = 0;
+        } else {
+            return None;
+        }
+
+        Some((self.results[self.i], self.i == 0))
+    }
+
+    /// Moves to the previous match, like `next`.
+    pub fn prev(&mut self, wrap: bool) -> Option<(usize, bool)> {
+        if self.i > 0 {
+            self.i -= 1;
+        } else if wrap {
+            self.i = self.len() - 1;
+        } else {
+            return None;
+        }
+
+        Some((self.results[self.i], self.i == self.len() - 1))
+    }
+
+    /// Moves to the next match in the direction of the search.
+    pub fn advance(&mut self, wrap: bool) -> Option<(usize, bool)> {
+        match self.direction {
+            Direction::Forward => self.next(wrap),
+            Direction::Backward => self.prev(wrap),
+        }
+    }
+
+    /// Moves to the next match against the direction of the search.
+    pub fn retreat(&mut self, wrap: bool) -> Option<(usize, bool)> {
+        match self.direction {
+            Direction::Forward => self.prev(wrap),
+            Direction::Backward => self.next(wrap),
+        }
+    }
+
+    /// The offsets of the matches, sorted.
+    pub fn results(&self) -> &[usize] {
+        &self.results
+    }
+
+    pub fn match_size(&self) -> usize {
+        self.match_size
+    }
+
+    pub fn select(&mut self, i: usize) {
+        self.i = cmp::min(i, self.len() - 1);
     }
 
+    /// The offset of the selected match.
     pub fn result(&self) -> usize {
         self.results[self.i]
     }
 
+    /// The index of the selected match.
     pub fn idx(&self) -> usize {
         self.i
     }
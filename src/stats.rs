@@ -0,0 +1,59 @@
+use std::cmp;
+
+/// Block size used by `:entropy` when none is given.
+pub const DEFAULT_BLOCK_SIZE: usize = 0x1000;
+
+/// How many of the most common byte values `:histogram` names when not told otherwise.
+pub const DEFAULT_TOP: usize = 8;
+
+/// Returns how many times each byte value occurs in `bytes`.
+pub fn byte_counts(bytes: &[u8]) -> [usize; 256] {
+    let mut counts = [0; 256];
+
+    for &byte in bytes {
+        counts[usize::from(byte)] += 1;
+    }
+
+    counts
+}
+
+/// Returns the Shannon entropy of `bytes` in bits per byte, from 0 for a single repeated value to 8 for random data.
+pub fn entropy(bytes: &[u8]) -> f64 {
+    let len = bytes.len() as f64;
+
+    byte_counts(bytes)
+        .into_iter()
+        .filter(|&count| count > 0)
+        .map(|count| {
+            let p = count as f64 / len;
+            p * (1.0 / p).log2()
+        })
+        .sum()
+}
+
+/// Returns the `n` most common byte values in `counts`, most common first, leaving out values that don't occur.
+pub fn most_common(counts: &[usize; 256], n: usize) -> Vec<u8> {
+    let mut values: Vec<u8> = (0..=255)
+        .filter(|&value| counts[usize::from(value)] > 0)
+        .collect();
+
+    // stable, so that equally common values stay in order
+    values.sort_by_key(|&value| cmp::Reverse(counts[usize::from(value)]));
+    values.truncate(n);
+    values
+}
+
+/// Draws `value` out of `max` as a bar `width` columns long at most, using eighths of a block for the last column.
+pub fn bar(value: f64, max: f64, width: usize) -> String {
+    const EIGHTHS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+    let eighths = (value / max * (width * 8) as f64).round() as usize;
+    let mut bar = "█".repeat(eighths / 8);
+
+    match eighths % 8 {
+        0 => {}
+        partial => bar.push(EIGHTHS[partial]),
+    }
+
+    bar
+}
@@ -0,0 +1,518 @@
+//! Reading the fields of a structure out of a buffer, as given by a definition in TOML, a Kaitai Struct definition or
+//! an 010 Editor binary template.
+
+use std::{collections::HashMap, fs, ops::Range};
+
+use serde::Deserialize;
+
+use crate::{
+    binary_template, config_file, expr, kaitai, parse_number,
+    value::{self, Endianness},
+};
+
+/// How deep structures can be nested, which stops structures that contain themselves.
+const MAX_DEPTH: usize = 32;
+
+/// Most elements shown in the value of an array of numbers.
+const MAX_ARRAY_VALUES: usize = 16;
+
+/// Most fields read from a structure, which stops arrays running to the end of the file from reading garbage for too
+/// long.
+const MAX_NODES: usize = 100_000;
+
+/// Names of the built-in definitions, for completion.
+pub const NAMES: [&str; 6] = ["elf", "fat", "mbr", "pe", "png", "zip"];
+
+/// Definitions that come with hexzen, used when there is no file of the same name in the `structs` directory.
+pub const BUILTIN: [(&str, &str); 6] = [
+    ("elf", include_str!("structs/elf.toml")),
+    ("fat", include_str!("structs/fat.toml")),
+    ("mbr", include_str!("structs/mbr.toml")),
+    ("pe", include_str!("structs/pe.toml")),
+    ("png", include_str!("structs/png.toml")),
+    ("zip", include_str!("structs/zip.toml")),
+];
+
+/// A structure definition read from a TOML file: the fields of the structure, and the structures its fields can be
+/// made of.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Definition {
+    pub(crate) endian: Option<Endianness>,
+    pub(crate) fields: Vec<Field>,
+    #[serde(default)]
+    pub(crate) structs: HashMap<String, Struct>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Struct {
+    /// The byte order of the fields, inherited from the structure containing this one if not given.
+    pub(crate) endian: Option<Endianness>,
+    pub(crate) fields: Vec<Field>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Field {
+    pub(crate) name: String,
+    /// `u8` to `u64`, `i8` to `i64`, `f32`, `f64`, `char` or the name of a structure in `structs`.
+    #[serde(rename = "type")]
+    pub(crate) ty: String,
+    /// Makes the field an array of this many elements, or of as many as fit before the end of the file if `*`.
+    pub(crate) count: Option<Expr>,
+    /// Where the field starts, counted from the start of the outermost structure, instead of after the previous field.
+    pub(crate) offset: Option<Expr>,
+    /// How many bytes a structure, or each element of an array of structures, takes whatever its fields read.
+    pub(crate) size: Option<Expr>,
+    /// Leaves the field out if the expression is 0.
+    #[serde(rename = "if")]
+    pub(crate) condition: Option<String>,
+    pub(crate) endian: Option<Endianness>,
+    /// Names for values of an integer field, keyed by the value written like in expressions.
+    #[serde(default)]
+    pub(crate) values: HashMap<String, String>,
+}
+
+/// A number, or an expression that can use the values of the fields before it.
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Expr {
+    Number(usize),
+    Text(String),
+}
+
+impl Expr {
+    /// Returns the expression as text, writing a number in hex like expressions do.
+    pub(crate) fn into_text(self) -> String {
+        match self {
+            Expr::Number(number) => format!("{:#x}", number),
+            Expr::Text(text) => text,
+        }
+    }
+}
+
+/// A field found when applying a structure, or an element of an array field.
+pub struct Node {
+    pub range: Range<usize>,
+    /// How many structures and arrays the field is inside.
+    pub depth: usize,
+    pub name: String,
+    /// The names of the structures the field is inside followed by its own, separated by dots.
+    pub path: String,
+    /// `None` for structures and arrays of structures, whose fields follow as nodes of their own.
+    pub value: Option<String>,
+}
+
+/// The fields of a structure applied to a buffer, in the order they were read.
+pub struct Structure {
+    pub name: String,
+    /// The offset the structure was applied at.
+    pub start: usize,
+    pub nodes: Vec<Node>,
+    /// Why reading stopped early, if it did.
+    pub error: Option<String>,
+}
+
+impl Structure {
+    /// Returns the innermost field with a value that holds `offset`.
+    pub fn field_at(&self, offset: usize) -> Option<&Node> {
+        self.nodes
+            .iter()
+            .filter(|node| node.value.is_some() && node.range.contains(&offset))
+            .max_by_key(|node| node.depth)
+    }
+
+    /// Returns whether the fields of a structure or the elements of an array follow the node at index `node`.
+    pub fn has_children(&self, node: usize) -> bool {
+        self.nodes
+            .get(node + 1)
+            .is_some_and(|child| child.depth > self.nodes[node].depth)
+    }
+
+    /// Returns the index of the structure or array the node at index `node` is inside.
+    pub fn parent(&self, node: usize) -> Option<usize> {
+        let depth = self.nodes[node].depth;
+        self.nodes[..node]
+            .iter()
+            .rposition(|parent| parent.depth < depth)
+    }
+
+    /// Returns the ranges of the fields with a value, sorted by offset and without overlaps.
+    pub fn field_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = self
+            .nodes
+            .iter()
+            .filter(|node| node.value.is_some() && !node.range.is_empty())
+            .map(|node| node.range.clone())
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+
+        let mut end = 0;
+        ranges.retain(|range| {
+            let keep = range.start >= end;
+            end = end.max(range.end);
+            keep
+        });
+
+        ranges
+    }
+}
+
+/// Extensions of the definition files looked for in the `structs` directory, in order.
+const EXTENSIONS: [&str; 3] = ["toml", "ksy", "bt"];
+
+/// Reads the definition at `name` if it is a path, or else the one named `name.toml`, `name.ksy` or `name.bt` in the
+/// `structs` directory next to the config file or the built-in one of that name. Files ending in `.ksy` are read as
+/// Kaitai Struct definitions and those ending in `.bt` as 010 Editor binary templates.
+pub fn load(name: &str) -> Result<Definition, String> {
+    let path = if name.contains(['/', '.']) {
+        name.into()
+    } else {
+        let path = config_file::structs_dir().map(|dir| {
+            EXTENSIONS
+                .iter()
+                .map(|extension| dir.join(format!("{}.{}", name, extension)))
+                .find(|path| path.exists())
+                .unwrap_or_else(|| dir.join(format!("{}.toml", name)))
+        });
+
+        match (path, BUILTIN.iter().find(|(builtin, _)| *builtin == name)) {
+            (Some(path), _) if path.exists() => path,
+            (_, Some((_, source))) => {
+                return toml::from_str(source).map_err(|e| format!("{}: {}", name, e))
+            }
+            (Some(path), None) => path,
+            (None, None) => return Err(format!("no structure named {}", name)),
+        }
+    };
+
+    let source = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let definition = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("ksy") => kaitai::parse(&source),
+        Some("bt") => binary_template::parse(&source),
+        _ => toml::from_str(&source).map_err(|e| e.to_string()),
+    };
+
+    definition.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+impl Definition {
+    /// Reads the fields of the structure starting at `start` in `buffer`, with `endianness` as the byte order unless
+    /// the definition gives one. Fields read before an error are kept.
+    pub fn apply(
+        &self,
+        name: &str,
+        buffer: &[u8],
+        start: usize,
+        endianness: Endianness,
+    ) -> Structure {
+        let mut reader = Reader {
+            definition: self,
+            buffer,
+            start,
+            nodes: Vec::new(),
+            values: HashMap::new(),
+        };
+        let error = reader
+            .read_fields(&self.fields, self.endian, start, 0, "", endianness)
+            .err();
+
+        Structure {
+            name: name.to_owned(),
+            start,
+            nodes: reader.nodes,
+            error,
+        }
+    }
+}
+
+struct Reader<'a> {
+    definition: &'a Definition,
+    buffer: &'a [u8],
+    /// Where the outermost structure starts.
+    start: usize,
+    nodes: Vec<Node>,
+    /// The value of the integer field of each name read last, which expressions can use.
+    values: HashMap<String, u64>,
+}
+
+impl Reader<'_> {
+    /// Reads `fields`, in the byte order `endian` if given, starting at `position` and returns where they end.
+    fn read_fields(
+        &mut self,
+        fields: &[Field],
+        endian: Option<Endianness>,
+        mut position: usize,
+        depth: usize,
+        path: &str,
+        endianness: Endianness,
+    ) -> Result<usize, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("structures nested too deep at {}", path));
+        }
+
+        let endianness = endian.unwrap_or(endianness);
+
+        for field in fields {
+            if let Some(condition) = &field.condition {
+                if self.evaluate(condition)? == 0 {
+                    continue;
+                }
+            }
+
+            if let Some(offset) = &field.offset {
+                position = self
+                    .start
+                    .checked_add(self.size(offset)?)
+                    .ok_or("offset out of range")?;
+            }
+
+            if self.nodes.len() >= MAX_NODES {
+                return Err(format!("stopped after {} fields", MAX_NODES));
+            }
+
+            position = self.read_field(field, position, depth, path, endianness)?;
+        }
+
+        Ok(position)
+    }
+
+    /// Reads `field` starting at `position` and returns where it ends.
+    fn read_field(
+        &mut self,
+        field: &Field,
+        position: usize,
+        depth: usize,
+        path: &str,
+        endianness: Endianness,
+    ) -> Result<usize, String> {
+        let endianness = field.endian.unwrap_or(endianness);
+        let path = match path {
+            "" => field.name.clone(),
+            path => format!("{}.{}", path, field.name),
+        };
+
+        if let Some(strukt) = self.definition.structs.get(&field.ty) {
+            let size = field
+                .size
+                .as_ref()
+                .map(|size| self.size(size))
+                .transpose()?;
+
+            let Some(count) = &field.count else {
+                self.nodes.push(Node {
+                    range: position..position,
+                    depth,
+                    name: field.name.clone(),
+                    path: path.clone(),
+                    value: None,
+                });
+                let i = self.nodes.len() - 1;
+                let end = self.read_fields(
+                    &strukt.fields,
+                    strukt.endian,
+                    position,
+                    depth + 1,
+                    &path,
+                    endianness,
+                )?;
+                let end = self.sized_end(position, end, size, &path)?;
+                self.nodes[i].range.end = end;
+
+                return Ok(end);
+            };
+
+            // the count of an array of structures running to the end of the file isn't known until it gets there
+            let count = match count {
+                Expr::Text(text) if text.trim() == "*" => None,
+                count => Some(self.size(count)?),
+            };
+
+            self.nodes.push(Node {
+                range: position..position,
+                depth,
+                name: field.name.clone(),
+                path: path.clone(),
+                value: None,
+            });
+            let array = self.nodes.len() - 1;
+            let mut end = position;
+            let mut i = 0;
+
+            while count.map_or(end < self.buffer.len(), |count| i < count) {
+                let name = format!("{}[{}]", field.name, i);
+                let element_path = format!("{}[{}]", path, i);
+
+                self.nodes.push(Node {
+                    range: end..end,
+                    depth: depth + 1,
+                    name,
+                    path: element_path.clone(),
+                    value: None,
+                });
+                let element = self.nodes.len() - 1;
+                let element_end = self.read_fields(
+                    &strukt.fields,
+                    strukt.endian,
+                    end,
+                    depth + 2,
+                    &element_path,
+                    endianness,
+                )?;
+                let element_end = self.sized_end(end, element_end, size, &element_path)?;
+                self.nodes[element].range.end = element_end;
+
+                // an empty element would repeat forever
+                if element_end == end && count.is_none() {
+                    break;
+                }
+
+                end = element_end;
+                i += 1;
+                self.nodes[array].range.end = end;
+            }
+
+            return Ok(end);
+        }
+
+        if field.size.is_some() {
+            return Err(format!("{} has a size but isn't a structure", path));
+        }
+
+        let size = match field.ty.as_str() {
+            "u8" | "i8" | "char" => 1,
+            "u16" | "i16" => 2,
+            "u32" | "i32" | "f32" => 4,
+            "u64" | "i64" | "f64" => 8,
+            ty => return Err(format!("unknown type {} of {}", ty, path)),
+        };
+        let count = match &field.count {
+            Some(Expr::Text(text)) if text.trim() == "*" => {
+                self.buffer.len().saturating_sub(position) / size
+            }
+            Some(count) => self.size(count)?,
+            None => 1,
+        };
+        let end = count
+            .checked_mul(size)
+            .and_then(|len| position.checked_add(len))
+            .filter(|&end| end <= self.buffer.len())
+            .ok_or_else(|| format!("{} runs past the end of the file", path))?;
+
+        let bytes = &self.buffer[position..end];
+        let value = match (field.ty.as_str(), &field.count) {
+            ("char", _) => format!("{:?}", String::from_utf8_lossy(bytes)),
+            (ty, None) => {
+                let value = value::read_uint(bytes, endianness);
+
+                if ty.starts_with('f') {
+                    format_value(ty, value)
+                } else {
+                    self.values.insert(field.name.clone(), value);
+
+                    let name = field
+                        .values
+                        .iter()
+                        .find(|(key, _)| parse_number(key).is_some_and(|key| key as u64 == value));
+
+                    match name {
+                        Some((_, name)) => format!("{} {}", format_value(ty, value), name),
+                        None => format_value(ty, value),
+                    }
+                }
+            }
+            ("u8", Some(_)) => {
+                let hex: Vec<String> = bytes
+                    .iter()
+                    .take(MAX_ARRAY_VALUES)
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect();
+                let more = if count > MAX_ARRAY_VALUES { " ..." } else { "" };
+
+                format!("{}{}", hex.join(" "), more)
+            }
+            (ty, Some(_)) => {
+                let values: Vec<String> = bytes
+                    .chunks(size)
+                    .take(MAX_ARRAY_VALUES)
+                    .map(|bytes| match value::read_uint(bytes, endianness) {
+                        // the hex of every element would make the list hard to read
+                        value if ty.starts_with('u') => value.to_string(),
+                        value => format_value(ty, value),
+                    })
+                    .collect();
+                let more = if count > MAX_ARRAY_VALUES {
+                    ", ..."
+                } else {
+                    ""
+                };
+
+                format!("[{}{}]", values.join(", "), more)
+            }
+        };
+
+        self.nodes.push(Node {
+            range: position..end,
+            depth,
+            name: field.name.clone(),
+            path,
+            value: Some(value),
+        });
+
+        Ok(end)
+    }
+
+    /// Returns where a structure starting at `start` whose fields end at `end` ends, which is `size` bytes on if given.
+    fn sized_end(
+        &self,
+        start: usize,
+        end: usize,
+        size: Option<usize>,
+        path: &str,
+    ) -> Result<usize, String> {
+        match size {
+            Some(size) => start
+                .checked_add(size)
+                .filter(|&end| end <= self.buffer.len())
+                .ok_or_else(|| format!("{} runs past the end of the file", path)),
+            None => Ok(end),
+        }
+    }
+
+    /// Evaluates `input` with the values of the integer fields read so far.
+    fn evaluate(&self, input: &str) -> Result<i128, String> {
+        let lookup = |name: &str| {
+            self.values
+                .get(name)
+                .and_then(|&value| usize::try_from(value).ok())
+        };
+
+        expr::evaluate(input, &lookup)
+    }
+
+    fn size(&self, expr: &Expr) -> Result<usize, String> {
+        match expr {
+            Expr::Number(number) => Ok(*number),
+            Expr::Text(text) => {
+                let value = self.evaluate(text)?;
+                usize::try_from(value).map_err(|_| format!("invalid size {} from {}", value, text))
+            }
+        }
+    }
+}
+
+/// Formats a value read as an unsigned integer as the type `ty`.
+fn format_value(ty: &str, value: u64) -> String {
+    match ty {
+        "f32" => format!("{:?}", f32::from_bits(value as u32)),
+        "f64" => format!("{:?}", f64::from_bits(value)),
+        _ if ty.starts_with('i') => {
+            // sign-extend from the size of the value
+            let shift = 64 - ty[1..].parse::<u32>().unwrap_or(64);
+            (((value << shift) as i64) >> shift).to_string()
+        }
+        _ => format!("{} ({:#x})", value, value),
+    }
+}
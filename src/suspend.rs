@@ -0,0 +1,32 @@
+use std::{io, thread};
+
+use signal_hook::{
+    consts::{SIGTSTP, SIGWINCH},
+    iterator::Signals,
+    low_level,
+};
+
+use crate::screen;
+
+/// Restores the terminal before the process is stopped by SIGTSTP, and sets it up again once it is continued.
+pub fn handle_signals() -> Result<(), io::Error> {
+    let mut signals = Signals::new([SIGTSTP])?;
+
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            let _ = screen::restore_terminal();
+            // only returns once the process is continued
+            let _ = low_level::emulate_default_handler(SIGTSTP);
+            let _ = screen::setup_terminal();
+            // the editor redraws everything when the terminal is resized
+            let _ = low_level::raise(SIGWINCH);
+        }
+    });
+
+    Ok(())
+}
+
+/// Stops the process like Ctrl+Z does in a shell.
+pub fn suspend() -> Result<(), io::Error> {
+    low_level::raise(SIGTSTP)
+}
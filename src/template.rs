@@ -0,0 +1,493 @@
+//! Layouts for the dump given by format units like those of `hexdump -e`.
+
+use std::{
+    io::{self, Read, Write},
+    iter::Peekable,
+    mem,
+    str::Chars,
+};
+
+use crate::{
+    dump::read_full,
+    value::{self, Endianness},
+};
+
+/// A layout for the dump made of format units like those of `hexdump -e`, e.g. `"%08_ax  " 16/1 "%02x " "\n"`. Each
+/// unit is an optional iteration count and byte count followed by a quoted printf-like format, which is repeated that
+/// many times, its conversions reading units of that many bytes.
+#[derive(Clone, Debug)]
+pub struct Template {
+    units: Vec<Unit>,
+}
+
+#[derive(Clone, Debug)]
+struct Unit {
+    iterations: usize,
+    pieces: Vec<Piece>,
+}
+
+#[derive(Clone, Debug)]
+enum Piece {
+    Text(String),
+    Conversion(Conversion),
+}
+
+#[derive(Clone, Debug)]
+struct Conversion {
+    flags: String,
+    width: usize,
+    precision: Option<usize>,
+    kind: Kind,
+    /// How many bytes the conversion reads, 0 for offsets.
+    size: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    Signed,
+    Unsigned,
+    Octal,
+    Hex {
+        uppercase: bool,
+    },
+    /// One of `eEfgG`.
+    Float(char),
+    Char,
+    /// The offset of the next byte, written in decimal, octal or hex like `%d`, `%o` or `%x`.
+    Offset(char),
+}
+
+impl Template {
+    /// Parses format units like `-e` takes them.
+    pub fn parse(input: &str) -> Result<Template, String> {
+        let mut chars = input.chars().peekable();
+        let mut units = Vec::new();
+
+        loop {
+            skip_whitespace(&mut chars);
+            if chars.peek().is_none() {
+                break;
+            }
+
+            let mut iterations = 1;
+            let mut byte_count = number(&mut chars);
+
+            if chars.next_if_eq(&'/').is_some() {
+                iterations = byte_count.ok_or("expected an iteration count before '/'")?;
+                byte_count = number(&mut chars);
+            }
+
+            skip_whitespace(&mut chars);
+            if chars.next() != Some('"') {
+                return Err("expected a quoted format".to_string());
+            }
+
+            let format = quoted(&mut chars)?;
+            let pieces = pieces(&format, byte_count)?;
+
+            units.push(Unit { iterations, pieces });
+        }
+
+        // a layout that reads nothing would be repeated forever
+        let reads = units.iter().any(|unit| {
+            unit.iterations > 0
+                && unit.pieces.iter().any(|piece| match piece {
+                    Piece::Conversion(conversion) => conversion.size > 0,
+                    Piece::Text(_) => false,
+                })
+        });
+
+        if !reads {
+            return Err("the format reads no bytes".to_string());
+        }
+
+        Ok(Template { units })
+    }
+
+    /// Returns how many bytes one pass of the layout reads.
+    fn block_size(&self) -> usize {
+        self.units
+            .iter()
+            .map(|unit| {
+                let size: usize = unit
+                    .pieces
+                    .iter()
+                    .map(|piece| match piece {
+                        Piece::Conversion(conversion) => conversion.size,
+                        Piece::Text(_) => 0,
+                    })
+                    .sum();
+                unit.iterations * size
+            })
+            .sum()
+    }
+
+    /// Writes a pass of the layout over `block`, which starts at `offset`. Conversions reached past the end of the
+    /// block are left blank, and a unit cut off by it is padded with zeros.
+    fn write_block(
+        &self,
+        out: &mut impl Write,
+        block: &[u8],
+        offset: usize,
+        endianness: Endianness,
+        replacement_char: char,
+    ) -> Result<(), io::Error> {
+        let mut position = 0;
+
+        for unit in &self.units {
+            for _ in 0..unit.iterations {
+                for piece in &unit.pieces {
+                    let conversion = match piece {
+                        Piece::Text(text) => {
+                            out.write_all(text.as_bytes())?;
+                            continue;
+                        }
+                        Piece::Conversion(conversion) => conversion,
+                    };
+
+                    let value = if conversion.size == 0 {
+                        Some((offset + position) as u64)
+                    } else if position < block.len() {
+                        let mut bytes = [0; 8];
+                        let end = block.len().min(position + conversion.size);
+                        bytes[..end - position].copy_from_slice(&block[position..end]);
+                        position += conversion.size;
+
+                        Some(value::read_uint(&bytes[..conversion.size], endianness))
+                    } else {
+                        None
+                    };
+
+                    write!(out, "{}", conversion.format(value, replacement_char))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes `reader`, which starts at `offset`, in the layouts of `templates`. Like with `hexdump -e`, each of them is
+/// applied to the same blocks of the input, as large as the most any of them reads in a pass.
+pub fn write(
+    templates: &[Template],
+    out: &mut impl Write,
+    mut reader: impl Read,
+    mut offset: usize,
+    endianness: Endianness,
+    replacement_char: char,
+) -> Result<(), io::Error> {
+    let block_size = templates
+        .iter()
+        .map(Template::block_size)
+        .max()
+        .unwrap_or(0);
+    let mut block = vec![0; block_size];
+
+    loop {
+        let len = read_full(&mut reader, &mut block)?;
+        if len == 0 {
+            return Ok(());
+        }
+
+        for template in templates {
+            template.write_block(out, &block[..len], offset, endianness, replacement_char)?;
+        }
+
+        offset += len;
+    }
+}
+
+impl Conversion {
+    /// Formats `value` like printf would, or leaves the conversion blank if there is none.
+    fn format(&self, value: Option<u64>, replacement_char: char) -> String {
+        let Some(value) = value else {
+            return format!("{:1$}", "", self.width);
+        };
+
+        let bits = 8 * self.size as u32;
+        let alternate = self.flags.contains('#');
+
+        let (sign, prefix, digits) = match self.kind {
+            Kind::Signed => {
+                // sign-extend from the size of the unit
+                let shift = 64 - bits;
+                let value = ((value << shift) as i64) >> shift;
+                let digits = self.precise(value.unsigned_abs().to_string());
+                (self.sign(value < 0), "", digits)
+            }
+            Kind::Unsigned => ("", "", self.precise(value.to_string())),
+            Kind::Octal => {
+                let prefix = if alternate && value != 0 { "0" } else { "" };
+                ("", prefix, self.precise(format!("{:o}", value)))
+            }
+            Kind::Hex { uppercase } => {
+                let prefix = match (alternate && value != 0, uppercase) {
+                    (false, _) => "",
+                    (true, false) => "0x",
+                    (true, true) => "0X",
+                };
+                let digits = match uppercase {
+                    true => format!("{:X}", value),
+                    false => format!("{:x}", value),
+                };
+                ("", prefix, self.precise(digits))
+            }
+            Kind::Offset(radix) => {
+                let digits = match radix {
+                    'd' => value.to_string(),
+                    'o' => format!("{:o}", value),
+                    _ => format!("{:x}", value),
+                };
+                ("", "", self.precise(digits))
+            }
+            Kind::Float(conversion) => {
+                let value = match self.size {
+                    4 => f32::from_bits(value as u32) as f64,
+                    _ => f64::from_bits(value),
+                };
+                let sign = self.sign(value.is_sign_negative() && !value.is_nan());
+                (
+                    sign,
+                    "",
+                    float(value.abs(), conversion, self.precision, alternate),
+                )
+            }
+            Kind::Char => {
+                let byte = value as u8;
+                let c = match byte {
+                    32..=126 => byte as char,
+                    _ => replacement_char,
+                };
+                return self.pad("", c.to_string(), false);
+            }
+        };
+
+        // zeros go between the sign and the digits, and aren't used for integers with a precision
+        let zeros = self.flags.contains('0')
+            && !self.flags.contains('-')
+            && (matches!(self.kind, Kind::Float(_)) || self.precision.is_none());
+
+        self.pad(&format!("{}{}", sign, prefix), digits, zeros)
+    }
+
+    fn sign(&self, negative: bool) -> &'static str {
+        if negative {
+            "-"
+        } else if self.flags.contains('+') {
+            "+"
+        } else if self.flags.contains(' ') {
+            " "
+        } else {
+            ""
+        }
+    }
+
+    /// Pads integer digits with zeros to the precision.
+    fn precise(&self, digits: String) -> String {
+        match self.precision {
+            Some(precision) => format!("{:0>1$}", digits, precision),
+            None => digits,
+        }
+    }
+
+    fn pad(&self, prefix: &str, digits: String, zeros: bool) -> String {
+        let len = prefix.chars().count() + digits.chars().count();
+        let padding = self.width.saturating_sub(len);
+
+        if self.flags.contains('-') {
+            format!("{}{}{:3$}", prefix, digits, "", padding)
+        } else if zeros {
+            format!("{}{}{}", prefix, "0".repeat(padding), digits)
+        } else {
+            format!("{:1$}{2}{3}", "", padding, prefix, digits)
+        }
+    }
+}
+
+/// Formats the non-negative `value` like the printf conversion `conversion`.
+fn float(value: f64, conversion: char, precision: Option<usize>, alternate: bool) -> String {
+    let uppercase = conversion.is_ascii_uppercase();
+    let precision = precision.unwrap_or(6);
+
+    let text = if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        "inf".to_string()
+    } else {
+        match conversion.to_ascii_lowercase() {
+            'f' => format!("{:.1$}", value, precision),
+            'e' => exponent(value, precision),
+            _ => {
+                // %g uses the shorter of %e and %f, decided by the exponent, without trailing zeros
+                let precision = precision.max(1);
+                let exp = exponent_of(value, precision - 1);
+
+                let text = if (-4..precision as i32).contains(&exp) {
+                    format!("{:.1$}", value, (precision as i32 - 1 - exp) as usize)
+                } else {
+                    exponent(value, precision - 1)
+                };
+
+                if alternate {
+                    text
+                } else {
+                    strip_zeros(&text)
+                }
+            }
+        }
+    };
+
+    match uppercase {
+        true => text.to_uppercase(),
+        false => text,
+    }
+}
+
+/// Formats `value` like `%e`, with a sign and at least two digits in the exponent.
+fn exponent(value: f64, precision: usize) -> String {
+    let text = format!("{:.1$e}", value, precision);
+    let (mantissa, exp) = text.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+
+    format!(
+        "{}e{}{:02}",
+        mantissa,
+        if exp < 0 { '-' } else { '+' },
+        exp.abs()
+    )
+}
+
+/// Returns the exponent `value` has when written with `precision` digits after the point, after rounding.
+fn exponent_of(value: f64, precision: usize) -> i32 {
+    let text = format!("{:.1$e}", value, precision);
+    text.split_once('e').unwrap().1.parse().unwrap()
+}
+
+/// Removes the trailing zeros after the point of a number written by `%g`, and the point if nothing is left after it.
+fn strip_zeros(text: &str) -> String {
+    let (number, exp) = match text.split_once('e') {
+        Some((number, exp)) => (number, format!("e{}", exp)),
+        None => (text, String::new()),
+    };
+
+    let number = match number.contains('.') {
+        true => number.trim_end_matches('0').trim_end_matches('.'),
+        false => number,
+    };
+
+    format!("{}{}", number, exp)
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn number(chars: &mut Peekable<Chars>) -> Option<usize> {
+    let mut number = None;
+
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        chars.next();
+        number = Some(number.unwrap_or(0) * 10 + digit as usize);
+    }
+
+    number
+}
+
+/// Reads a quoted string up to its closing quote, replacing escapes like `\n`.
+fn quoted(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut text = String::new();
+
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(text),
+            Some('\\') => text.push(match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('0') => '\0',
+                Some('\\') => '\\',
+                Some('"') => '"',
+                Some(c) => return Err(format!("unknown escape \\{}", c)),
+                None => return Err("unterminated format".to_string()),
+            }),
+            Some(c) => text.push(c),
+            None => return Err("unterminated format".to_string()),
+        }
+    }
+}
+
+/// Splits a format into text and conversions, which read `byte_count` bytes each if it is given.
+fn pieces(format: &str, byte_count: Option<usize>) -> Result<Vec<Piece>, String> {
+    let mut chars = format.chars().peekable();
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+            continue;
+        }
+
+        if chars.next_if_eq(&'%').is_some() {
+            text.push('%');
+            continue;
+        }
+
+        if !text.is_empty() {
+            pieces.push(Piece::Text(mem::take(&mut text)));
+        }
+
+        let mut flags = String::new();
+        while let Some(flag) = chars.next_if(|c| "-+ #0".contains(*c)) {
+            flags.push(flag);
+        }
+
+        let width = number(&mut chars).unwrap_or(0);
+        let precision = chars
+            .next_if_eq(&'.')
+            .map(|_| number(&mut chars).unwrap_or(0));
+
+        let (kind, sizes): (Kind, &[usize]) = match chars.next() {
+            Some('d' | 'i') => (Kind::Signed, &[4, 1, 2, 8]),
+            Some('u') => (Kind::Unsigned, &[4, 1, 2, 8]),
+            Some('o') => (Kind::Octal, &[4, 1, 2, 8]),
+            Some('x') => (Kind::Hex { uppercase: false }, &[4, 1, 2, 8]),
+            Some('X') => (Kind::Hex { uppercase: true }, &[4, 1, 2, 8]),
+            Some(c @ ('e' | 'E' | 'f' | 'g' | 'G')) => (Kind::Float(c), &[8, 4]),
+            Some('c') => (Kind::Char, &[1]),
+            Some('_') => match chars.next() {
+                Some('p') => (Kind::Char, &[1]),
+                Some('a') => {
+                    let radix = chars.next_if(|c| "dox".contains(*c)).unwrap_or('x');
+                    (Kind::Offset(radix), &[0])
+                }
+                Some(c) => return Err(format!("unknown conversion %_{}", c)),
+                None => return Err("unterminated conversion".to_string()),
+            },
+            Some(c) => return Err(format!("unknown conversion %{}", c)),
+            None => return Err("unterminated conversion".to_string()),
+        };
+
+        // the first size is the default, and offsets read nothing whatever the byte count
+        let size = match (kind, byte_count) {
+            (Kind::Offset(_), _) | (_, None) => sizes[0],
+            (_, Some(size)) if sizes.contains(&size) => size,
+            (_, Some(size)) => return Err(format!("a {}-byte unit can't be converted", size)),
+        };
+
+        pieces.push(Piece::Conversion(Conversion {
+            flags,
+            width,
+            precision,
+            kind,
+            size,
+        }));
+    }
+
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+
+    Ok(pieces)
+}
@@ -0,0 +1,124 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// The byte order values are read and written in.
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+/// Reads an unsigned integer from all of `bytes`, which must not be longer than 8 bytes.
+pub fn read_uint(bytes: &[u8], endianness: Endianness) -> u64 {
+    let mut buf = [0; 8];
+
+    match endianness {
+        Endianness::Little => {
+            buf[..bytes.len()].copy_from_slice(bytes);
+            u64::from_le_bytes(buf)
+        }
+        Endianness::Big => {
+            buf[8 - bytes.len()..].copy_from_slice(bytes);
+            u64::from_be_bytes(buf)
+        }
+    }
+}
+
+/// Encodes a value written as `type:value`, like `u32le:443`, `i16be:-2`, `f32:1.5` or `utf16:text`. Integer types
+/// are `u8` to `u64` and `i8` to `i64`, and can be written in hex with a `0x` prefix. Text can be encoded as `utf8`,
+/// `utf16` or `latin1`. The type can end with `le` or `be` to override `default_endianness`.
+pub fn encode(input: &str, default_endianness: Endianness) -> Option<Vec<u8>> {
+    let (ty, value) = input.split_once(':')?;
+    let (ty, endianness) = if let Some(ty) = ty.strip_suffix("le") {
+        (ty, Endianness::Little)
+    } else if let Some(ty) = ty.strip_suffix("be") {
+        (ty, Endianness::Big)
+    } else {
+        (ty, default_endianness)
+    };
+
+    // text is taken as is, including any whitespace around it
+    match ty {
+        "utf8" => return Some(value.as_bytes().to_vec()),
+        "utf16" => {
+            return Some(
+                value
+                    .encode_utf16()
+                    .flat_map(|unit| match endianness {
+                        Endianness::Little => unit.to_le_bytes(),
+                        Endianness::Big => unit.to_be_bytes(),
+                    })
+                    .collect(),
+            )
+        }
+        "latin1" => return value.chars().map(|c| u8::try_from(c).ok()).collect(),
+        _ => {}
+    }
+
+    let value = value.trim();
+
+    let mut bytes = match ty {
+        "f32" => value.parse::<f32>().ok()?.to_le_bytes().to_vec(),
+        "f64" => value.parse::<f64>().ok()?.to_le_bytes().to_vec(),
+        _ => {
+            let signed = ty.starts_with('i');
+            let bits = ty
+                .strip_prefix(['u', 'i'])?
+                .parse::<u32>()
+                .ok()
+                .filter(|bits| [8, 16, 32, 64].contains(bits))?;
+
+            let (negative, digits) = match value.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, value),
+            };
+            let magnitude = match digits.strip_prefix("0x") {
+                Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+                None => digits.parse::<i128>().ok()?,
+            };
+            let value = if negative { -magnitude } else { magnitude };
+
+            let (min, max) = if signed {
+                (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+            } else {
+                (0, (1 << bits) - 1)
+            };
+
+            if value < min || value > max {
+                return None;
+            }
+
+            (value as u64).to_le_bytes()[..bits as usize / 8].to_vec()
+        }
+    };
+
+    if endianness == Endianness::Big {
+        bytes.reverse();
+    }
+
+    Some(bytes)
+}
+
+/// Formats `value` in decimal, hex, octal and binary, followed by its bytes as text if they are all printable. Negative
+/// values are shown in the other bases as 64-bit two's complement.
+pub fn format_bases(value: i128) -> String {
+    let bits = match i64::try_from(value) {
+        Ok(value) if value < 0 => value as u64 as u128,
+        _ => value as u128,
+    };
+
+    let mut text = format!("{} {:#x} {:#o} {:#b}", value, bits, bits, bits);
+
+    let bytes = bits.to_be_bytes();
+    let bytes = &bytes[bytes
+        .iter()
+        .position(|&byte| byte != 0)
+        .unwrap_or(bytes.len())..];
+
+    if !bytes.is_empty() && bytes.iter().all(|byte| (32..=126).contains(byte)) {
+        text += &format!(" \"{}\"", String::from_utf8_lossy(bytes));
+    }
+
+    text
+}